@@ -1,7 +1,35 @@
+use std::{env, fs, path::PathBuf};
+
+/// Protobuf package of the service unless `ACTFLOW_PROTO_PACKAGE` names another one
+const DEFAULT_PROTO_PACKAGE: &str = "workflow";
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     built::write_built_file().expect("Failed to acquire build-time information");
+
+    // gateways serving several APIs may need the service namespaced, e.g. `actflow.v1`
+    println!("cargo:rerun-if-env-changed=ACTFLOW_PROTO_PACKAGE");
+    println!("cargo:rerun-if-changed=proto/workflow.proto");
+    let package = env::var("ACTFLOW_PROTO_PACKAGE").unwrap_or_else(|_| DEFAULT_PROTO_PACKAGE.to_owned());
+    if !package.split('.').all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')) {
+        return Err(format!("ACTFLOW_PROTO_PACKAGE {:?} is not a valid protobuf package", package).into());
+    }
+    let proto = fs::read_to_string("proto/workflow.proto")?;
+    let declaration = format!("package {};", DEFAULT_PROTO_PACKAGE);
+    if !proto.contains(&declaration) {
+        return Err("proto/workflow.proto does not declare the default package".into());
+    }
+    let proto_dir = PathBuf::from(env::var("OUT_DIR")?).join("proto");
+    fs::create_dir_all(&proto_dir)?;
+    fs::write(
+        proto_dir.join("workflow.proto"),
+        proto.replacen(&declaration, &format!("package {};", package), 1),
+    )?;
+    println!("cargo:rustc-env=ACTFLOW_PROTO_PACKAGE={}", package);
+
+    // the client is used by the integration tests
+    // event sinks write the events as JSON
     tonic_prost_build::configure()
-        .build_client(false) // only build server code
-        .compile_protos(&["proto/workflow.proto"], &["proto"])?;
+        .type_attribute(".", "#[derive(serde::Serialize)] #[serde(rename_all = \"snake_case\")]")
+        .compile_protos(&[proto_dir.join("workflow.proto")], &[proto_dir])?;
     Ok(())
 }
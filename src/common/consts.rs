@@ -1,8 +1,36 @@
 /// Default logging level for the system
-pub const DEFAULT_LOG_LEVEL: &'static str = "INFO";
+pub const DEFAULT_LOG_LEVEL: &str = "INFO";
 /// Default logging level for third-party libraries
-pub const DEFAULT_THIRD_PARTY_LOG_LEVEL: &'static str = "WARN";
+pub const DEFAULT_THIRD_PARTY_LOG_LEVEL: &str = "WARN";
 /// Default log file
-pub const DEFAULT_LOG_FILE: &'static str = "/var/log/prism/fluxon-engine/fluxon-engine.log";
+pub const DEFAULT_LOG_FILE: &str = "/var/log/prism/fluxon-engine/fluxon-engine.log";
+/// Log file keeping the log lines in memory instead, for tests asserting on them
+pub const MEMORY_LOG_FILE: &str = ":memory:";
+/// Default strftime format of the log line timestamps
+pub const DEFAULT_LOG_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.6f %:z";
 /// Default log retention days
 pub const DEFAULT_LOG_RETENTION: usize = 365;
+/// Default interval before the first listener bind retry in milliseconds
+pub const DEFAULT_BIND_RETRY_INTERVAL_MS: u64 = 500;
+/// Default time the engine has to become ready after startup in milliseconds
+pub const DEFAULT_ENGINE_READY_TIMEOUT_MS: u64 = 10_000;
+/// Default maximum size of a workflow model in bytes
+pub const DEFAULT_MAX_MODEL_BYTES: usize = 4 * 1024 * 1024;
+/// Default maximum size of the outputs reported in a `WorkflowSuccess` event in bytes
+pub const DEFAULT_MAX_CAPTURED_OUTPUT_BYTES: usize = 1024 * 1024;
+/// Default maximum number of events buffered for a slow `run_workflow` reader
+pub const DEFAULT_REPLAY_BUFFER_SIZE: usize = 1024;
+/// Default time a `run_workflow` client has to make room in its stream in milliseconds
+pub const DEFAULT_STREAM_SEND_TIMEOUT_MS: u64 = 10_000;
+/// Default maximum number of runs waiting for a free slot
+pub const DEFAULT_MAX_QUEUED_WORKFLOWS: usize = 100;
+/// Default number of started runs kept in the history
+pub const DEFAULT_HISTORY_SIZE: usize = 1024;
+/// Default maximum number of subscriber streams per workflow process
+pub const DEFAULT_MAX_SUBSCRIBERS_PER_WORKFLOW: usize = 16;
+/// Default RPCs exempt from the rate limit, the read-only ones
+pub const DEFAULT_RATE_LIMIT_EXEMPT_RPCS: [&str; 3] = ["GetVersion", "GetServerStats", "ListWorkflows"];
+/// Default OTLP/gRPC endpoint the spans of the runs are exported to
+pub const DEFAULT_OTEL_ENDPOINT: &str = "http://localhost:4317";
+/// Default service name the spans of the runs are reported under
+pub const DEFAULT_OTEL_SERVICE_NAME: &str = "actflow-server";
@@ -0,0 +1,33 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/// Returns the id the server reports itself under: the configured one, else the hostname, else a random id
+pub fn resolve_instance_id(configured: Option<&str>) -> String {
+    configured.filter(|id| !id.is_empty()).map(str::to_owned).or_else(hostname).unwrap_or_else(random_id)
+}
+
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    // the name is cut to the buffer and then not necessarily NUL-terminated, so the length is searched
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8(buf[..len].to_vec()).ok().filter(|name| !name.is_empty())
+}
+
+#[cfg(not(unix))]
+fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok().filter(|name| !name.is_empty())
+}
+
+/// Returns an id unique enough to tell the instances of a fleet apart
+fn random_id() -> String {
+    // every `RandomState` is seeded with fresh random keys
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    format!("actflow-{:016x}", hasher.finish())
+}
@@ -1,5 +1,7 @@
 pub mod consts;
+pub mod instance;
+pub mod pidfile;
 pub mod shutdown;
 mod version;
 
-pub use version::VersionInfo;
+pub use version::{VERSION_INFO, VersionInfo};
@@ -0,0 +1,72 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// A pid file holding the current process id, removed when dropped
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Writes the current process id to `path`
+    ///
+    /// Fails if the file already exists and points at a live process, a stale file is overwritten.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        if let Ok(contents) = fs::read_to_string(path)
+            && let Ok(pid) = contents.trim().parse::<u32>()
+            && pid != process::id()
+            && is_alive(pid)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "pid file {} points at running process {}, is the server already started?",
+                    path.display(),
+                    pid
+                ),
+            ));
+        }
+
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, format!("{}\n", process::id()))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Returns the path of the pid file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Checks whether a process with the given id is running
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // signal 0 only checks for existence, EPERM means the process exists but belongs to another user
+    unsafe { libc::kill(pid, 0) == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) }
+}
+
+/// Checks whether a process with the given id is running
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    false
+}
@@ -1,44 +1,93 @@
 use std::{
-    fmt::Debug,
+    fmt::{self, Debug, Display},
     future::Future,
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicBool, Ordering},
     },
 };
 
 use tokio::sync::Notify;
 
+/// Why a shutdown was initiated
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShutdownReason {
+    /// Shutdown requested programmatically
+    Manual,
+    /// Interrupted by SIGINT (Ctrl-C)
+    Sigint,
+    /// Terminated by SIGTERM
+    Sigterm,
+    /// An internal component failed irrecoverably
+    Fatal(String),
+}
+
+impl Display for ShutdownReason {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            ShutdownReason::Manual => write!(f, "manual"),
+            ShutdownReason::Sigint => write!(f, "SIGINT"),
+            ShutdownReason::Sigterm => write!(f, "SIGTERM"),
+            ShutdownReason::Fatal(err) => write!(f, "fatal error: {}", err),
+        }
+    }
+}
+
 /// Thread-safe shutdown coordinator
 #[derive(Clone)]
 pub struct Shutdown {
-    /// Tuple of (shutdown flag, notification mechanism)
-    /// Both wrapped in Arc for thread-safe sharing
-    inner: Arc<(AtomicBool, Notify)>,
+    /// Tuple of (shutdown flag, notification mechanism, shutdown reason)
+    /// All wrapped in Arc for thread-safe sharing
+    inner: Arc<(AtomicBool, Notify, Mutex<Option<ShutdownReason>>)>,
 }
 
 impl Shutdown {
     /// Creates a new shutdown coordinator
     pub fn new() -> Self {
         Self {
-            inner: Arc::new((AtomicBool::new(false), Notify::new())),
+            inner: Arc::new((AtomicBool::new(false), Notify::new(), Mutex::new(None))),
         }
     }
 
-    /// Initiates shutdown
+    /// Initiates shutdown with the `Manual` reason
     pub fn shutdown(&self) {
-        self.inner.0.swap(true, Ordering::Relaxed);
+        self.shutdown_with_reason(ShutdownReason::Manual);
+    }
+
+    /// Initiates shutdown, recording why it happened
+    ///
+    /// Only the first reason is kept if shutdown is initiated more than once.
+    pub fn shutdown_with_reason(
+        &self,
+        reason: ShutdownReason,
+    ) {
+        {
+            let mut current = self.inner.2.lock().unwrap();
+            if current.is_none() {
+                *current = Some(reason);
+            }
+        }
+        self.inner.0.store(true, Ordering::SeqCst);
         self.inner.1.notify_waiters();
     }
 
+    /// Returns the reason of the initiated shutdown, if any
+    pub fn reason(&self) -> Option<ShutdownReason> {
+        self.inner.2.lock().unwrap().clone()
+    }
+
     /// Resets the shutdown state
     pub fn reset(&self) {
-        self.inner.0.store(false, Ordering::Relaxed);
+        self.inner.2.lock().unwrap().take();
+        self.inner.0.store(false, Ordering::SeqCst);
     }
 
     /// Checks if shutdown has been initiated
     pub fn is_terminated(&self) -> bool {
-        self.inner.0.load(Ordering::Relaxed)
+        self.inner.0.load(Ordering::SeqCst)
     }
 
     /// Waits for shutdown to be initiated
@@ -46,10 +95,12 @@ impl Shutdown {
         let inner = self.inner.clone();
         async move {
             // Initial fast check
-            if !inner.0.load(Ordering::Relaxed) {
+            if !inner.0.load(Ordering::SeqCst) {
+                // `Notified` receives `notify_waiters` wakeups as soon as it is created,
+                // so it must exist before the flag is re-checked
                 let notify = inner.1.notified();
                 // Second check to avoid "missed wakeup" race conditions
-                if !inner.0.load(Ordering::Relaxed) {
+                if !inner.0.load(Ordering::SeqCst) {
                     notify.await;
                 }
             }
@@ -70,6 +121,9 @@ impl Debug for Shutdown {
         &self,
         f: &mut std::fmt::Formatter<'_>,
     ) -> std::fmt::Result {
-        f.debug_struct("Shutdown").field("is_terminated", &self.inner.0.load(Ordering::Relaxed)).finish()
+        f.debug_struct("Shutdown")
+            .field("is_terminated", &self.inner.0.load(Ordering::SeqCst))
+            .field("reason", &self.reason())
+            .finish()
     }
 }
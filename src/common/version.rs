@@ -2,6 +2,18 @@ use std::fmt;
 
 use chrono::{DateTime, Local};
 
+use crate::built_info;
+
+/// Build information of this binary
+pub const VERSION_INFO: &VersionInfo = &VersionInfo {
+    name: built_info::PKG_NAME,
+    version: built_info::PKG_VERSION,
+    branch: built_info::GIT_HEAD_REF,
+    commit_hash: built_info::GIT_COMMIT_HASH,
+    compiler: built_info::RUSTC_VERSION,
+    compile_time: built_info::BUILT_TIME_UTC,
+};
+
 #[derive(Debug)]
 pub struct VersionInfo {
     /// Name of the system component
@@ -1,9 +1,34 @@
-use std::{env, fs, path::Path};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use serde::Deserialize;
+use chrono::format::{Item, StrftimeItems};
+use http::HeaderValue;
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
 use thiserror::Error;
 
-use crate::common::consts::{DEFAULT_LOG_FILE, DEFAULT_LOG_LEVEL, DEFAULT_LOG_RETENTION, DEFAULT_THIRD_PARTY_LOG_LEVEL};
+use crate::common::consts::{
+    DEFAULT_BIND_RETRY_INTERVAL_MS, DEFAULT_ENGINE_READY_TIMEOUT_MS, DEFAULT_HISTORY_SIZE, DEFAULT_LOG_FILE, DEFAULT_LOG_LEVEL,
+    DEFAULT_LOG_RETENTION, DEFAULT_LOG_TIMESTAMP_FORMAT, DEFAULT_MAX_CAPTURED_OUTPUT_BYTES, DEFAULT_MAX_MODEL_BYTES,
+    DEFAULT_MAX_QUEUED_WORKFLOWS, DEFAULT_MAX_SUBSCRIBERS_PER_WORKFLOW, DEFAULT_OTEL_ENDPOINT, DEFAULT_OTEL_SERVICE_NAME,
+    DEFAULT_RATE_LIMIT_EXEMPT_RPCS, DEFAULT_REPLAY_BUFFER_SIZE, DEFAULT_STREAM_SEND_TIMEOUT_MS, DEFAULT_THIRD_PARTY_LOG_LEVEL,
+    MEMORY_LOG_FILE,
+};
+use crate::common::instance::resolve_instance_id;
+
+/// Setting names whose values are masked in [`Config::effective_settings`]
+const SECRET_SETTINGS: [&str; 3] = ["key-pem", "token", "password"];
+
+/// Replacement shown for the value of a secret setting
+const REDACTED: &str = "<redacted>";
+
+/// Maximum depth of `!include` chains, deeper chains are most likely an include cycle
+const MAX_INCLUDE_DEPTH: usize = 16;
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
@@ -11,77 +36,705 @@ pub enum ConfigError {
     ConfigFileEmpty,
     #[error("yaml config invalid: {0}")]
     YamlConfigInvalid(String),
+    #[error("failed to include {0}: {1}")]
+    IncludeFailed(String, String),
+    #[error("unknown settings {0}, set allow-unknown-settings to only warn about them")]
+    UnknownSettings(String),
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct Config {
+    /// Name the server reports in its logs, events and history, unset uses the hostname
+    ///
+    /// Without a hostname a random id is generated on startup.
+    pub instance_id: Option<String>,
     pub server: ServerConfig,
     pub log: LogConfig,
     pub async_worker_thread_number: u16,
+    pub diagnostics: DiagnosticsConfig,
+    /// Export a span of every run to an OpenTelemetry collector, unset disables tracing
+    ///
+    /// Only takes effect in builds with the `otel` feature, otherwise a warning is logged on startup.
+    pub otel: Option<OtelConfig>,
+    /// Only warn about settings the server does not know instead of refusing to load the config
+    ///
+    /// Lets a server load a config written for a newer version, the unknown settings end up in
+    /// [`Config::unknown_settings`].
+    pub allow_unknown_settings: bool,
+    /// Settings of the loaded config the server does not know, with where they were found
+    #[serde(skip)]
+    pub unknown_settings: Vec<String>,
 }
 
 impl Config {
+    /// Returns the configured instance id, else the hostname, else a random id
+    ///
+    /// The random id differs on every call, so the result should be resolved once and passed on.
+    pub fn resolve_instance_id(&self) -> String {
+        resolve_instance_id(self.instance_id.as_deref())
+    }
+
     /// Load configuration from a file path
+    ///
+    /// A value tagged `!include path` is replaced by the contents of that file, relative paths are
+    /// resolved against the directory of the including file.
     pub fn load_from_file<T: AsRef<Path>>(path: T) -> Result<Self, ConfigError> {
-        let contents = fs::read_to_string(path).map_err(|e| ConfigError::YamlConfigInvalid(e.to_string()))?;
-        Self::load(&contents)
+        Self::load_from_files(&[path])
+    }
+
+    /// Load configuration from several files, see [`Config::load_merged`] for the merge rules
+    ///
+    /// Includes are resolved as in [`Config::load_from_file`] before the files are merged.
+    pub fn load_from_files<T: AsRef<Path>>(paths: &[T]) -> Result<Self, ConfigError> {
+        let mut documents = Vec::new();
+        let mut sources = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            let contents =
+                fs::read_to_string(path).map_err(|e| ConfigError::YamlConfigInvalid(format!("{}: {}", path.display(), e)))?;
+            let base_dir = path.parent().unwrap_or(Path::new(""));
+            documents.extend(parse_document(&contents, base_dir)?);
+            sources.push((path.display().to_string(), contents));
+        }
+        Self::from_documents(documents, &sources)
     }
 
     /// Load configuration from a string
     pub fn load<C: AsRef<str>>(contents: C) -> Result<Self, ConfigError> {
-        let contents = contents.as_ref();
-        if contents.len() == 0 {
-            // parsing empty string leads to EOF error
-            Ok(Self::default())
-        } else {
-            let mut cfg: Self = serde_yaml::from_str(contents).map_err(|e| ConfigError::YamlConfigInvalid(e.to_string()))?;
-
-            if cfg.log.log_file.is_empty() {
-                cfg.log.log_file = DEFAULT_LOG_FILE.to_owned();
+        Self::load_merged(&[contents])
+    }
+
+    /// Load configuration from several YAML documents, later documents override earlier ones
+    ///
+    /// Mappings such as the `server` and `log` sections are merged key by key, so an overlay only
+    /// needs the keys it changes. Any other value, including lists like `server.listeners`, is
+    /// replaced as a whole. Includes are resolved against the working directory.
+    pub fn load_merged<C: AsRef<str>>(documents: &[C]) -> Result<Self, ConfigError> {
+        let mut values = Vec::new();
+        let mut sources = Vec::new();
+        for (i, contents) in documents.iter().enumerate() {
+            values.extend(parse_document(contents.as_ref(), Path::new(""))?);
+            sources.push((format!("document {}", i + 1), contents.as_ref().to_owned()));
+        }
+        Self::from_documents(values, &sources)
+    }
+
+    /// Merges the parsed documents and checks the resulting configuration
+    ///
+    /// `sources` holds the name and contents of every document, to tell where an unknown setting is.
+    fn from_documents(
+        documents: Vec<Value>,
+        sources: &[(String, String)],
+    ) -> Result<Self, ConfigError> {
+        let mut merged = None;
+        for value in documents {
+            match &mut merged {
+                None => merged = Some(value),
+                Some(base) => merge_yaml(base, value),
+            }
+        }
+        let Some(merged) = merged else {
+            return Ok(Self::default());
+        };
+
+        let mut unknown = Vec::new();
+        let mut cfg: Self = serde_ignored::deserialize(merged, |path| unknown.push(setting_name(&path)))
+            .map_err(|e| ConfigError::YamlConfigInvalid(e.to_string()))?;
+        // a misspelled setting would otherwise silently keep its default
+        let unknown: Vec<_> = unknown
+            .into_iter()
+            .map(|name| match locate_setting(sources, &name) {
+                Some(location) => format!("{} ({})", name, location),
+                None => name,
+            })
+            .collect();
+        if !unknown.is_empty() && !cfg.allow_unknown_settings {
+            return Err(ConfigError::UnknownSettings(unknown.join(", ")));
+        }
+        cfg.unknown_settings = unknown;
+
+        if cfg.log.log_file.is_empty() {
+            cfg.log.log_file = DEFAULT_LOG_FILE.to_owned();
+        }
+        // convert relative path to absolute
+        if cfg.log.log_file != MEMORY_LOG_FILE && Path::new(&cfg.log.log_file).is_relative() {
+            let Ok(mut pb) = env::current_dir() else {
+                return Err(ConfigError::YamlConfigInvalid("get cwd failed".to_owned()));
+            };
+            pb.push(&cfg.log.log_file);
+            match pb.to_str() {
+                Some(s) => cfg.log.log_file = s.to_owned(),
+                None => {
+                    return Err(ConfigError::YamlConfigInvalid(format!("invalid log path {}", cfg.log.log_file)));
+                }
+            }
+        }
+
+        for (module, level) in &cfg.log.module_levels {
+            if LevelFilter::from_str(level).is_err() {
+                return Err(ConfigError::YamlConfigInvalid(format!(
+                    "invalid log level {} for module {}",
+                    level, module
+                )));
+            }
+        }
+
+        if StrftimeItems::new(&cfg.log.timestamp_format).any(|item| item == Item::Error) {
+            return Err(ConfigError::YamlConfigInvalid(format!(
+                "invalid log timestamp format {}",
+                cfg.log.timestamp_format
+            )));
+        }
+
+        if cfg.server.rate_limit_per_sec == Some(0) {
+            return Err(ConfigError::YamlConfigInvalid(
+                "rate-limit-per-sec must be positive, leave it unset to disable the limit".to_owned(),
+            ));
+        }
+
+        for origin in &cfg.server.cors_allowed_origins {
+            if HeaderValue::from_str(origin).is_err() {
+                return Err(ConfigError::YamlConfigInvalid(format!("invalid cors origin {}", origin)));
             }
-            // convert relative path to absolute
-            if Path::new(&cfg.log.log_file).is_relative() {
-                let Ok(mut pb) = env::current_dir() else {
-                    return Err(ConfigError::YamlConfigInvalid("get cwd failed".to_owned()));
+        }
+
+        Ok(cfg)
+    }
+
+    /// Returns every setting as a dotted name and its value, with secrets masked
+    ///
+    /// Names are the ones used in the config file, e.g. `server.tls.key-pem`; list items are
+    /// numbered, e.g. `server.listeners[0].address`.
+    pub fn effective_settings(&self) -> Vec<(String, String)> {
+        let mut settings = Vec::new();
+        match serde_yaml::to_value(self) {
+            Ok(value) => flatten_setting(String::new(), &value, &mut settings),
+            Err(e) => settings.push(("error".to_owned(), e.to_string())),
+        }
+        settings
+    }
+}
+
+fn flatten_setting(
+    name: String,
+    value: &Value,
+    settings: &mut Vec<(String, String)>,
+) {
+    match value {
+        Value::Mapping(mapping) if !mapping.is_empty() => {
+            for (key, value) in mapping {
+                let key = key.as_str().map(str::to_owned).unwrap_or_else(|| format!("{:?}", key));
+                let name = if name.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", name, key)
                 };
-                pb.push(&cfg.log.log_file);
-                match pb.to_str() {
-                    Some(s) => cfg.log.log_file = s.to_owned(),
+                flatten_setting(name, value, settings);
+            }
+        }
+        Value::Sequence(items) if !items.is_empty() => {
+            for (i, item) in items.iter().enumerate() {
+                flatten_setting(format!("{}[{}]", name, i), item, settings);
+            }
+        }
+        _ => {
+            let is_secret = SECRET_SETTINGS.iter().any(|secret| name.ends_with(secret));
+            let value = match value {
+                Value::Null => "unset".to_owned(),
+                Value::String(s) if is_secret && !s.is_empty() => REDACTED.to_owned(),
+                Value::String(s) => s.clone(),
+                Value::Mapping(_) => "{}".to_owned(),
+                Value::Sequence(_) => "[]".to_owned(),
+                value => serde_yaml::to_string(value).map(|s| s.trim_end().to_owned()).unwrap_or_default(),
+            };
+            settings.push((name, value));
+        }
+    }
+}
+
+/// Returns the dotted name of a setting as used in [`Config::effective_settings`], e.g. `server.listeners[0].name`
+fn setting_name(path: &serde_ignored::Path) -> String {
+    match path {
+        serde_ignored::Path::Root => String::new(),
+        serde_ignored::Path::Seq {
+            parent,
+            index,
+        } => format!("{}[{}]", setting_name(parent), index),
+        serde_ignored::Path::Map {
+            parent,
+            key,
+        } => match setting_name(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{}.{}", parent, key),
+        },
+        serde_ignored::Path::Some {
+            parent,
+        }
+        | serde_ignored::Path::NewtypeStruct {
+            parent,
+        }
+        | serde_ignored::Path::NewtypeVariant {
+            parent,
+        } => setting_name(parent),
+    }
+}
+
+/// Returns the line setting `name` is on, in the last of `sources` setting it
+///
+/// Settings are found by their indentation, settings brought in with `!include` are not found.
+fn locate_setting(
+    sources: &[(String, String)],
+    name: &str,
+) -> Option<String> {
+    let keys: Vec<_> = name.split('.').map(|key| key.split('[').next().unwrap_or(key)).collect();
+    sources.iter().rev().find_map(|(source, contents)| {
+        let mut path: Vec<(usize, &str)> = Vec::new();
+        for (i, line) in contents.lines().enumerate() {
+            let mut entry = line.trim_start();
+            let mut indent = line.len() - entry.len();
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+            // the first key of a list item is indented past its dash
+            while let Some(item) = entry.strip_prefix('-') {
+                let trimmed = item.trim_start();
+                indent += entry.len() - trimmed.len();
+                entry = trimmed;
+            }
+            let Some((key, _)) = entry.split_once(':') else {
+                continue;
+            };
+            path.retain(|(depth, _)| *depth < indent);
+            path.push((indent, key.trim()));
+            if path.iter().map(|(_, key)| *key).eq(keys.iter().copied()) {
+                return Some(format!("line {} of {}", i + 1, source));
+            }
+        }
+        None
+    })
+}
+
+/// Parses a YAML document and resolves its includes, `None` for an empty document
+fn parse_document(
+    contents: &str,
+    base_dir: &Path,
+) -> Result<Option<Value>, ConfigError> {
+    if contents.is_empty() {
+        // parsing empty string leads to EOF error
+        return Ok(None);
+    }
+    let mut value: Value = serde_yaml::from_str(contents).map_err(|e| ConfigError::YamlConfigInvalid(e.to_string()))?;
+    resolve_includes(&mut value, base_dir, 0)?;
+    Ok(Some(value))
+}
+
+/// Replaces every value tagged `!include path` with the parsed contents of that file
+fn resolve_includes(
+    value: &mut Value,
+    base_dir: &Path,
+    depth: usize,
+) -> Result<(), ConfigError> {
+    match value {
+        Value::Tagged(tagged) if tagged.tag == "include" => {
+            let Value::String(file) = &tagged.value else {
+                return Err(ConfigError::YamlConfigInvalid(format!(
+                    "!include expects a file path, got {:?}",
+                    tagged.value
+                )));
+            };
+            let path = base_dir.join(file);
+            if depth >= MAX_INCLUDE_DEPTH {
+                return Err(ConfigError::IncludeFailed(
+                    path.display().to_string(),
+                    format!(
+                        "includes are nested deeper than {}, is a file including itself?",
+                        MAX_INCLUDE_DEPTH
+                    ),
+                ));
+            }
+            let contents =
+                fs::read_to_string(&path).map_err(|e| ConfigError::IncludeFailed(path.display().to_string(), e.to_string()))?;
+            let mut included = if contents.trim().is_empty() {
+                Value::Null
+            } else {
+                serde_yaml::from_str(&contents)
+                    .map_err(|e| ConfigError::IncludeFailed(path.display().to_string(), e.to_string()))?
+            };
+            resolve_includes(&mut included, path.parent().unwrap_or(base_dir), depth + 1)?;
+            *value = included;
+        }
+        Value::Tagged(tagged) => resolve_includes(&mut tagged.value, base_dir, depth)?,
+        Value::Mapping(mapping) => {
+            for value in mapping.values_mut() {
+                resolve_includes(value, base_dir, depth)?;
+            }
+        }
+        Value::Sequence(sequence) => {
+            for value in sequence {
+                resolve_includes(value, base_dir, depth)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Merges `overlay` into `base`, mappings are merged recursively and any other value is replaced
+fn merge_yaml(
+    base: &mut Value,
+    overlay: Value,
+) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_yaml(existing, value),
                     None => {
-                        return Err(ConfigError::YamlConfigInvalid(format!("invalid log path {}", cfg.log.log_file)));
+                        base.insert(key, value);
                     }
                 }
             }
-
-            Ok(cfg)
         }
+        (base, overlay) => *base = overlay,
     }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            instance_id: None,
             server: ServerConfig::default(),
             log: LogConfig::default(),
             async_worker_thread_number: 16,
+            diagnostics: DiagnosticsConfig::default(),
+            otel: None,
+            allow_unknown_settings: false,
+            unknown_settings: Vec::new(),
         }
     }
 }
 
-#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+/// Settings of the workflow service
+///
+/// Settings documented as reloadable are applied to a running server by
+/// [`crate::server::LimitsReloader`], e.g. on SIGHUP; the others need a restart.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct ServerConfig {
     pub port: u16,
+    /// Gzip-compress responses for clients that accept it, and accept gzip-compressed requests
+    pub compression: bool,
+    /// Listeners with their own address and RPC allowlist, when empty `port` serves every RPC
+    pub listeners: Vec<ListenerConfig>,
+    /// Number of times binding a listener is retried before giving up
+    pub bind_retries: u32,
+    /// Interval before the first bind retry in milliseconds, doubled on each further retry
+    pub bind_retry_interval_ms: u64,
+    /// Length of the queue of connections waiting to be accepted, unset keeps the default of the standard library
+    ///
+    /// The kernel caps it, e.g. at `net.core.somaxconn` on Linux.
+    pub tcp_backlog: Option<u32>,
+    /// Maximum number of concurrent HTTP/2 streams per client connection, unset keeps the tonic default
+    ///
+    /// Each `run_workflow` call holds a stream for the whole run, so this also bounds how many
+    /// workflows a single connection can run at once.
+    pub max_concurrent_streams: Option<u32>,
+    /// File the server writes its process id to, removed on graceful shutdown
+    pub pid_file: Option<String>,
+    /// Serve every listener over TLS, unset serves plaintext
+    pub tls: Option<TlsConfig>,
+    /// Maximum size of a `run_workflow` model in bytes, request messages are capped slightly above it
+    pub max_model_bytes: usize,
+    /// Checkpoint running workflows so they can be run again after a restart, unset disables it
+    pub checkpoint: Option<CheckpointConfig>,
+    /// How node events and logs of a workflow are ordered on its stream
+    pub event_ordering: EventOrdering,
+    /// Maximum number of events buffered for a `run_workflow` client that asked for a replay buffer, reloadable
+    pub replay_buffer_size: usize,
+    /// Time a `run_workflow` client without a replay buffer has to make room in its full stream in milliseconds
+    ///
+    /// Events wait for room instead of being dropped; a client that reads nothing for this long
+    /// gets the events already in its stream, then the stream ends with `DATA_LOSS` while the run
+    /// goes on. Reloadable.
+    pub stream_send_timeout_ms: u64,
+    /// Queue the events of a `run_workflow` stream without bound until its client takes them
+    ///
+    /// No event is ever dropped and a slow client never has its stream closed, which overrides
+    /// `stream-send-timeout-ms` and the replay buffer. A client that stays connected but stops
+    /// reading makes the server hold every further event of its run in memory, so this is only
+    /// meant for trusted clients. `SubscribeWorkflow` streams stay bounded.
+    pub unbounded_events: bool,
+    /// Reject a `run_workflow` whose workflow id is already running instead of running it twice
+    pub reject_duplicate_wid: bool,
+    /// Node types (the `uses` of a node, e.g. "http_request") workflows may use, empty allows every type
+    pub allowed_node_types: Vec<String>,
+    /// Node types workflows may not use, even when they are also allowed
+    pub denied_node_types: Vec<String>,
+    /// Maximum size of a streamed node log line in bytes, longer lines are truncated, unset means unbounded
+    pub max_log_line_bytes: Option<usize>,
+    /// Also serve gRPC-Web over HTTP/1.1 for browser clients
+    pub grpc_web: bool,
+    /// Also serve a REST gateway with JSON requests and server-sent event streams, unset disables it
+    pub rest: Option<RestConfig>,
+    /// Origins browsers may call the server from with gRPC-Web, e.g. "https://dashboard.example.com"
+    ///
+    /// "*" allows any origin, empty allows none, so only same-origin pages can call the server.
+    pub cors_allowed_origins: Vec<String>,
+    /// Number of started runs kept in memory for `replay_workflow`, 0 disables the history
+    pub history_size: usize,
+    /// Close a `run_workflow` stream after its `WorkflowPause` event instead of streaming through the pause
+    ///
+    /// The paused process keeps running in the engine, a client has to reattach to it with
+    /// `subscribe_workflow` to follow it further.
+    pub close_stream_on_pause: bool,
+    /// Run a built-in workflow through the engine before serving, and refuse to start if it fails
+    pub self_test: bool,
+    /// Time the engine has to become ready after startup in milliseconds, the server exits if it takes longer
+    ///
+    /// The health service reports `NOT_SERVING` until then.
+    pub engine_ready_timeout_ms: u64,
+    /// Maximum number of workflows running at once, unset means unlimited
+    ///
+    /// Further runs wait in a queue and start by priority as running workflows finish. Reloadable,
+    /// a lower limit stops no running workflow.
+    pub max_concurrent_workflows: Option<usize>,
+    /// Maximum number of runs waiting for a free slot, more are rejected with `RESOURCE_EXHAUSTED`, reloadable
+    pub max_queued_workflows: usize,
+    /// Close client connections after this many seconds without a request in flight, unset keeps them open
+    ///
+    /// An open `run_workflow` or `subscribe_workflow` stream keeps its connection busy however long
+    /// it goes without an event.
+    pub connection_idle_timeout_secs: Option<u64>,
+    /// Abort a workflow after this many seconds without events or logs, unset never aborts idle workflows
+    ///
+    /// Runs can override it through `RunWorkflowRequest.idle_timeout_secs`.
+    pub workflow_idle_timeout_secs: Option<u64>,
+    /// Abort every workflow still running after this many seconds, unset lets workflows run as long as they take
+    ///
+    /// A backstop against runaway workflows, whatever the client asked for: a `grpc-timeout` or
+    /// idle timeout can only end a run earlier.
+    pub max_workflow_runtime_secs: Option<u64>,
+    /// Requests per second a single client may send, unset means unlimited
+    ///
+    /// Clients are told apart by their certificate common name, or else their IP address, and may
+    /// burst up to one second worth of requests. Requests beyond are rejected with `RESOURCE_EXHAUSTED`.
+    /// Reloadable.
+    pub rate_limit_per_sec: Option<u32>,
+    /// RPCs that are not rate limited (e.g. "GetServerStats"), the health service never is, reloadable
+    pub rate_limit_exempt_rpcs: Vec<String>,
+    /// Maximum number of `SubscribeWorkflow` streams open on one workflow process, more are rejected with `RESOURCE_EXHAUSTED`
+    pub max_subscribers_per_workflow: usize,
+    /// Send a `NodeStart` event with the inputs of a node before its `NodeRunning` event
+    ///
+    /// The inputs may be large or hold secrets, so they are only captured when enabled.
+    pub capture_node_inputs: bool,
+    /// Maximum size in bytes of the outputs a run asking for them gets in its `WorkflowSuccess` event,
+    /// longer outputs are truncated
+    pub max_captured_output_bytes: usize,
+    /// Where the events of every workflow are forwarded to besides the client streams, in order
+    pub event_sinks: Vec<EventSinkConfig>,
+    /// Name of the server instance, set by the runner to the resolved [`Config::instance_id`]
+    ///
+    /// Unset uses the hostname, or a random id without one.
+    #[serde(skip)]
+    pub instance_id: Option<String>,
+    /// Log file streamed by `TailServerLog`, set by the runner to the file the logger writes to
+    #[serde(skip)]
+    pub server_log_file: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            port: 0,
+            compression: false,
+            listeners: Vec::new(),
+            bind_retries: 0,
+            bind_retry_interval_ms: DEFAULT_BIND_RETRY_INTERVAL_MS,
+            tcp_backlog: None,
+            max_concurrent_streams: None,
+            pid_file: None,
+            tls: None,
+            max_model_bytes: DEFAULT_MAX_MODEL_BYTES,
+            checkpoint: None,
+            event_ordering: EventOrdering::BestEffort,
+            replay_buffer_size: DEFAULT_REPLAY_BUFFER_SIZE,
+            stream_send_timeout_ms: DEFAULT_STREAM_SEND_TIMEOUT_MS,
+            unbounded_events: false,
+            reject_duplicate_wid: false,
+            allowed_node_types: Vec::new(),
+            denied_node_types: Vec::new(),
+            max_log_line_bytes: None,
+            grpc_web: false,
+            rest: None,
+            cors_allowed_origins: Vec::new(),
+            history_size: DEFAULT_HISTORY_SIZE,
+            close_stream_on_pause: false,
+            self_test: false,
+            engine_ready_timeout_ms: DEFAULT_ENGINE_READY_TIMEOUT_MS,
+            max_concurrent_workflows: None,
+            max_queued_workflows: DEFAULT_MAX_QUEUED_WORKFLOWS,
+            connection_idle_timeout_secs: None,
+            workflow_idle_timeout_secs: None,
+            max_workflow_runtime_secs: None,
+            rate_limit_per_sec: None,
+            rate_limit_exempt_rpcs: DEFAULT_RATE_LIMIT_EXEMPT_RPCS.iter().map(|rpc| rpc.to_string()).collect(),
+            max_subscribers_per_workflow: DEFAULT_MAX_SUBSCRIBERS_PER_WORKFLOW,
+            capture_node_inputs: false,
+            max_captured_output_bytes: DEFAULT_MAX_CAPTURED_OUTPUT_BYTES,
+            event_sinks: Vec::new(),
+            instance_id: None,
+            server_log_file: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Returns the configured listeners, or a single listener on `port` serving every RPC
+    pub fn effective_listeners(&self) -> Vec<ListenerConfig> {
+        if !self.listeners.is_empty() {
+            return self.listeners.clone();
+        }
+        vec![ListenerConfig {
+            name: "default".to_owned(),
+            address: format!("0.0.0.0:{}", self.port),
+            allowed_rpcs: Vec::new(),
+        }]
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ListenerConfig {
+    /// Listener name used in logs
+    pub name: String,
+    /// Socket address to bind, e.g. "127.0.0.1:20508"
+    pub address: String,
+    /// Names of the RPCs served on this listener (e.g. "RunWorkflow"), empty means all
+    pub allowed_rpcs: Vec<String>,
+}
+
+/// REST gateway served next to the gRPC listeners
+///
+/// Its requests share the rate limit of the listeners, keyed by IP address, but the gateway is
+/// served without TLS, without a listener's RPC allowlist and without client certificates; bind
+/// it to a trusted network or put it behind a proxy that adds them.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RestConfig {
+    /// Socket address the gateway binds, e.g. "0.0.0.0:20510", it cannot share a listener's port
+    pub address: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventOrdering {
+    /// Events and logs are streamed as soon as the engine delivers them
+    ///
+    /// The engine delivers events and logs from separate queues, so a log may arrive before the
+    /// event of the node state it was written in, or the other way round.
+    #[default]
+    BestEffort,
+    /// Events and logs are held back briefly and streamed in timestamp order
+    Causal,
+}
+
+/// Destination of the events of every workflow, see [`crate::server::EventSink`]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventSinkConfig {
+    /// Writes every event as a line of JSON to the standard output
+    Stdout,
+    /// Appends every event as a line of JSON to a file
+    File {
+        path: String,
+    },
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CheckpointConfig {
+    /// Directory holding one checkpoint file per running workflow
+    pub dir: String,
+    /// Run the workflows interrupted by the last shutdown again on startup, otherwise they are discarded
+    ///
+    /// Resumed workflows start over from their first node and have no client stream.
+    pub resume: bool,
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TlsConfig {
+    /// PEM certificate chain presented by the server
+    pub cert_file: String,
+    /// PEM private key of the server certificate
+    pub key_file: String,
+    /// Inline PEM certificate chain, e.g. injected by a secret manager, used when `cert_file` is empty
+    pub cert_pem: String,
+    /// Inline PEM private key, used when `key_file` is empty
+    pub key_pem: String,
+    /// PEM CA certificates client certificates must be signed by, unset means clients are not authenticated
+    ///
+    /// Client certificates are checked during the server TLS handshake, so this requires a
+    /// certificate and key as well; the server refuses to start without them.
+    /// Without it `CancelAll`, which stops every run, is refused.
+    pub client_ca_file: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct LogConfig {
     pub level: String,
     pub third_party_log_level: String,
+    /// Path of the log file, ":memory:" keeps the lines in memory for tests, see `logger::take_memory_log`
     pub log_file: String,
     pub retention: usize,
+    /// Maximum total size of the log files in bytes, 0 means unbounded
+    pub max_total_log_bytes: u64,
+    /// Log levels of individual modules, e.g. `tonic: debug`, overriding the levels above
+    pub module_levels: BTreeMap<String, String>,
+    /// Let `RUST_LOG` replace the levels above when it is set, by default the config file wins
+    pub respect_rust_log: bool,
+    /// Keep a symlink at `log_file` pointing at the current log file, skipped with a warning where symlinks are unsupported
+    pub create_symlink: bool,
+    /// Refuse to start when the log file cannot be written instead of only logging to the console
+    pub require_file_logging: bool,
+    /// Also write the logs to stderr
+    pub log_to_stderr: bool,
+    /// Also write the logs to stdout, for log collectors that only capture stdout
+    pub log_to_stdout: bool,
+    /// strftime format of the log line timestamps, e.g. `%Y-%m-%dT%H:%M:%S%.3fZ`
+    pub timestamp_format: String,
+    /// Timezone of the log line timestamps
+    pub timezone: LogTimezone,
+    /// Whether log lines are colored with ANSI codes
+    pub color: LogColor,
+    /// Name of the server instance written into every log line, set by the runner to the resolved
+    /// [`Config::instance_id`], unset leaves it out
+    #[serde(skip)]
+    pub instance_id: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogTimezone {
+    /// The timezone of the host
+    #[default]
+    Local,
+    /// UTC, so logs aggregated across regions line up
+    Utc,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogColor {
+    /// Colored when stderr is a terminal
+    #[default]
+    Auto,
+    Always,
+    /// Plain lines, for log collectors that would show the ANSI codes
+    Never,
 }
 
 impl Default for LogConfig {
@@ -91,6 +744,45 @@ impl Default for LogConfig {
             third_party_log_level: DEFAULT_THIRD_PARTY_LOG_LEVEL.into(),
             log_file: DEFAULT_LOG_FILE.into(),
             retention: DEFAULT_LOG_RETENTION,
+            max_total_log_bytes: 0,
+            module_levels: BTreeMap::new(),
+            respect_rust_log: false,
+            create_symlink: true,
+            require_file_logging: false,
+            log_to_stderr: true,
+            log_to_stdout: false,
+            timestamp_format: DEFAULT_LOG_TIMESTAMP_FORMAT.into(),
+            timezone: LogTimezone::Local,
+            color: LogColor::Auto,
+            instance_id: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct DiagnosticsConfig {
+    /// Serve the runtime state to `tokio-console` on 127.0.0.1:6669
+    ///
+    /// Only takes effect in builds with the `tokio-console` feature and `--cfg tokio_unstable`,
+    /// otherwise a warning is logged on startup.
+    pub tokio_console: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct OtelConfig {
+    /// OTLP/gRPC endpoint of the collector, e.g. "http://localhost:4317"
+    pub endpoint: String,
+    /// Service name the spans are reported under
+    pub service_name: String,
+}
+
+impl Default for OtelConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: DEFAULT_OTEL_ENDPOINT.into(),
+            service_name: DEFAULT_OTEL_SERVICE_NAME.into(),
         }
     }
 }
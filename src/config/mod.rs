@@ -1,3 +1,5 @@
+#[allow(clippy::module_inception)]
 mod config;
+mod template;
 
 pub use config::*;
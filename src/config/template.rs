@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use serde_yaml::{Mapping, Value};
+
+use super::Config;
+
+/// The example config shipped with the server, whose comments document the settings
+const EXAMPLE_CONFIG: &str = include_str!("../../actflow-server.yaml");
+
+impl Config {
+    /// Returns the default config as YAML, every setting preceded by its description
+    ///
+    /// The descriptions are the comments of the example config; settings that are unset by
+    /// default are printed as `null`. The output loads back into [`Config::default`].
+    pub fn default_template() -> String {
+        let docs = setting_docs(EXAMPLE_CONFIG);
+        let mut out = String::from("# Default actflow-server configuration\n");
+        match serde_yaml::to_value(Config::default()) {
+            Ok(Value::Mapping(mapping)) => render(&mapping, "", 0, &docs, &mut out),
+            Ok(_) => {}
+            Err(e) => out.push_str(&format!("# failed to serialize the default config: {}\n", e)),
+        }
+        out
+    }
+}
+
+/// Collects the comment lines preceding every setting of `example`, keyed by dotted setting name
+///
+/// Commented-out settings count as settings, so the comments above them are kept too. Comments
+/// are dropped at blank lines.
+fn setting_docs(example: &str) -> HashMap<String, Vec<String>> {
+    let mut docs = HashMap::new();
+    let mut path: Vec<&str> = Vec::new();
+    let mut pending = Vec::new();
+    // depth of the latest commented-out setting, whose example value may span the lines below it
+    let mut commented_depth = None;
+    for line in example.lines() {
+        let mut column = 0;
+        let mut markers = 0;
+        let mut rest = line;
+        loop {
+            let trimmed = rest.trim_start();
+            column += rest.len() - trimmed.len();
+            match trimmed.strip_prefix('#') {
+                Some(comment) => {
+                    column += 1;
+                    markers += 1;
+                    rest = comment;
+                }
+                None => {
+                    rest = trimmed;
+                    break;
+                }
+            }
+        }
+        // "# " in front of a commented-out setting does not count as indentation
+        let depth = column.saturating_sub(2 * markers) / 2;
+        let commented = markers > 0;
+        if rest.is_empty() {
+            if !commented {
+                pending.clear();
+            }
+            continue;
+        }
+
+        match setting_key(rest) {
+            Some(key) => {
+                path.truncate(depth);
+                path.push(key);
+                docs.insert(path.join("."), std::mem::take(&mut pending));
+                // a setting inside the example value of a commented-out setting keeps that setting's depth
+                commented_depth = commented.then(|| commented_depth.filter(|&outer| outer < depth).unwrap_or(depth));
+            }
+            // part of the example value of a commented-out setting
+            None if commented_depth.is_some_and(|setting| depth > setting) => {}
+            None if commented => pending.push(rest.trim_start().to_owned()),
+            None => pending.clear(),
+        }
+    }
+    docs
+}
+
+/// Returns the key of a `key: value` line, `None` for any other line
+fn setting_key(line: &str) -> Option<&str> {
+    let (key, value) = line.split_once(':')?;
+    let is_key = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    (is_key && (value.is_empty() || value.starts_with(' '))).then_some(key)
+}
+
+fn render(
+    mapping: &Mapping,
+    prefix: &str,
+    indent: usize,
+    docs: &HashMap<String, Vec<String>>,
+    out: &mut String,
+) {
+    let pad = " ".repeat(indent);
+    for (key, value) in mapping {
+        let key = key.as_str().map(str::to_owned).unwrap_or_else(|| format!("{:?}", key));
+        let name = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        for comment in docs.get(&name).into_iter().flatten() {
+            out.push_str(&format!("{}# {}\n", pad, comment));
+        }
+        match value {
+            Value::Mapping(nested) if !nested.is_empty() => {
+                out.push_str(&format!("{}{}:\n", pad, key));
+                render(nested, &name, indent + 2, docs, out);
+            }
+            value => {
+                let yaml = serde_yaml::to_string(value).unwrap_or_default();
+                let yaml = yaml.trim_end();
+                if yaml.contains('\n') {
+                    out.push_str(&format!("{}{}:\n", pad, key));
+                    for line in yaml.lines() {
+                        out.push_str(&format!("{}  {}\n", pad, line));
+                    }
+                } else {
+                    out.push_str(&format!("{}{}: {}\n", pad, key, yaml));
+                }
+            }
+        }
+    }
+}
@@ -2,10 +2,13 @@ pub mod common;
 pub mod config;
 pub mod logger;
 pub mod runner;
-mod server;
+pub mod server;
 
-mod proto {
-    tonic::include_proto!("workflow");
+pub mod proto {
+    /// Protobuf package of the service, `workflow` unless `ACTFLOW_PROTO_PACKAGE` set another one at build time
+    pub const PACKAGE: &str = env!("ACTFLOW_PROTO_PACKAGE");
+
+    include!(concat!(env!("OUT_DIR"), "/", env!("ACTFLOW_PROTO_PACKAGE"), ".rs"));
 }
 
 pub mod built_info {
@@ -1,12 +1,74 @@
-use std::{fs, path::Path};
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, IsTerminal, Write},
+    path::Path,
+    sync::{
+        RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
 
 use anyhow::Result;
-use flexi_logger::{Age, Cleanup, Criterion, Duplicate, FileSpec, Logger, Naming, colored_opt_format};
+use flexi_logger::{
+    Age, Cleanup, Criterion, DeferredNow, Duplicate, FileSpec, Logger, LoggerHandle, Naming, Record, style,
+    writers::FileLogWriter,
+};
 
-use crate::config;
+use super::{memory::MemoryWriter, prune_to_retention};
+use crate::{
+    common::consts::{DEFAULT_LOG_TIMESTAMP_FORMAT, MEMORY_LOG_FILE},
+    config::{self, LogColor, LogTimezone},
+};
+
+/// Log target of the lines written for runs asking for a log level of their own
+///
+/// It is always enabled, only the runs that asked for a level write to it.
+pub const RUN_LOG_TARGET: &str = "actflow_server::run";
+
+/// Timestamp format and timezone of the log lines, set by `init_logger`
+///
+/// Format functions are plain function pointers, so the settings cannot be captured.
+static TIMESTAMP_STYLE: RwLock<Option<(String, LogTimezone)>> = RwLock::new(None);
+
+/// Name of the server instance written into every log line, set by `init_logger`
+static INSTANCE_ID: RwLock<Option<String>> = RwLock::new(None);
+
+/// Whether the log lines are colored, set by `init_logger`
+static COLORED: AtomicBool = AtomicBool::new(true);
 
 /// Initializes the application's logging system
+///
+/// With the log file set to [`MEMORY_LOG_FILE`] the lines are only kept in memory, see [`super::take_memory_log`].
 pub fn init_logger(log_config: &config::LogConfig) -> Result<Logger> {
+    let crate_name = env!("CARGO_PKG_NAME").replace("-", "_");
+    let mut log_level = format!(
+        "{},{}={},{}=trace",
+        log_config.third_party_log_level, crate_name, log_config.level, RUN_LOG_TARGET
+    );
+    // later module entries override the crate-wide level above
+    for (module, level) in &log_config.module_levels {
+        log_level.push_str(&format!(",{}={}", module, level));
+    }
+    let logger = if log_config.respect_rust_log {
+        Logger::try_with_env_or_str(&log_level)?
+    } else {
+        Logger::try_with_str(&log_level)?
+    }
+    .format(log_format);
+    *TIMESTAMP_STYLE.write().unwrap() = Some((log_config.timestamp_format.clone(), log_config.timezone));
+    *INSTANCE_ID.write().unwrap() = log_config.instance_id.clone();
+    let colored = match log_config.color {
+        LogColor::Auto => io::stderr().is_terminal(),
+        LogColor::Always => true,
+        LogColor::Never => false,
+    };
+    COLORED.store(colored, Ordering::Relaxed);
+
+    if log_config.log_file == MEMORY_LOG_FILE {
+        return Ok(logger.log_to_writer(Box::new(MemoryWriter)));
+    }
+
     let base_path = match Path::new(&log_config.log_file).parent() {
         Some(base_path) => base_path,
         None => {
@@ -22,29 +84,140 @@ pub fn init_logger(log_config: &config::LogConfig) -> Result<Logger> {
         fs::create_dir_all(base_path).is_ok()
     };
 
-    let crate_name = env!("CARGO_PKG_NAME").replace("-", "_");
-    let log_level = format!("{},{}={}", log_config.third_party_log_level, crate_name, log_config.level);
-    let logger = Logger::try_with_env_or_str(&log_level)?.format(colored_opt_format);
-
     let logger = if write_to_file {
-        logger
+        let logger = logger
             .log_to_file(FileSpec::try_from(&log_config.log_file)?)
-            // .duplicate_to_stdout(Duplicate::All)
-            .duplicate_to_stderr(Duplicate::All)
+            .duplicate_to_stdout(duplicate(log_config.log_to_stdout))
+            .duplicate_to_stderr(duplicate(log_config.log_to_stderr))
             .rotate(
                 Criterion::Age(Age::Day),
                 Naming::Timestamps,
                 Cleanup::KeepLogFiles(log_config.retention),
             )
-            .create_symlink(&log_config.log_file)
-            .append()
+            .append();
+        if !log_config.create_symlink {
+            logger
+        } else if let Err(e) = probe_symlink(base_path) {
+            // the logger is not running yet, so this cannot go through `warn!`
+            eprintln!(
+                "Symlinks are not supported in '{}' ({}), the log file symlink will not be created",
+                base_path.display(),
+                e
+            );
+            logger
+        } else {
+            logger.create_symlink(&log_config.log_file)
+        }
+    } else if log_config.require_file_logging {
+        return Err(anyhow::Error::msg(format!(
+            "Init logger failure, the log path({}) is not writable",
+            log_config.log_file
+        )));
     } else {
         eprintln!(
             "Log file path '{}' access denied, logs will not be written to file",
             log_config.log_file
         );
-        logger
+        // the console is all that is left, stdout wins when both are enabled
+        if log_config.log_to_stdout {
+            logger.log_to_stdout()
+        } else if log_config.log_to_stderr {
+            logger.log_to_stderr()
+        } else {
+            eprintln!("Console logging is disabled as well, nothing will be logged");
+            logger.do_not_log()
+        }
     };
 
     Ok(logger)
 }
+
+/// Returns how many log lines go to a console stream next to the log file
+fn duplicate(enabled: bool) -> Duplicate {
+    if enabled {
+        Duplicate::All
+    } else {
+        Duplicate::None
+    }
+}
+
+/// Applies the retention of `log_config` to the running logger
+///
+/// Later rotations keep the new number of files, and the rotated files beyond it are deleted
+/// right away. The other log settings only take effect on restart.
+pub fn apply_log_retention(
+    handle: &LoggerHandle,
+    log_config: &config::LogConfig,
+) -> Result<()> {
+    // nothing is rotated in memory
+    if log_config.log_file == MEMORY_LOG_FILE {
+        return Ok(());
+    }
+    // everything but the retention has to match `init_logger`, the logger refuses other write modes
+    let mut builder = FileLogWriter::builder(FileSpec::try_from(&log_config.log_file)?)
+        .format(log_format)
+        .rotate(
+            Criterion::Age(Age::Day),
+            Naming::Timestamps,
+            Cleanup::KeepLogFiles(log_config.retention),
+        )
+        .append();
+    if log_config.create_symlink && Path::new(&log_config.log_file).parent().is_some_and(|dir| probe_symlink(dir).is_ok()) {
+        builder = builder.create_symlink(&log_config.log_file);
+    }
+    // pruning goes first, a reset writer only lists the rotated files once it wrote again
+    prune_to_retention(handle, log_config.retention)?;
+    handle.reset_flw(&builder)?;
+    Ok(())
+}
+
+/// Checks that symlinks can be created in `dir`, flexi_logger only reports a failure once logging started
+fn probe_symlink(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let link = dir.join(format!(".symlink-probe-{}", std::process::id()));
+        std::os::unix::fs::symlink(dir, &link)?;
+        fs::remove_file(&link)?;
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// Writes a log line, colored unless disabled, stamped in the configured format and timezone and tagged with the instance id
+pub(super) fn log_format(
+    w: &mut dyn Write,
+    now: &mut DeferredNow,
+    record: &Record,
+) -> io::Result<()> {
+    let mut timestamp = String::new();
+    // an invalid format fails the write instead of panicking like `to_string` would
+    let res = match &*TIMESTAMP_STYLE.read().unwrap() {
+        Some((format, LogTimezone::Utc)) => write!(timestamp, "{}", now.now_utc_owned().format(format)),
+        Some((format, LogTimezone::Local)) => write!(timestamp, "{}", now.now().format(format)),
+        None => write!(timestamp, "{}", now.now().format(DEFAULT_LOG_TIMESTAMP_FORMAT)),
+    };
+    if res.is_err() {
+        timestamp = "invalid timestamp format".to_owned();
+    }
+    let level = record.level();
+    let paint = |text: String| {
+        if COLORED.load(Ordering::Relaxed) {
+            style(level).paint(text).to_string()
+        } else {
+            text
+        }
+    };
+    write!(w, "[{}] ", paint(timestamp))?;
+    if let Some(instance_id) = &*INSTANCE_ID.read().unwrap() {
+        write!(w, "[{}] ", instance_id)?;
+    }
+    write!(
+        w,
+        "{} [{}:{}] {}",
+        paint(level.to_string()),
+        record.file().unwrap_or("<unnamed>"),
+        record.line().unwrap_or(0),
+        paint(record.args().to_string())
+    )
+}
@@ -0,0 +1,33 @@
+use std::{io, sync::Mutex};
+
+use flexi_logger::{DeferredNow, Record, writers::LogWriter};
+
+use super::logger::log_format;
+
+/// Lines written while the log file is [`MEMORY_LOG_FILE`](crate::common::consts::MEMORY_LOG_FILE)
+static LINES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Keeps the formatted log lines in memory, so tests can assert on them without touching the filesystem
+pub(super) struct MemoryWriter;
+
+impl LogWriter for MemoryWriter {
+    fn write(
+        &self,
+        now: &mut DeferredNow,
+        record: &Record,
+    ) -> io::Result<()> {
+        let mut line = Vec::new();
+        log_format(&mut line, now, record)?;
+        LINES.lock().unwrap().push(String::from_utf8_lossy(&line).into_owned());
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the log lines written since the last call, when logging to memory
+pub fn take_memory_log() -> Vec<String> {
+    std::mem::take(&mut *LINES.lock().unwrap())
+}
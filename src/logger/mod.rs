@@ -1,3 +1,8 @@
+#[allow(clippy::module_inception)]
 mod logger;
+mod memory;
+mod pruner;
 
-pub use logger::init_logger;
+pub use logger::{RUN_LOG_TARGET, apply_log_retention, init_logger};
+pub use memory::take_memory_log;
+pub use pruner::{prune_to_retention, spawn_log_pruner};
@@ -0,0 +1,78 @@
+use std::{fs, path::PathBuf, thread, time::Duration};
+
+use anyhow::Result;
+use flexi_logger::{LogfileSelector, LoggerHandle};
+use log::{info, warn};
+
+/// Interval between two disk usage checks
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Spawns a background thread keeping the total size of the log files under `max_total_bytes`
+///
+/// The oldest rotated files are deleted first, the file currently written to is never deleted.
+/// A cap of 0 means unbounded and no thread is spawned.
+pub fn spawn_log_pruner(
+    handle: LoggerHandle,
+    max_total_bytes: u64,
+) -> Result<()> {
+    if max_total_bytes == 0 {
+        return Ok(());
+    }
+
+    thread::Builder::new().name("log-pruner".to_owned()).spawn(move || {
+        loop {
+            if let Err(e) = prune_log_files(&handle, max_total_bytes) {
+                warn!("failed to prune log files: {}", e);
+            }
+            thread::sleep(PRUNE_INTERVAL);
+        }
+    })?;
+
+    Ok(())
+}
+
+/// Deletes the oldest rotated log files beyond the newest `retention`, returning how many were deleted
+///
+/// The logger only applies its retention when it rotates, this applies a lowered one right away.
+pub fn prune_to_retention(
+    handle: &LoggerHandle,
+    retention: usize,
+) -> Result<usize> {
+    // sorted by name, so the oldest timestamped file comes first
+    let rotated = handle.existing_log_files(&LogfileSelector::default())?;
+    let excess = rotated.len().saturating_sub(retention);
+    for file in &rotated[..excess] {
+        fs::remove_file(file)?;
+        info!("removed log file {} to keep {} rotated log files", file.display(), retention);
+    }
+    Ok(excess)
+}
+
+/// Deletes the oldest rotated log files until the total size is under `max_total_bytes`
+fn prune_log_files(
+    handle: &LoggerHandle,
+    max_total_bytes: u64,
+) -> Result<()> {
+    let current = handle.existing_log_files(&LogfileSelector::none().with_r_current())?;
+    // sorted by name, so the oldest timestamped file comes first
+    let rotated = handle.existing_log_files(&LogfileSelector::default())?;
+
+    let size = |path: &PathBuf| fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+    let mut total: u64 = current.iter().chain(rotated.iter()).map(size).sum();
+
+    for file in rotated {
+        if total <= max_total_bytes {
+            break;
+        }
+        let len = size(&file);
+        fs::remove_file(&file)?;
+        total = total.saturating_sub(len);
+        info!(
+            "removed log file {} to keep log disk usage under {} bytes",
+            file.display(),
+            max_total_bytes
+        );
+    }
+
+    Ok(())
+}
@@ -1,47 +1,188 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use actflow::EngineBuilder;
-use anyhow::Result;
-use log::info;
+use flexi_logger::{LogfileSelector, LoggerHandle};
+use log::{error, info, warn};
 use tokio::{runtime::Runtime, signal::ctrl_c};
 
-use crate::{common::shutdown::Shutdown, config::Config, logger::init_logger, server};
+use crate::{
+    common::{pidfile::PidFile, shutdown::ShutdownReason},
+    config::Config,
+    logger::{apply_log_retention, init_logger, spawn_log_pruner},
+    server::{LifecycleHooks, LimitsReloader, ServerError, ServerHandle},
+};
+
+/// Time the listeners get to stop after a shutdown, so their last log lines are written before exiting
+const LISTENER_STOP_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 pub async fn run(
     config: Config,
+    config_files: Vec<String>,
     runtime: Arc<Runtime>,
-) -> Result<()> {
+) -> Result<(), ServerError> {
+    #[cfg(feature = "tokio-console")]
+    if config.diagnostics.tokio_console {
+        console_subscriber::init();
+    }
+
+    // resolved once, a random id would differ between the logs and the server
+    let instance_id = config.resolve_instance_id();
+    let mut log_config = config.log.clone();
+    log_config.instance_id = Some(instance_id.clone());
+
     // Init logger
-    let logger = init_logger(&config.log)?;
-    logger.start()?;
+    let logger = init_logger(&log_config).map_err(|e| ServerError::Internal(e.to_string()))?;
+    let logger_handle = logger.start().map_err(|e| ServerError::Internal(format!("failed to start logger: {}", e)))?;
+    spawn_log_pruner(logger_handle.clone(), config.log.max_total_log_bytes)
+        .map_err(|e| ServerError::Internal(format!("failed to start log pruner: {}", e)))?;
 
-    info!("config {:#?}", config);
+    #[cfg(feature = "otel")]
+    let otel = config.otel.as_ref().map(crate::server::OtelExporter::install).transpose()?;
 
-    info!("==================== Launching Actflow-Server ====================");
+    let res = serve(config, config_files, runtime, instance_id, logger_handle.clone()).await;
+    #[cfg(feature = "otel")]
+    if let Some(otel) = otel {
+        otel.shutdown();
+    }
+    if let Err(e) = &res {
+        error!("actflow server failed: {}", e);
+    }
+    // the file writer buffers lines, a fast exit would lose the last ones
+    logger_handle.flush();
+    logger_handle.shutdown();
+    res
+}
+
+/// Runs the server until it fails or is asked to shut down, once the logger is up
+async fn serve(
+    config: Config,
+    config_files: Vec<String>,
+    runtime: Arc<Runtime>,
+    instance_id: String,
+    logger_handle: LoggerHandle,
+) -> Result<(), ServerError> {
+    let settings = config.effective_settings();
+    let width = settings.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    let table: Vec<_> = settings.iter().map(|(name, value)| format!("  {:<width$}  {}", name, value)).collect();
+    info!("effective config:\n{}", table.join("\n"));
+    for setting in &config.unknown_settings {
+        warn!("ignoring unknown setting {}", setting);
+    }
+    if config.diagnostics.tokio_console && !cfg!(feature = "tokio-console") {
+        warn!("diagnostics.tokio-console is set but the server was built without the tokio-console feature");
+    }
+    match &config.otel {
+        Some(otel) if cfg!(feature = "otel") => info!("exporting the spans of the runs to {}", otel.endpoint),
+        Some(_) => warn!("otel is set but the server was built without the otel feature"),
+        None => {}
+    }
+
+    // removed when dropped at the end of a graceful shutdown
+    let pid_file =
+        config.server.pid_file.as_ref().map(PidFile::create).transpose().map_err(|e| ServerError::Internal(e.to_string()))?;
+    if let Some(pid_file) = &pid_file {
+        info!("pid file written to {}", pid_file.path().display());
+    }
 
-    // Build actflow engine
-    let engine = Arc::new(EngineBuilder::new().runtime(runtime.clone()).build()?);
-    engine.launch();
+    info!("==================== Launching Actflow-Server ====================");
+    info!("instance id: {}", instance_id);
 
-    let shutdown = Shutdown::new();
+    // the rotation renames the current file, the tail follows the name it is always written under
+    let mut server_config = config.server.clone();
+    server_config.instance_id = Some(instance_id.clone());
+    server_config.server_log_file = logger_handle
+        .existing_log_files(&LogfileSelector::none().with_r_current())
+        .ok()
+        .and_then(|files| files.into_iter().next());
 
-    let server_task =
-        async { server::start_server(engine.clone(), format!("0.0.0.0:{}", config.server.port), shutdown.wait()).await };
+    // read from the runtime itself, so operators can confirm it matches the config
+    info!("async runtime running {} worker threads", runtime.metrics().num_workers());
+    let hooks = LifecycleHooks::default().on_ready(|| info!("actflow server ready, serving requests"));
+    let mut handle = ServerHandle::start_with_hooks(&server_config, runtime, hooks)?;
+    tokio::spawn(reload_on_sighup(config_files, logger_handle, handle.limits_reloader()));
 
     let sigint = ctrl_c();
 
-    tokio::select! {
-        res = server_task => res?,
-        Ok(()) = sigint => (),
+    let mut server_err = None;
+    let reason = tokio::select! {
+        res = handle.wait() => match res {
+            Ok(()) => ShutdownReason::Manual,
+            Err(e) => {
+                let reason = ShutdownReason::Fatal(e.to_string());
+                server_err = Some(e);
+                reason
+            }
+        },
+        Ok(()) = sigint => ShutdownReason::Sigint,
+        _ = sigterm() => ShutdownReason::Sigterm,
         else => return Ok(()),
+    };
+    info!("Gracefully shutting down, reason: {}", reason);
+
+    // shutdown the listeners and the actflow engine
+    handle.shutdown_with_reason(reason);
+    // a failed server has already stopped, this returns right away then
+    match tokio::time::timeout(LISTENER_STOP_TIMEOUT, handle.wait()).await {
+        Ok(_) => info!("listeners stopped"),
+        Err(_) => warn!("listeners did not stop within {:?}, exiting anyway", LISTENER_STOP_TIMEOUT),
     }
-    shutdown.shutdown();
-    info!("Gracefully shutting down...");
 
-    // shutdown actflow engine
-    engine.shutdown();
-    info!("Actflow engine shutdown");
+    match server_err {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
 
-    Ok(())
+/// Reloads the config files on every SIGHUP and applies the log retention and the server limits,
+/// does nothing on non-unix platforms
+///
+/// Other settings only take effect on restart, see [`LimitsReloader`] for the reloadable limits.
+async fn reload_on_sighup(
+    config_files: Vec<String>,
+    logger_handle: LoggerHandle,
+    limits: LimitsReloader,
+) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut sig = match signal(SignalKind::hangup()) {
+            Ok(sig) => sig,
+            Err(e) => {
+                warn!("failed to listen for SIGHUP, config reload is disabled: {}", e);
+                return;
+            }
+        };
+        while sig.recv().await.is_some() {
+            info!("SIGHUP received, reloading {:?}", config_files);
+            let config = match Config::load_from_files(&config_files) {
+                Ok(config) => config,
+                Err(e) => {
+                    warn!("failed to reload config, keeping the current one: {}", e);
+                    continue;
+                }
+            };
+            match apply_log_retention(&logger_handle, &config.log) {
+                Ok(()) => info!("log retention set to {} files", config.log.retention),
+                Err(e) => warn!("failed to apply log retention: {}", e),
+            }
+            limits.reload(&config.server);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (config_files, logger_handle, limits);
+}
+
+/// Resolves when the process receives SIGTERM, never resolves on non-unix platforms
+async fn sigterm() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        if let Ok(mut sig) = signal(SignalKind::terminate()) {
+            sig.recv().await;
+            return;
+        }
+    }
+    std::future::pending::<()>().await
 }
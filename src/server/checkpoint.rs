@@ -0,0 +1,103 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// What is needed to run a workflow process again after a restart
+///
+/// The engine does not expose the state of a running process, so a resumed workflow starts over
+/// from its first node.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Checkpoint {
+    /// Process id the workflow ran under before the restart
+    pub pid: String,
+    /// Id of the workflow model
+    pub wid: String,
+    /// JSON workflow model as run, with the retry policy of the request applied
+    pub workflow_model: String,
+    /// Node filter of the original request
+    pub node_filter: Vec<String>,
+    /// Labels of the original request
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+/// Storage for the checkpoints of running workflow processes
+pub trait CheckpointStore: Send + Sync {
+    /// Stores the checkpoint of a process that started
+    fn save(
+        &self,
+        checkpoint: &Checkpoint,
+    ) -> io::Result<()>;
+
+    /// Drops the checkpoint of a process that finished
+    fn remove(
+        &self,
+        pid: &str,
+    ) -> io::Result<()>;
+
+    /// Returns every stored checkpoint
+    fn list(&self) -> io::Result<Vec<Checkpoint>>;
+}
+
+/// Checkpoint store keeping one JSON file per process in a directory
+pub struct FsCheckpointStore {
+    dir: PathBuf,
+}
+
+impl FsCheckpointStore {
+    /// Opens the store, creating the directory if needed
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    fn path(
+        &self,
+        pid: &str,
+    ) -> PathBuf {
+        self.dir.join(format!("{}.json", pid))
+    }
+}
+
+impl CheckpointStore for FsCheckpointStore {
+    fn save(
+        &self,
+        checkpoint: &Checkpoint,
+    ) -> io::Result<()> {
+        let contents = serde_json::to_vec(checkpoint)?;
+        // write then rename so a crash never leaves a truncated checkpoint behind
+        let tmp = self.dir.join(format!(".{}.json.tmp", checkpoint.pid));
+        fs::write(&tmp, contents)?;
+        fs::rename(tmp, self.path(&checkpoint.pid))
+    }
+
+    fn remove(
+        &self,
+        pid: &str,
+    ) -> io::Result<()> {
+        match fs::remove_file(self.path(pid)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn list(&self) -> io::Result<Vec<Checkpoint>> {
+        let mut checkpoints = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let contents = fs::read(&path)?;
+            let checkpoint = serde_json::from_slice(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            checkpoints.push(checkpoint);
+        }
+        Ok(checkpoints)
+    }
+}
@@ -0,0 +1,85 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::{sync::mpsc, time::Instant};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Status;
+
+use super::{scheduler::Scheduler, tracker::ProcessTracker};
+use crate::{common::shutdown::Shutdown, proto::DrainProgress};
+
+/// Interval between two counts of the workflows left
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub type DrainStream = ReceiverStream<Result<DrainProgress, Status>>;
+
+/// Waits until no workflow is left or `timeout` elapsed, returning the number of workflows left
+pub async fn wait_idle(
+    tracker: &ProcessTracker,
+    scheduler: &Scheduler,
+    timeout: Option<Duration>,
+) -> usize {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        let remaining = tracker.all().len() + scheduler.queued();
+        if remaining == 0 {
+            return 0;
+        }
+        let sleep = match deadline {
+            Some(deadline) if Instant::now() >= deadline => return remaining,
+            Some(deadline) => POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now())),
+            None => POLL_INTERVAL,
+        };
+        tokio::time::sleep(sleep).await;
+    }
+}
+
+/// Streams the number of workflows left until there are none or `timeout` elapsed
+///
+/// Runs waiting for a free slot count as left, they are started once a running workflow finishes.
+/// A message is sent whenever the number changes, the last one is marked as done. The stream also
+/// ends when the client goes away or the server shuts down.
+pub fn watch(
+    tracker: Arc<ProcessTracker>,
+    scheduler: Arc<Scheduler>,
+    timeout: Option<Duration>,
+    shutdown: Shutdown,
+) -> DrainStream {
+    let (tx, rx) = mpsc::channel(16);
+    tokio::spawn(async move {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let stopped = shutdown.wait();
+        tokio::pin!(stopped);
+        let mut last = None;
+        loop {
+            let remaining = (tracker.all().len() + scheduler.queued()) as u32;
+            let done = remaining == 0 || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+            if (done || last != Some(remaining))
+                && tx
+                    .send(Ok(DrainProgress {
+                        remaining,
+                        done,
+                    }))
+                    .await
+                    .is_err()
+            {
+                return;
+            }
+            if done {
+                return;
+            }
+            last = Some(remaining);
+
+            let sleep = match deadline {
+                Some(deadline) => POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now())),
+                None => POLL_INTERVAL,
+            };
+            tokio::select! {
+                _ = &mut stopped => return,
+                _ = tx.closed() => return,
+                _ = tokio::time::sleep(sleep) => {}
+            }
+        }
+    });
+
+    ReceiverStream::new(rx)
+}
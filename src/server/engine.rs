@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use actflow::{Engine, EngineBuilder};
+use arc_swap::ArcSwap;
+use tokio::runtime::Runtime;
+
+use super::ServerError;
+
+/// The engine new workflows run on, replaced as a whole by `reload_engine`
+pub struct EngineSlot {
+    current: ArcSwap<Engine>,
+    /// Runtime every engine is built on
+    runtime: Arc<Runtime>,
+}
+
+impl EngineSlot {
+    /// Builds and launches the first engine on `runtime`
+    pub fn launch(runtime: Arc<Runtime>) -> Result<Self, ServerError> {
+        let engine = build(&runtime)?;
+        Ok(Self {
+            current: ArcSwap::new(engine),
+            runtime,
+        })
+    }
+
+    /// Returns the engine new workflows run on
+    pub fn current(&self) -> Arc<Engine> {
+        self.current.load_full()
+    }
+
+    /// Builds and launches another engine, it only runs workflows once it replaced the current one
+    pub fn build(&self) -> Result<Arc<Engine>, ServerError> {
+        build(&self.runtime)
+    }
+
+    /// Makes `engine` the current engine, returning the one it replaced
+    pub fn replace(
+        &self,
+        engine: Arc<Engine>,
+    ) -> Arc<Engine> {
+        self.current.swap(engine)
+    }
+}
+
+fn build(runtime: &Arc<Runtime>) -> Result<Arc<Engine>, ServerError> {
+    let engine = EngineBuilder::new().runtime(runtime.clone()).build().map_err(|e| ServerError::EngineBuild(e.to_string()))?;
+    engine.launch();
+    Ok(Arc::new(engine))
+}
@@ -0,0 +1,82 @@
+use actflow::ActflowError;
+use thiserror::Error;
+use tonic::{Code, Status};
+use tonic_types::{ErrorDetails, StatusExt};
+
+/// Domain of the `ErrorInfo` details attached to error statuses
+const ERROR_DOMAIN: &str = "actflow-server";
+
+#[derive(Debug, Error)]
+pub enum ServerError {
+    #[error("bind failed: {0}")]
+    Bind(String),
+    #[error("tls config invalid: {0}")]
+    Tls(String),
+    #[error("engine build failed: {0}")]
+    EngineBuild(String),
+    #[error("invalid workflow model: {0}")]
+    InvalidModel(String),
+    #[error("failed to build workflow process: {0}")]
+    WorkflowBuild(ActflowError),
+    #[error("self test failed: {0}")]
+    SelfTest(String),
+    #[error("engine not ready: {0}")]
+    NotReady(String),
+    #[error("workflow {0} is already running")]
+    DuplicateWorkflow(String),
+    #[error("workflow process {0} is already running")]
+    DuplicateProcess(String),
+    #[error("node {0} uses node type {1}, which this server does not allow")]
+    NodeTypeDenied(String, String),
+    #[error("workflow queue is full: {0}")]
+    QueueFull(String),
+    #[error("internal error: {0}")]
+    Internal(String),
+}
+
+impl From<ServerError> for Status {
+    fn from(err: ServerError) -> Self {
+        match err {
+            ServerError::InvalidModel(_) => Status::invalid_argument(err.to_string()),
+            ServerError::DuplicateWorkflow(_) | ServerError::DuplicateProcess(_) => Status::already_exists(err.to_string()),
+            ServerError::NodeTypeDenied(..) => Status::permission_denied(err.to_string()),
+            ServerError::QueueFull(_) => Status::resource_exhausted(err.to_string()),
+            ServerError::WorkflowBuild(ref e) => {
+                let (code, category) = classify_build_error(e);
+                let details = ErrorDetails::with_error_info(
+                    "WORKFLOW_BUILD_FAILED",
+                    ERROR_DOMAIN,
+                    [("category".to_owned(), category.to_owned())],
+                );
+                Status::with_error_details(code, err.to_string(), details)
+            }
+            _ => Status::internal(err.to_string()),
+        }
+    }
+}
+
+/// Tells whether a workflow build error is the client's fault, returning the status code and error category
+///
+/// Building a process only fails on the server side when the engine cannot take it, every other
+/// error comes from the model, e.g. an edge to an unknown node or action parameters failing
+/// validation (reported by actflow as a runtime error).
+fn classify_build_error(err: &ActflowError) -> (Code, &'static str) {
+    match err {
+        ActflowError::Engine(_) => (Code::Internal, "engine"),
+        ActflowError::Process(_) => (Code::Internal, "process"),
+        ActflowError::Store(_) => (Code::Internal, "store"),
+        ActflowError::IoError(_) => (Code::Internal, "io"),
+        ActflowError::Queue(_) => (Code::Internal, "queue"),
+        ActflowError::Workflow(_) => (Code::InvalidArgument, "workflow"),
+        ActflowError::Node(_) => (Code::InvalidArgument, "node"),
+        ActflowError::Edge(_) => (Code::InvalidArgument, "edge"),
+        ActflowError::Action(_) => (Code::InvalidArgument, "action"),
+        ActflowError::Config(_) => (Code::InvalidArgument, "config"),
+        ActflowError::Convert(_) => (Code::InvalidArgument, "convert"),
+        ActflowError::Script(_) => (Code::InvalidArgument, "script"),
+        ActflowError::Runtime(_) => (Code::InvalidArgument, "validation"),
+        ActflowError::Exception {
+            ..
+        } => (Code::InvalidArgument, "exception"),
+    }
+}
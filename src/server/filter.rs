@@ -0,0 +1,81 @@
+use std::{
+    collections::HashSet,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use tonic::Status;
+use tower::{Layer, Service};
+
+/// Path prefix of the gRPC health service, served on every listener regardless of its allowlist
+pub const HEALTH_SERVICE_PREFIX: &str = "/grpc.health.v1.Health/";
+
+/// Layer rejecting the RPCs missing from a listener's allowlist
+#[derive(Clone)]
+pub struct RpcFilterLayer {
+    /// Allowed RPC names (e.g. "RunWorkflow"), empty means all
+    allowed: Arc<HashSet<String>>,
+}
+
+impl RpcFilterLayer {
+    pub fn new(allowed: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            allowed: Arc::new(allowed.into_iter().collect()),
+        }
+    }
+}
+
+impl<S> Layer<S> for RpcFilterLayer {
+    type Service = RpcFilter<S>;
+
+    fn layer(
+        &self,
+        inner: S,
+    ) -> Self::Service {
+        RpcFilter {
+            inner,
+            allowed: self.allowed.clone(),
+        }
+    }
+}
+
+/// Service answering `UNIMPLEMENTED` for RPCs that are not allowed
+#[derive(Clone)]
+pub struct RpcFilter<S> {
+    inner: S,
+    allowed: Arc<HashSet<String>>,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for RpcFilter<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+    ResBody: Default,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(
+        &mut self,
+        req: http::Request<ReqBody>,
+    ) -> Self::Future {
+        // gRPC paths look like "/<package>.<Service>/<Method>"
+        let method = req.uri().path().rsplit('/').next().unwrap_or_default();
+        if self.allowed.is_empty() || self.allowed.contains(method) || req.uri().path().starts_with(HEALTH_SERVICE_PREFIX) {
+            Box::pin(self.inner.call(req))
+        } else {
+            let status = Status::unimplemented(format!("{} is not served on this listener", method));
+            Box::pin(async move { Ok(status.into_http()) })
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use log::info;
+use tokio::{runtime::Runtime, sync::watch, task::JoinHandle};
+
+use super::{EngineSlot, LifecycleHooks, ServerError, start_server};
+use crate::{
+    common::shutdown::{Shutdown, ShutdownReason},
+    config::ServerConfig,
+};
+
+/// A server running on a caller-provided runtime, for programs embedding actflow-server
+///
+/// Unlike `runner::run` it installs no logger or signal handlers; the embedding program decides
+/// when to call [`ServerHandle::shutdown`].
+pub struct ServerHandle {
+    shutdown: Shutdown,
+    /// Address of every listener, `None` until it is bound
+    bound: watch::Receiver<Vec<Option<SocketAddr>>>,
+    /// Configs whose reloadable settings the server applies
+    reloaded: watch::Sender<ServerConfig>,
+    /// Task serving the listeners, taken once it finished
+    task: Option<JoinHandle<Result<(), ServerError>>>,
+}
+
+impl ServerHandle {
+    /// Builds and launches the engine on `runtime`, then serves every listener on it
+    ///
+    /// Returns as soon as the listeners are spawned, bind errors are reported by [`ServerHandle::wait`].
+    pub fn start(
+        config: &ServerConfig,
+        runtime: Arc<Runtime>,
+    ) -> Result<Self, ServerError> {
+        Self::start_with_hooks(config, runtime, LifecycleHooks::default())
+    }
+
+    /// Like [`ServerHandle::start`], running `hooks` when the server is ready and when it shuts down
+    pub fn start_with_hooks(
+        config: &ServerConfig,
+        runtime: Arc<Runtime>,
+        hooks: LifecycleHooks,
+    ) -> Result<Self, ServerError> {
+        let engine = Arc::new(EngineSlot::launch(runtime.clone())?);
+
+        let shutdown = Shutdown::new();
+        let (bound_tx, bound) = watch::channel(vec![None; config.effective_listeners().len()]);
+        let (reloaded, reloaded_rx) = watch::channel(config.clone());
+        let (announced_tx, mut announced) = watch::channel(false);
+        // stopping the engine aborts the running workflows, which would end their streams before the
+        // shutdown notice could
+        {
+            let engine = engine.clone();
+            let shutdown = shutdown.clone();
+            runtime.spawn(async move {
+                shutdown.wait().await;
+                // also ends if the server stopped before it could announce anything
+                let _ = announced.wait_for(|announced| *announced).await;
+                engine.current().shutdown();
+                info!("actflow engine shut down");
+            });
+        }
+        let task = {
+            let config = config.clone();
+            let shutdown = shutdown.clone();
+            let hooks = Arc::new(hooks);
+            runtime.spawn(async move {
+                let res = start_server(engine, &config, bound_tx, announced_tx, reloaded_rx, hooks.clone(), shutdown).await;
+                hooks.shutdown_complete();
+                res
+            })
+        };
+
+        Ok(Self {
+            shutdown,
+            bound,
+            reloaded,
+            task: Some(task),
+        })
+    }
+
+    /// Returns the address of every listener in the order they are configured
+    ///
+    /// Waits until all of them are bound, so it also tells the port the OS picked for a listener on port 0.
+    pub async fn local_addrs(&self) -> Result<Vec<SocketAddr>, ServerError> {
+        let mut bound = self.bound.clone();
+        let addrs = bound
+            .wait_for(|addrs| addrs.iter().all(Option::is_some))
+            .await
+            .map_err(|_| ServerError::Bind("the server stopped before every listener was bound".to_owned()))?;
+        Ok(addrs.iter().flatten().copied().collect())
+    }
+
+    /// Returns a handle applying the reloadable settings of a changed config to the running server
+    pub fn limits_reloader(&self) -> LimitsReloader {
+        LimitsReloader {
+            reloaded: self.reloaded.clone(),
+        }
+    }
+
+    /// Waits until every listener stopped, after a shutdown or because one of them failed
+    pub async fn wait(&mut self) -> Result<(), ServerError> {
+        let Some(task) = self.task.as_mut() else {
+            return Ok(());
+        };
+        let res = task.await;
+        self.task = None;
+        res.map_err(|e| ServerError::Internal(format!("server task failed: {}", e)))?
+    }
+
+    /// Stops the listeners and, once the running streams ended with a shutdown notice, the engine
+    pub fn shutdown(&self) {
+        self.shutdown_with_reason(ShutdownReason::Manual);
+    }
+
+    /// Like [`ServerHandle::shutdown`], recording why
+    pub fn shutdown_with_reason(
+        &self,
+        reason: ShutdownReason,
+    ) {
+        self.shutdown.shutdown_with_reason(reason);
+    }
+
+    /// Returns why the server was shut down, `None` while it is running
+    pub fn reason(&self) -> Option<ShutdownReason> {
+        self.shutdown.reason()
+    }
+}
+
+/// Applies the settings of a reloaded config that take effect without a restart
+///
+/// These are `max-concurrent-workflows`, `max-queued-workflows`, `rate-limit-per-sec`,
+/// `rate-limit-exempt-rpcs`, `replay-buffer-size` and `stream-send-timeout-ms`; every other
+/// server setting needs a restart. Lowering `max-concurrent-workflows` stops no running workflow,
+/// new runs wait until enough of them finished.
+#[derive(Clone)]
+pub struct LimitsReloader {
+    reloaded: watch::Sender<ServerConfig>,
+}
+
+impl LimitsReloader {
+    /// Applies the reloadable settings of `config`, ignored once the server stopped
+    pub fn reload(
+        &self,
+        config: &ServerConfig,
+    ) {
+        self.reloaded.send_replace(config.clone());
+    }
+}
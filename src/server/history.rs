@@ -0,0 +1,65 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+/// What a workflow run was started with
+#[derive(Clone, Debug)]
+pub struct HistoryRecord {
+    /// Process id assigned by the engine
+    pub pid: String,
+    /// Id of the workflow model
+    pub wid: String,
+    /// JSON workflow model the run was started with
+    pub workflow_model: String,
+    /// Node filter of the client stream
+    pub node_filter: Vec<String>,
+    /// Labels the client attached to the run
+    pub labels: HashMap<String, String>,
+    /// Client that started the run, `None` for runs the server started itself
+    pub peer: Option<String>,
+    /// Process id of the run this one replays
+    pub replay_of: Option<String>,
+    /// Name of the server instance that ran it
+    pub instance_id: String,
+}
+
+/// In-memory history of the most recently started runs
+pub struct HistoryStore {
+    /// Records, oldest first
+    records: Mutex<VecDeque<HistoryRecord>>,
+    /// Maximum number of records kept, 0 keeps none
+    capacity: usize,
+}
+
+impl HistoryStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            records: Mutex::new(VecDeque::new()),
+            capacity,
+        }
+    }
+
+    /// Records a run, evicting the oldest record when the store is full
+    pub fn record(
+        &self,
+        record: HistoryRecord,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut records = self.records.lock().unwrap();
+        if records.len() >= self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Returns the record of the run with the given pid
+    pub fn get(
+        &self,
+        pid: &str,
+    ) -> Option<HistoryRecord> {
+        self.records.lock().unwrap().iter().find(|record| record.pid == pid).cloned()
+    }
+}
@@ -0,0 +1,311 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use http_body::{Body, Frame, SizeHint};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+    time::{Instant, Sleep},
+};
+use tonic::transport::server::{Connected, TcpConnectInfo, TlsConnectInfo};
+use tower::{Layer, Service};
+
+/// Connections of a listener by remote address, with the requests each one has in flight
+///
+/// Shared by the connections and the [`ConnectionActivityLayer`] of a listener, which tells them
+/// apart by the remote address in the request extensions.
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    conns: Mutex<HashMap<SocketAddr, Arc<Activity>>>,
+}
+
+/// Requests in flight on a connection and since when it has none
+struct Activity {
+    active: AtomicUsize,
+    idle_since: Mutex<Instant>,
+}
+
+impl Activity {
+    /// Returns when the connection times out, `None` while it has requests in flight
+    fn deadline(
+        &self,
+        timeout: Duration,
+    ) -> Option<Instant> {
+        let idle_since = self.idle_since.lock().unwrap();
+        (self.active.load(Ordering::SeqCst) == 0).then(|| *idle_since + timeout)
+    }
+}
+
+/// Marks a request in flight on its connection until dropped
+struct ActiveGuard(Arc<Activity>);
+
+impl ActiveGuard {
+    fn new(activity: Arc<Activity>) -> Self {
+        activity.active.fetch_add(1, Ordering::SeqCst);
+        Self(activity)
+    }
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        // under the lock, so `deadline` never sees no requests with a stale idle time
+        let mut idle_since = self.0.idle_since.lock().unwrap();
+        if self.0.active.fetch_sub(1, Ordering::SeqCst) == 1 {
+            *idle_since = Instant::now();
+        }
+    }
+}
+
+impl ConnectionRegistry {
+    /// Wraps an accepted connection, closing it once it had no request in flight for `timeout`
+    ///
+    /// `None` never closes the connection.
+    pub fn track(
+        self: &Arc<Self>,
+        stream: TcpStream,
+        timeout: Option<Duration>,
+    ) -> IdleConnection {
+        let timeout = timeout.zip(stream.peer_addr().ok()).map(|(timeout, addr)| {
+            let activity = Arc::new(Activity {
+                active: AtomicUsize::new(0),
+                idle_since: Mutex::new(Instant::now()),
+            });
+            self.conns.lock().unwrap().insert(addr, activity.clone());
+            IdleTimeout {
+                timeout,
+                timer: Box::pin(tokio::time::sleep(timeout)),
+                activity,
+                addr,
+                registry: self.clone(),
+            }
+        });
+        IdleConnection {
+            stream,
+            idle: timeout,
+        }
+    }
+
+    fn get(
+        &self,
+        addr: &SocketAddr,
+    ) -> Option<Arc<Activity>> {
+        self.conns.lock().unwrap().get(addr).cloned()
+    }
+}
+
+/// A connection closed by the server once it had no request in flight for the idle timeout
+pub struct IdleConnection {
+    stream: TcpStream,
+    idle: Option<IdleTimeout>,
+}
+
+struct IdleTimeout {
+    timeout: Duration,
+    timer: Pin<Box<Sleep>>,
+    activity: Arc<Activity>,
+    addr: SocketAddr,
+    registry: Arc<ConnectionRegistry>,
+}
+
+impl IdleTimeout {
+    /// Fails once the connection has been idle for the timeout, registering the timer otherwise
+    fn poll_expired(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> io::Result<()> {
+        while self.timer.as_mut().poll(cx).is_ready() {
+            match self.activity.deadline(self.timeout) {
+                Some(deadline) if deadline <= Instant::now() => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "connection idle timeout"));
+                }
+                Some(deadline) => self.timer.as_mut().reset(deadline),
+                // a request finishing restarts the idle time, checked again after a full timeout
+                None => self.timer.as_mut().reset(Instant::now() + self.timeout),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for IdleTimeout {
+    fn drop(&mut self) {
+        let mut conns = self.registry.conns.lock().unwrap();
+        // the address may already belong to a newer connection
+        if conns.get(&self.addr).is_some_and(|activity| Arc::ptr_eq(activity, &self.activity)) {
+            conns.remove(&self.addr);
+        }
+    }
+}
+
+impl Connected for IdleConnection {
+    type ConnectInfo = TcpConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.stream.connect_info()
+    }
+}
+
+impl AsyncRead for IdleConnection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        // the server always waits for the next frame, so the timer is polled with the reads
+        if let Some(idle) = &mut this.idle
+            && let Err(e) = idle.poll_expired(cx)
+        {
+            return Poll::Ready(Err(e));
+        }
+        Pin::new(&mut this.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for IdleConnection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+}
+
+/// Counts the requests in flight on each connection, until their response body ended
+///
+/// A streaming RPC stays in flight while its stream is open, however long it goes without a message.
+#[derive(Clone)]
+pub struct ConnectionActivityLayer {
+    registry: Arc<ConnectionRegistry>,
+}
+
+impl ConnectionActivityLayer {
+    pub fn new(registry: Arc<ConnectionRegistry>) -> Self {
+        Self {
+            registry,
+        }
+    }
+}
+
+impl<S> Layer<S> for ConnectionActivityLayer {
+    type Service = ConnectionActivity<S>;
+
+    fn layer(
+        &self,
+        inner: S,
+    ) -> Self::Service {
+        ConnectionActivity {
+            inner,
+            registry: self.registry.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ConnectionActivity<S> {
+    inner: S,
+    registry: Arc<ConnectionRegistry>,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for ConnectionActivity<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<ActiveBody<ResBody>>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(
+        &mut self,
+        req: http::Request<ReqBody>,
+    ) -> Self::Future {
+        let guard = remote_addr(&req).and_then(|addr| self.registry.get(&addr)).map(ActiveGuard::new);
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let response = response.await?;
+            Ok(response.map(|body| ActiveBody {
+                inner: body,
+                _guard: guard,
+            }))
+        })
+    }
+}
+
+fn remote_addr<B>(req: &http::Request<B>) -> Option<SocketAddr> {
+    match req.extensions().get::<TlsConnectInfo<TcpConnectInfo>>() {
+        Some(tls) => tls.get_ref().remote_addr(),
+        None => req.extensions().get::<TcpConnectInfo>().and_then(|info| info.remote_addr()),
+    }
+}
+
+/// Response body keeping its request in flight until it is dropped
+pub struct ActiveBody<B> {
+    inner: B,
+    _guard: Option<ActiveGuard>,
+}
+
+impl<B: Body + Unpin> Body for ActiveBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.get_mut().inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,66 @@
+use crate::common::shutdown::ShutdownReason;
+
+type Hook = Box<dyn Fn() + Send + Sync>;
+type ShutdownHook = Box<dyn Fn(&ShutdownReason) + Send + Sync>;
+
+/// Callbacks run at the lifecycle points of a server, for programs embedding actflow-server
+///
+/// They run on the server's runtime and should return quickly, e.g. after flipping a flag of the
+/// host application.
+#[derive(Default)]
+pub struct LifecycleHooks {
+    ready: Option<Hook>,
+    shutdown_start: Option<ShutdownHook>,
+    shutdown_complete: Option<Hook>,
+}
+
+impl LifecycleHooks {
+    /// Runs `hook` once the engine is ready and the health service reports SERVING
+    pub fn on_ready(
+        mut self,
+        hook: impl Fn() + Send + Sync + 'static,
+    ) -> Self {
+        self.ready = Some(Box::new(hook));
+        self
+    }
+
+    /// Runs `hook` with the shutdown reason as soon as a shutdown was initiated, before the running
+    /// workflows are told about it
+    pub fn on_shutdown_start(
+        mut self,
+        hook: impl Fn(&ShutdownReason) + Send + Sync + 'static,
+    ) -> Self {
+        self.shutdown_start = Some(Box::new(hook));
+        self
+    }
+
+    /// Runs `hook` once every listener stopped, also when the server stopped because one of them failed
+    pub fn on_shutdown_complete(
+        mut self,
+        hook: impl Fn() + Send + Sync + 'static,
+    ) -> Self {
+        self.shutdown_complete = Some(Box::new(hook));
+        self
+    }
+
+    pub(crate) fn ready(&self) {
+        if let Some(hook) = &self.ready {
+            hook();
+        }
+    }
+
+    pub(crate) fn shutdown_start(
+        &self,
+        reason: &ShutdownReason,
+    ) {
+        if let Some(hook) = &self.shutdown_start {
+            hook(reason);
+        }
+    }
+
+    pub(crate) fn shutdown_complete(&self) {
+        if let Some(hook) = &self.shutdown_complete {
+            hook();
+        }
+    }
+}
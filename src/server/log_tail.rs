@@ -0,0 +1,133 @@
+use std::{
+    io::{self, SeekFrom},
+    path::PathBuf,
+    time::Duration,
+};
+
+use log::warn;
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::mpsc,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Status;
+
+use crate::{common::shutdown::Shutdown, proto::LogLine};
+
+/// Interval between two checks of the log file for appended lines
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Maximum number of existing lines a client can ask for
+const MAX_TAIL_LINES: usize = 10_000;
+
+/// Size of the chunks the end of the file is read in while looking for the last lines
+const CHUNK_BYTES: u64 = 64 * 1024;
+
+pub type LogLineStream = ReceiverStream<Result<LogLine, Status>>;
+
+/// Streams the last `lines` lines of the log file at `path`, then the lines appended to it
+///
+/// Lines are sent as the logger wrote them. The file is reopened when it becomes shorter than
+/// what was already read, which is how a rotation shows up for a file that keeps its name. The
+/// stream ends when the client goes away or the server shuts down.
+pub async fn tail(
+    path: PathBuf,
+    lines: usize,
+    shutdown: Shutdown,
+) -> io::Result<LogLineStream> {
+    let mut file = File::open(&path).await?;
+    let mut pos = start_of_last_lines(&mut file, lines.min(MAX_TAIL_LINES)).await?;
+    file.seek(SeekFrom::Start(pos)).await?;
+
+    let (tx, rx) = mpsc::channel(100);
+    tokio::spawn(async move {
+        let stopped = shutdown.wait();
+        tokio::pin!(stopped);
+        let mut pending = Vec::new();
+        let mut buf = vec![0; CHUNK_BYTES as usize];
+        loop {
+            let read = match file.read(&mut buf).await {
+                Ok(read) => read,
+                Err(e) => {
+                    warn!("failed to read log file {}: {}", path.display(), e);
+                    let _ = tx.send(Err(Status::internal(format!("failed to read log file: {}", e)))).await;
+                    return;
+                }
+            };
+            if read > 0 {
+                pos += read as u64;
+                pending.extend_from_slice(&buf[..read]);
+                // a trailing partial line waits for the rest of it
+                while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = pending.drain(..=end).collect();
+                    let line = String::from_utf8_lossy(&line).trim_end_matches(['\n', '\r']).to_owned();
+                    if tx
+                        .send(Ok(LogLine {
+                            line,
+                        }))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                continue;
+            }
+
+            tokio::select! {
+                _ = &mut stopped => return,
+                _ = tx.closed() => return,
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+            if fs::metadata(&path).await.is_ok_and(|meta| meta.len() < pos)
+                && let Ok(reopened) = File::open(&path).await
+            {
+                file = reopened;
+                pos = 0;
+                pending.clear();
+            }
+        }
+    });
+
+    Ok(ReceiverStream::new(rx))
+}
+
+/// Returns the offset of the first of the last `lines` lines of `file`
+async fn start_of_last_lines(
+    file: &mut File,
+    lines: usize,
+) -> io::Result<u64> {
+    let len = file.metadata().await?.len();
+    if lines == 0 {
+        return Ok(len);
+    }
+
+    // a final newline ends the last line rather than starting an empty one
+    let mut newlines = 0;
+    let mut end = len;
+    let mut skip_final = true;
+    let mut buf = vec![0; CHUNK_BYTES as usize];
+    while end > 0 {
+        let start = end.saturating_sub(CHUNK_BYTES);
+        let chunk = &mut buf[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start)).await?;
+        file.read_exact(chunk).await?;
+        for (i, b) in chunk.iter().enumerate().rev() {
+            if *b != b'\n' {
+                skip_final = false;
+                continue;
+            }
+            if skip_final {
+                skip_final = false;
+                continue;
+            }
+            newlines += 1;
+            if newlines == lines {
+                return Ok(start + i as u64 + 1);
+            }
+        }
+        end = start;
+    }
+    Ok(0)
+}
@@ -1,27 +1,357 @@
+mod checkpoint;
+mod drain;
+mod engine;
+mod error;
+mod filter;
+mod handle;
+mod history;
+mod idle;
+mod lifecycle;
+mod log_tail;
+mod rate_limit;
+mod rest;
+mod scheduler;
+mod self_test;
+#[allow(clippy::module_inception)]
 mod server;
+mod sink;
+mod stream;
+mod tls;
+mod trace;
+mod tracker;
+mod web;
 
-use std::{net::ToSocketAddrs, sync::Arc};
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    sync::Arc,
+    time::Duration,
+};
 
-use actflow::Engine;
-use anyhow::{Result, anyhow};
-use log::info;
-use tonic::transport::server::Server as TonicServer;
+use log::{info, warn};
+use tokio::{net::TcpSocket, sync::watch, task::JoinSet};
+use tokio_stream::StreamExt;
+use tonic::{
+    codec::CompressionEncoding,
+    service::interceptor::InterceptedService,
+    transport::{
+        ServerTlsConfig,
+        server::{Server as TonicServer, TcpIncoming},
+    },
+};
+use tonic_health::{
+    ServingStatus,
+    pb::health_server::{Health, HealthServer},
+    server::health_reporter,
+};
+use tonic_web::GrpcWebLayer;
+use tower::util::option_layer;
 
-use crate::proto::workflow_service_server::WorkflowServiceServer;
+use crate::{
+    common::shutdown::{Shutdown, ShutdownReason},
+    config::{EventSinkConfig, ListenerConfig, ServerConfig},
+    proto::workflow_service_server::WorkflowServiceServer,
+};
+pub use checkpoint::{Checkpoint, CheckpointStore, FsCheckpointStore};
+pub use engine::EngineSlot;
+pub use error::ServerError;
+use filter::RpcFilterLayer;
+pub use handle::{LimitsReloader, ServerHandle};
+use idle::{ConnectionActivityLayer, ConnectionRegistry};
+pub use lifecycle::LifecycleHooks;
+use rate_limit::{RateLimitLayer, RateLimiter};
 use server::WorkflowServer;
+pub use sink::{EventSink, JsonLinesSink, NoopSink};
+pub use tls::ClientIdentity;
+#[cfg(feature = "otel")]
+pub use trace::OtelExporter;
+pub use trace::{TRACE_ID_HEADER, TRACEPARENT_HEADER};
 
+/// Room left in the decoding limit for the request fields besides the workflow model
+///
+/// Without it every oversized model would be rejected by the decoder with a generic error before
+/// `run_workflow` could report the model size limit.
+const REQUEST_OVERHEAD_BYTES: usize = 64 * 1024;
+
+/// Serves the workflow service on every configured listener until shutdown
+///
+/// `bound` receives the address of every listener once it is bound, at the listener's position in
+/// [`ServerConfig::effective_listeners`]. `announced` turns true once the running streams were told
+/// about the shutdown.
 pub async fn start_server(
-    engine: Arc<Engine>,
-    addr: impl ToSocketAddrs,
-    signal: impl Future<Output = ()>,
-) -> Result<()> {
-    let addr = addr.to_socket_addrs()?.next().ok_or_else(|| anyhow!("Invalid address"))?;
-    info!("actflow server linstening on {}", addr);
-
-    TonicServer::builder()
-        .add_service(WorkflowServiceServer::new(WorkflowServer::new(engine)))
-        .serve_with_shutdown(addr, signal)
-        .await?;
+    engine: Arc<EngineSlot>,
+    config: &ServerConfig,
+    bound: watch::Sender<Vec<Option<SocketAddr>>>,
+    announced_tx: watch::Sender<bool>,
+    mut reloaded: watch::Receiver<ServerConfig>,
+    hooks: Arc<LifecycleHooks>,
+    shutdown: Shutdown,
+) -> Result<(), ServerError> {
+    // listeners only start once the engine proved it can run a workflow
+    if config.self_test {
+        self_test::run(&engine.current()).await?;
+    }
+
+    // all listeners share one service so they see the same running workflows
+    let checkpoints = match &config.checkpoint {
+        Some(checkpoint) => {
+            let store = FsCheckpointStore::open(&checkpoint.dir)
+                .map_err(|e| ServerError::Internal(format!("failed to open checkpoint dir {}: {}", checkpoint.dir, e)))?;
+            Some(Arc::new(store) as Arc<dyn CheckpointStore>)
+        }
+        None => None,
+    };
+    let mut sinks = Vec::with_capacity(config.event_sinks.len());
+    for sink in &config.event_sinks {
+        let sink: Arc<dyn EventSink> = match sink {
+            EventSinkConfig::Stdout => Arc::new(JsonLinesSink::stdout()),
+            EventSinkConfig::File {
+                path,
+            } => Arc::new(
+                JsonLinesSink::open_file(path)
+                    .map_err(|e| ServerError::Internal(format!("failed to open event sink {}: {}", path, e)))?,
+            ),
+        };
+        sinks.push(sink);
+    }
+    let workflow_server = Arc::new(WorkflowServer::new(
+        engine.clone(),
+        config,
+        checkpoints,
+        sinks,
+        shutdown.clone(),
+    ));
+    if let Some(checkpoint) = &config.checkpoint {
+        workflow_server.resume_checkpoints(checkpoint.resume);
+    }
+
+    // the listeners only start their graceful shutdown once the running streams ended with a notice,
+    // so clients can tell a planned shutdown from a crash
+    let announced = announced_tx.subscribe();
+    {
+        let workflow_server = workflow_server.clone();
+        let shutdown = shutdown.clone();
+        let hooks = hooks.clone();
+        tokio::spawn(async move {
+            shutdown.wait().await;
+            let reason = shutdown.reason().unwrap_or(ShutdownReason::Manual);
+            hooks.shutdown_start(&reason);
+            workflow_server.announce_shutdown(&reason.to_string()).await;
+            let _ = announced_tx.send(true);
+        });
+    }
+    let tls = config.tls.as_ref().map(tls::load_tls_config).transpose()?;
+    // shared so a client cannot get around the limit by using several listeners
+    let rate_limiter = Arc::new(RateLimiter::new(
+        config.rate_limit_per_sec,
+        config.rate_limit_exempt_rpcs.iter().cloned(),
+    ));
+
+    // the settings documented as reloadable are applied live, the others only on restart
+    {
+        let workflow_server = workflow_server.clone();
+        let rate_limiter = rate_limiter.clone();
+        tokio::spawn(async move {
+            while reloaded.changed().await.is_ok() {
+                let config = reloaded.borrow_and_update().clone();
+                workflow_server.apply_limits(&config);
+                rate_limiter.set_limit(config.rate_limit_per_sec, config.rate_limit_exempt_rpcs.iter().cloned());
+                info!(
+                    "applied reloaded limits: max-concurrent-workflows {:?}, max-queued-workflows {}, rate-limit-per-sec {:?}",
+                    config.max_concurrent_workflows, config.max_queued_workflows, config.rate_limit_per_sec
+                );
+            }
+        });
+    }
+
+    // reports NOT_SERVING until the engine is ready
+    let (health_reporter, health_service) = health_reporter();
+    health_reporter.set_not_serving::<WorkflowServiceServer<WorkflowServer>>().await;
+    health_reporter.set_service_status("", ServingStatus::NotServing).await;
+
+    let mut listeners = JoinSet::new();
+    for (index, listener) in config.effective_listeners().into_iter().enumerate() {
+        let mut announced = announced.clone();
+        // also ends if the announcing task died
+        let stopped = async move {
+            let _ = announced.wait_for(|announced| *announced).await;
+        };
+        let server = serve_listener(
+            workflow_server.clone(),
+            health_service.clone(),
+            config.clone(),
+            listener,
+            tls.clone(),
+            rate_limiter.clone(),
+            index,
+            bound.clone(),
+            stopped,
+        );
+        listeners.spawn(server);
+    }
+
+    // shares the service and the rate limiter with the listeners, so REST clients see the same running
+    // workflows and cannot get around the limit either
+    if let Some(rest) = &config.rest {
+        let mut announced = announced.clone();
+        let stopped = async move {
+            let _ = announced.wait_for(|announced| *announced).await;
+        };
+        listeners.spawn(rest::serve(
+            workflow_server.clone(),
+            rate_limiter.clone(),
+            rest.clone(),
+            stopped,
+        ));
+    }
+
+    let current = engine.current();
+    let ready = self_test::wait_ready(&current, Duration::from_millis(config.engine_ready_timeout_ms));
+    tokio::select! {
+        res = ready => {
+            res?;
+            health_reporter.set_serving::<WorkflowServiceServer<WorkflowServer>>().await;
+            health_reporter.set_service_status("", ServingStatus::Serving).await;
+            hooks.ready();
+        }
+        // a listener failing to bind ends the startup right away
+        Some(res) = listeners.join_next() => {
+            res.map_err(|e| ServerError::Internal(format!("listener task failed: {}", e)))??;
+        }
+    }
+
+    while let Some(res) = listeners.join_next().await {
+        res.map_err(|e| ServerError::Internal(format!("listener task failed: {}", e)))??;
+    }
 
     Ok(())
 }
+
+#[allow(clippy::too_many_arguments)]
+async fn serve_listener(
+    workflow_server: Arc<WorkflowServer>,
+    health_service: HealthServer<impl Health>,
+    config: ServerConfig,
+    listener: ListenerConfig,
+    tls: Option<ServerTlsConfig>,
+    rate_limiter: Arc<RateLimiter>,
+    index: usize,
+    bound: watch::Sender<Vec<Option<SocketAddr>>>,
+    stopped: impl Future<Output = ()>,
+) -> Result<(), ServerError> {
+    let addr = listener
+        .address
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| ServerError::Bind(format!("invalid address {}", listener.address)))?;
+    let incoming = bind_with_retry(
+        addr,
+        config.tcp_backlog,
+        config.bind_retries,
+        Duration::from_millis(config.bind_retry_interval_ms),
+    )
+    .await?;
+    // differs from `addr` for port 0, where the OS picks the port
+    let addr = incoming.local_addr().map_err(|e| ServerError::Bind(format!("{}: {}", addr, e)))?;
+    let connections = Arc::new(ConnectionRegistry::default());
+    let incoming = {
+        let connections = connections.clone();
+        let idle_timeout = config.connection_idle_timeout_secs.map(Duration::from_secs);
+        incoming.map(move |stream| stream.map(|stream| connections.track(stream, idle_timeout)))
+    };
+    bound.send_modify(|addrs| addrs[index] = Some(addr));
+    if listener.allowed_rpcs.is_empty() {
+        info!(
+            "actflow server listener [{}] linstening on {}, serving all RPCs",
+            listener.name, addr
+        );
+    } else {
+        info!(
+            "actflow server listener [{}] linstening on {}, serving {:?}",
+            listener.name, addr, listener.allowed_rpcs
+        );
+    }
+
+    let mut service = WorkflowServiceServer::from_arc(workflow_server)
+        .max_decoding_message_size(config.max_model_bytes.saturating_add(REQUEST_OVERHEAD_BYTES));
+    if config.compression {
+        // compression is only applied when the client advertises support for it
+        service = service.send_compressed(CompressionEncoding::Gzip).accept_compressed(CompressionEncoding::Gzip);
+    }
+    let service = InterceptedService::new(service, tls::client_identity);
+
+    let mut builder = TonicServer::builder();
+    if let Some(tls) = tls {
+        builder = builder.tls_config(tls).map_err(|e| ServerError::Tls(e.to_string()))?;
+    }
+
+    // gRPC-Web runs over HTTP/1.1, CORS wraps it to answer the browser preflight requests
+    let cors = config.grpc_web.then(|| web::cors_layer(&config.cors_allowed_origins));
+    let grpc_web = config.grpc_web.then(GrpcWebLayer::new);
+
+    builder
+        .max_concurrent_streams(config.max_concurrent_streams)
+        .accept_http1(config.grpc_web)
+        // outermost, so a connection counts as busy until the response ended whatever the layers below do
+        .layer(ConnectionActivityLayer::new(connections))
+        .layer(option_layer(cors))
+        .layer(option_layer(grpc_web))
+        .layer(RpcFilterLayer::new(listener.allowed_rpcs))
+        // after the filter, so requests for RPCs the listener does not serve take no token
+        .layer(RateLimitLayer::new(rate_limiter))
+        .add_service(health_service)
+        .add_service(service)
+        .serve_with_incoming_shutdown(incoming, stopped)
+        .await
+        .map_err(|e| ServerError::Internal(format!("listener [{}] failed: {}", listener.name, e)))?;
+
+    Ok(())
+}
+
+/// Binds `addr`, retrying up to `retries` times with an exponentially growing interval
+///
+/// This smooths over the port still being held (e.g. in TIME_WAIT) during a rolling restart.
+async fn bind_with_retry(
+    addr: SocketAddr,
+    backlog: Option<u32>,
+    retries: u32,
+    interval: Duration,
+) -> Result<TcpIncoming, ServerError> {
+    let mut attempt = 0;
+    loop {
+        match bind(addr, backlog) {
+            Ok(incoming) => return Ok(incoming),
+            Err(e) if attempt < retries => {
+                let backoff = interval.saturating_mul(2u32.saturating_pow(attempt));
+                attempt += 1;
+                warn!(
+                    "failed to bind {} ({}), retrying in {:?} (attempt {}/{})",
+                    addr, e, backoff, attempt, retries
+                );
+                tokio::time::sleep(backoff).await;
+            }
+            Err(e) => return Err(ServerError::Bind(format!("{}: {}", addr, e))),
+        }
+    }
+}
+
+/// Binds `addr` with the given accept backlog, `None` leaves it to `TcpIncoming`
+fn bind(
+    addr: SocketAddr,
+    backlog: Option<u32>,
+) -> io::Result<TcpIncoming> {
+    let Some(backlog) = backlog else {
+        return TcpIncoming::bind(addr);
+    };
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    // like the standard library, so a restart can bind while old connections are in TIME_WAIT
+    #[cfg(unix)]
+    socket.set_reuseaddr(true)?;
+    socket.bind(addr)?;
+    Ok(socket.listen(backlog)?.into())
+}
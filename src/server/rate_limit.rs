@@ -0,0 +1,228 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    net::IpAddr,
+    pin::Pin,
+    sync::{Arc, Mutex, RwLock},
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use tonic::{
+    Status,
+    transport::server::{TcpConnectInfo, TlsConnectInfo},
+};
+use tower::{Layer, Service};
+
+use super::{filter::HEALTH_SERVICE_PREFIX, tls};
+
+/// Number of clients with a bucket above which the full buckets are dropped
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Token buckets of the clients, shared by every listener
+pub struct RateLimiter {
+    limit: RwLock<Limit>,
+    buckets: Mutex<HashMap<ClientKey, Bucket>>,
+}
+
+/// Settings of the rate limit, replaced on config reload
+struct Limit {
+    /// Tokens added per second, also the size of a bucket, `None` disables the limit
+    rate: Option<f64>,
+    /// RPC names that take no token
+    exempt: HashSet<String>,
+}
+
+/// What a client is told apart by, its certificate common name or else its IP address
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum ClientKey {
+    CommonName(String),
+    Ip(IpAddr),
+    Unknown,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing `rate_per_sec` requests per client, `None` lets every request through
+    pub fn new(
+        rate_per_sec: Option<u32>,
+        exempt: impl IntoIterator<Item = String>,
+    ) -> Self {
+        Self {
+            limit: RwLock::new(Limit {
+                rate: rate_per_sec.map(f64::from),
+                exempt: exempt.into_iter().collect(),
+            }),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replaces the rate and the exempt RPCs, the buckets keep their tokens up to the new rate
+    pub fn set_limit(
+        &self,
+        rate_per_sec: Option<u32>,
+        exempt: impl IntoIterator<Item = String>,
+    ) {
+        *self.limit.write().unwrap() = Limit {
+            rate: rate_per_sec.map(f64::from),
+            exempt: exempt.into_iter().collect(),
+        };
+    }
+
+    /// Takes a token for a call of RPC `method`, returns the rate it exceeds if the client's bucket is empty
+    fn check(
+        &self,
+        method: &str,
+        client: impl FnOnce() -> ClientKey,
+    ) -> Result<(), f64> {
+        let limit = self.limit.read().unwrap();
+        match limit.rate {
+            Some(rate) if !limit.exempt.contains(method) => self.try_acquire(client(), rate).then_some(()).ok_or(rate),
+            _ => Ok(()),
+        }
+    }
+
+    /// Takes a token for a call of RPC `method` by the client at `ip`, for requests not going through tonic
+    ///
+    /// Returns the `RESOURCE_EXHAUSTED` status to answer if the client's bucket is empty.
+    pub fn check_ip(
+        &self,
+        method: &str,
+        ip: IpAddr,
+    ) -> Result<(), Status> {
+        self.check(method, || ClientKey::Ip(ip)).map_err(exceeded)
+    }
+
+    /// Takes a token from the client's bucket, `false` if it is empty
+    fn try_acquire(
+        &self,
+        client: ClientKey,
+        rate: f64,
+    ) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            // a full bucket behaves like a missing one
+            buckets.retain(|_, bucket| bucket.refilled(now, rate) < rate);
+        }
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: rate,
+            updated: now,
+        });
+        bucket.tokens = bucket.refilled(now, rate);
+        bucket.updated = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+impl Bucket {
+    /// Returns the tokens in the bucket at `now`
+    fn refilled(
+        &self,
+        now: Instant,
+        rate: f64,
+    ) -> f64 {
+        (self.tokens + now.duration_since(self.updated).as_secs_f64() * rate).min(rate)
+    }
+}
+
+/// Layer rejecting the requests of clients exceeding the rate limit
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            limiter,
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(
+        &self,
+        inner: S,
+    ) -> Self::Service {
+        RateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+/// Service answering `RESOURCE_EXHAUSTED` for requests beyond the rate limit of their client
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for RateLimit<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+    ResBody: Default,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(
+        &mut self,
+        req: http::Request<ReqBody>,
+    ) -> Self::Future {
+        // gRPC paths look like "/<package>.<Service>/<Method>"
+        let path = req.uri().path();
+        let method = path.rsplit('/').next().unwrap_or_default();
+        if path.starts_with(HEALTH_SERVICE_PREFIX) {
+            return Box::pin(self.inner.call(req));
+        }
+        match self.limiter.check(method, || client_key(&req)) {
+            Ok(()) => Box::pin(self.inner.call(req)),
+            Err(rate) => {
+                let status = exceeded(rate);
+                Box::pin(async move { Ok(status.into_http()) })
+            }
+        }
+    }
+}
+
+fn exceeded(rate: f64) -> Status {
+    Status::resource_exhausted(format!("rate limit of {} requests per second exceeded", rate))
+}
+
+/// Identifies the client of a request, the interceptor adding the `ClientIdentity` only runs after this layer
+fn client_key<B>(req: &http::Request<B>) -> ClientKey {
+    if let Some(tls) = req.extensions().get::<TlsConnectInfo<TcpConnectInfo>>() {
+        let common_name = tls.peer_certs().and_then(|certs| certs.first().and_then(|cert| tls::common_name(cert)));
+        if let Some(common_name) = common_name {
+            return ClientKey::CommonName(common_name);
+        }
+        if let Some(addr) = tls.get_ref().remote_addr() {
+            return ClientKey::Ip(addr.ip());
+        }
+    }
+    match req.extensions().get::<TcpConnectInfo>().and_then(|info| info.remote_addr()) {
+        Some(addr) => ClientKey::Ip(addr.ip()),
+        None => ClientKey::Unknown,
+    }
+}
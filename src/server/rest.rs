@@ -0,0 +1,208 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::{SocketAddr, ToSocketAddrs},
+    sync::Arc,
+};
+
+use axum::{
+    Json, Router,
+    extract::{ConnectInfo, Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::post,
+};
+use log::info;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio_stream::StreamExt;
+use tonic::{Code, Status, transport::server::TcpConnectInfo};
+
+use super::{ServerError, TRACE_ID_HEADER, TRACEPARENT_HEADER, rate_limit::RateLimiter, server::WorkflowServer};
+use crate::{
+    config::RestConfig,
+    proto::{RunWorkflowRequest, StopWorkflowRequest, workflow_service_server::WorkflowService},
+};
+
+/// Body of `POST /workflows`, the fields of `RunWorkflowRequest` a REST client can set
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RunWorkflowBody {
+    /// The workflow model, as a JSON object or as a string holding it
+    workflow_model: serde_json::Value,
+    node_filter: Vec<String>,
+    labels: HashMap<String, String>,
+    priority: i32,
+    idle_timeout_secs: u64,
+    client_pid: String,
+    log_level: String,
+    capture_outputs: bool,
+}
+
+/// State shared by the handlers of the gateway
+#[derive(Clone)]
+struct Gateway {
+    workflow_server: Arc<WorkflowServer>,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+/// Body of an error response, and of the `error` event ending a stream that failed
+#[derive(Serialize)]
+struct ErrorBody {
+    code: String,
+    message: String,
+}
+
+/// Serves the REST gateway on its own address until `stopped` resolves
+pub async fn serve(
+    workflow_server: Arc<WorkflowServer>,
+    rate_limiter: Arc<RateLimiter>,
+    config: RestConfig,
+    stopped: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), ServerError> {
+    let addr = config
+        .address
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| ServerError::Bind(format!("invalid REST gateway address {}", config.address)))?;
+    let listener = TcpListener::bind(addr).await.map_err(|e| ServerError::Bind(format!("{}: {}", addr, e)))?;
+    let addr = listener.local_addr().map_err(|e| ServerError::Bind(format!("{}: {}", addr, e)))?;
+    info!("actflow REST gateway listening on {}", addr);
+
+    axum::serve(
+        listener,
+        router(Gateway {
+            workflow_server,
+            rate_limiter,
+        })
+        .into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(stopped)
+    .await
+    .map_err(|e| ServerError::Internal(format!("REST gateway failed: {}", e)))
+}
+
+/// Builds the REST gateway, mapping HTTP/JSON requests onto the workflow service
+///
+/// `POST /workflows` runs a workflow and streams its events as server-sent events, each holding
+/// the JSON of a `WorkflowEvent`; `POST /workflows/{pid}/stop` stops a run. Both count against
+/// the rate limit of the client's IP address as the RPC they map onto.
+fn router(gateway: Gateway) -> Router {
+    Router::new().route("/workflows", post(run_workflow)).route("/workflows/{pid}/stop", post(stop_workflow)).with_state(gateway)
+}
+
+async fn run_workflow(
+    State(gateway): State<Gateway>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(body): Json<RunWorkflowBody>,
+) -> Result<Response, ErrorResponse> {
+    gateway.rate_limiter.check_ip("RunWorkflow", remote_addr.ip())?;
+    let workflow_model = match body.workflow_model {
+        serde_json::Value::String(model) => model,
+        model => model.to_string(),
+    };
+    let mut request = with_peer(
+        RunWorkflowRequest {
+            workflow_model,
+            node_filter: body.node_filter,
+            labels: body.labels,
+            priority: body.priority,
+            idle_timeout_secs: body.idle_timeout_secs,
+            client_pid: body.client_pid,
+            log_level: body.log_level,
+            capture_outputs: body.capture_outputs,
+            ..Default::default()
+        },
+        remote_addr,
+    );
+    if let Some(traceparent) = headers.get(TRACEPARENT_HEADER).and_then(|value| value.to_str().ok())
+        && let Ok(value) = traceparent.parse()
+    {
+        request.metadata_mut().insert(TRACEPARENT_HEADER, value);
+    }
+    let response = gateway.workflow_server.run_workflow(request).await?;
+    let trace_id = response.metadata().get(TRACE_ID_HEADER).and_then(|value| HeaderValue::from_bytes(value.as_bytes()).ok());
+    let stream = response.into_inner();
+    let events = stream.map(|item| {
+        Ok::<_, Infallible>(match item {
+            Ok(event) => Event::default().json_data(event).unwrap_or_default(),
+            Err(status) => Event::default().event("error").json_data(error_body(&status)).unwrap_or_default(),
+        })
+    });
+    let mut response = Sse::new(events).keep_alive(KeepAlive::default()).into_response();
+    if let Some(trace_id) = trace_id {
+        response.headers_mut().insert(TRACE_ID_HEADER, trace_id);
+    }
+    Ok(response)
+}
+
+async fn stop_workflow(
+    State(gateway): State<Gateway>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    Path(pid): Path<String>,
+) -> Result<Response, ErrorResponse> {
+    gateway.rate_limiter.check_ip("StopWorkflow", remote_addr.ip())?;
+    let request = with_peer(
+        StopWorkflowRequest {
+            pid,
+            ..Default::default()
+        },
+        remote_addr,
+    );
+    let response = gateway.workflow_server.stop_workflow(request).await?.into_inner();
+    Ok(Json(response).into_response())
+}
+
+/// Wraps `message` like tonic would, so the service logs the client address
+fn with_peer<T>(
+    message: T,
+    remote_addr: SocketAddr,
+) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    request.extensions_mut().insert(TcpConnectInfo {
+        local_addr: None,
+        remote_addr: Some(remote_addr),
+    });
+    request
+}
+
+fn error_body(status: &Status) -> ErrorBody {
+    ErrorBody {
+        code: format!("{:?}", status.code()),
+        message: status.message().to_owned(),
+    }
+}
+
+/// A failed call answered with the HTTP status closest to its gRPC code
+struct ErrorResponse(Status);
+
+impl From<Status> for ErrorResponse {
+    fn from(status: Status) -> Self {
+        Self(status)
+    }
+}
+
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> Response {
+        let code = match self.0.code() {
+            Code::InvalidArgument | Code::OutOfRange => StatusCode::BAD_REQUEST,
+            Code::NotFound => StatusCode::NOT_FOUND,
+            Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+            Code::PermissionDenied => StatusCode::FORBIDDEN,
+            Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+            Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+            Code::FailedPrecondition => StatusCode::PRECONDITION_FAILED,
+            Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+            Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+            Code::Cancelled => StatusCode::REQUEST_TIMEOUT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (code, Json(error_body(&self.0))).into_response()
+    }
+}
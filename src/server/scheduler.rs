@@ -0,0 +1,194 @@
+use std::{
+    cmp::Ordering as CmpOrdering,
+    collections::BinaryHeap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::oneshot;
+
+use super::ServerError;
+
+/// Limits the number of running workflows, queueing further runs by priority
+pub struct Scheduler {
+    state: Mutex<State>,
+}
+
+struct State {
+    /// Maximum number of running workflows, `None` means unlimited
+    max_running: Option<usize>,
+    /// Maximum number of runs waiting for a permit
+    max_queued: usize,
+    running: usize,
+    queue: BinaryHeap<Waiting>,
+    /// Sequence number keeping runs with equal priority in submission order
+    seq: u64,
+}
+
+impl State {
+    /// Checks whether another workflow may run
+    fn has_room(&self) -> bool {
+        self.max_running.is_none_or(|max| self.running < max)
+    }
+}
+
+/// A run waiting for a permit
+struct Waiting {
+    priority: i32,
+    seq: u64,
+    tx: oneshot::Sender<Permit>,
+}
+
+impl PartialEq for Waiting {
+    fn eq(
+        &self,
+        other: &Self,
+    ) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for Waiting {}
+
+impl PartialOrd for Waiting {
+    fn partial_cmp(
+        &self,
+        other: &Self,
+    ) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiting {
+    /// Higher priority first, then earlier submission
+    fn cmp(
+        &self,
+        other: &Self,
+    ) -> CmpOrdering {
+        self.priority.cmp(&other.priority).then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// Permission to run a workflow, handed to the next waiting run when dropped
+pub struct Permit {
+    /// `None` for a permit that was never handed out
+    scheduler: Option<Arc<Scheduler>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(scheduler) = self.scheduler.take() {
+            scheduler.release();
+        }
+    }
+}
+
+impl Scheduler {
+    pub fn new(
+        max_running: Option<usize>,
+        max_queued: usize,
+    ) -> Self {
+        Self {
+            state: Mutex::new(State {
+                max_running,
+                max_queued,
+                running: 0,
+                queue: BinaryHeap::new(),
+                seq: 0,
+            }),
+        }
+    }
+
+    /// Changes the limits, for runs asking for a permit from now on
+    ///
+    /// A higher limit starts waiting runs right away. A lower one stops no running workflow, new
+    /// runs wait until enough of them finished; runs already queued keep their place.
+    pub fn set_limits(
+        self: &Arc<Self>,
+        max_running: Option<usize>,
+        max_queued: usize,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        state.max_running = max_running;
+        state.max_queued = max_queued;
+        self.start_waiting(&mut state);
+    }
+
+    /// Waits for a permit to run a workflow
+    ///
+    /// Fails right away when the limit is reached and the queue is full. Dropping the returned
+    /// future gives up the place in the queue.
+    pub async fn acquire(
+        self: &Arc<Self>,
+        priority: i32,
+    ) -> Result<Permit, ServerError> {
+        let rx = {
+            let mut state = self.state.lock().unwrap();
+            if state.has_room() {
+                state.running += 1;
+                return Ok(self.permit());
+            }
+            // waiting runs whose client went away no longer count
+            state.queue.retain(|waiting| !waiting.tx.is_closed());
+            if state.queue.len() >= state.max_queued {
+                return Err(ServerError::QueueFull(format!(
+                    "{} workflows running and {} queued",
+                    state.running,
+                    state.queue.len()
+                )));
+            }
+            let (tx, rx) = oneshot::channel();
+            let seq = state.seq;
+            state.seq += 1;
+            state.queue.push(Waiting {
+                priority,
+                seq,
+                tx,
+            });
+            rx
+        };
+        // a permit received but not taken because this future was dropped goes to the next run
+        rx.await.map_err(|_| ServerError::Internal("scheduler dropped a queued run".to_owned()))
+    }
+
+    /// Returns a permit regardless of the limit, for runs the server starts itself
+    pub fn acquire_now(self: &Arc<Self>) -> Permit {
+        self.state.lock().unwrap().running += 1;
+        self.permit()
+    }
+
+    /// Returns the number of runs waiting for a permit
+    pub fn queued(&self) -> usize {
+        self.state.lock().unwrap().queue.len()
+    }
+
+    fn permit(self: &Arc<Self>) -> Permit {
+        Permit {
+            scheduler: Some(self.clone()),
+        }
+    }
+
+    /// Hands the released slot to the highest priority waiting run, if the limit allows it
+    fn release(self: &Arc<Self>) {
+        let mut state = self.state.lock().unwrap();
+        state.running -= 1;
+        self.start_waiting(&mut state);
+    }
+
+    /// Hands permits to the highest priority waiting runs while the limit allows it
+    fn start_waiting(
+        self: &Arc<Self>,
+        state: &mut State,
+    ) {
+        while state.has_room() {
+            let Some(waiting) = state.queue.pop() else {
+                break;
+            };
+            state.running += 1;
+            if let Err(mut permit) = waiting.tx.send(self.permit()) {
+                // the client went away, the permit must not release the slot again
+                permit.scheduler = None;
+                state.running -= 1;
+            }
+        }
+    }
+}
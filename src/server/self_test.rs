@@ -0,0 +1,93 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use actflow::{ChannelEvent, ChannelOptions, Engine, GraphEvent, WorkflowEvent, WorkflowModel};
+use log::{info, warn};
+use tokio::sync::oneshot;
+
+use super::ServerError;
+
+/// Built-in workflow run by the self test, a start node followed by an end node
+const SELF_TEST_WORKFLOW: &str = r#"{
+    "id": "actflow-server-self-test", "name": "self test", "desc": "", "env": {},
+    "nodes": [
+        {"id": "start", "title": "start", "desc": "", "uses": "start", "action": {}},
+        {"id": "end", "title": "end", "desc": "", "uses": "end", "action": {}}
+    ],
+    "edges": [{"id": "e1", "source": "start", "target": "end", "source_handle": "source"}]
+}"#;
+
+/// How long the self test workflow may take before the engine is considered unhealthy
+const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a single readiness probe may take before it is tried again
+const READY_PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Runs the built-in workflow through the engine and checks that it succeeds
+pub async fn run(engine: &Engine) -> Result<(), ServerError> {
+    let started = Instant::now();
+    probe(engine, SELF_TEST_TIMEOUT).await.map_err(ServerError::SelfTest)?;
+    info!("self test passed in {} ms", started.elapsed().as_millis());
+    Ok(())
+}
+
+/// Waits until the engine runs the built-in workflow, trying again until `timeout` elapsed
+///
+/// `Engine::launch` returns before the engine processes events, a process started too early
+/// would never report its events.
+pub async fn wait_ready(
+    engine: &Engine,
+    timeout: Duration,
+) -> Result<(), ServerError> {
+    let started = Instant::now();
+    loop {
+        let remaining = timeout.saturating_sub(started.elapsed());
+        match probe(engine, remaining.min(READY_PROBE_TIMEOUT)).await {
+            Ok(()) => {
+                info!("engine ready after {} ms", started.elapsed().as_millis());
+                return Ok(());
+            }
+            Err(e) if started.elapsed() >= timeout => {
+                return Err(ServerError::NotReady(format!(
+                    "no successful probe within {} ms, last error: {}",
+                    timeout.as_millis(),
+                    e
+                )));
+            }
+            Err(e) => warn!("engine readiness probe failed: {}", e),
+        }
+    }
+}
+
+/// Runs the built-in workflow once, failing if it does not succeed within `timeout`
+async fn probe(
+    engine: &Engine,
+    timeout: Duration,
+) -> Result<(), String> {
+    let model: WorkflowModel = serde_json::from_str(SELF_TEST_WORKFLOW).map_err(|e| e.to_string())?;
+    let process = engine.build_workflow_process(&model).map_err(|e| e.to_string())?;
+
+    let (tx, rx) = oneshot::channel();
+    let tx = Arc::new(Mutex::new(Some(tx)));
+    ChannelEvent::channel(engine.channel(), ChannelOptions::with_pid(process.id().to_owned())).on_event(move |event| {
+        let outcome = match &event.event {
+            GraphEvent::Workflow(WorkflowEvent::Succeeded) => Ok(()),
+            GraphEvent::Workflow(WorkflowEvent::Failed(err)) => Err(err.error.clone()),
+            GraphEvent::Workflow(WorkflowEvent::Aborted(aborted)) => Err(format!("aborted: {}", aborted.reason)),
+            _ => return,
+        };
+        if let Some(tx) = tx.lock().unwrap().take() {
+            let _ = tx.send(outcome);
+        }
+    });
+    process.start();
+
+    match tokio::time::timeout(timeout, rx).await {
+        Ok(Ok(Ok(()))) => Ok(()),
+        Ok(Ok(Err(e))) => Err(format!("workflow failed: {}", e)),
+        Ok(Err(_)) => Err("engine dropped the workflow".to_owned()),
+        Err(_) => Err(format!("workflow did not finish within {} ms", timeout.as_millis())),
+    }
+}
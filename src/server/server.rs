@@ -1,212 +1,1743 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
-use actflow::{ChannelEvent, ChannelOptions, Engine};
+use actflow::{ActflowError, ChannelEvent, ChannelOptions, Engine};
 use anyhow::Result;
-use log::{error, info};
-use tokio::sync::mpsc;
-use tokio_stream::wrappers::ReceiverStream;
-use tonic::{Response, Status};
-
-use crate::proto::{
-    RunWorkflowRequest, StopWorkflowRequest, StopWorkflowResponse, WorkflowEvent, workflow_event::Event as ProtoEvent,
-    workflow_service_server::WorkflowService,
+use log::{Level, error, info, warn};
+use serde::Deserialize;
+use tokio::{runtime::Handle, sync::mpsc, task::JoinSet};
+use tokio_stream::StreamExt;
+use tonic::{
+    Response, Status,
+    metadata::{MetadataMap, MetadataValue},
+};
+
+use super::{
+    ClientIdentity, EngineSlot, ServerError,
+    checkpoint::{Checkpoint, CheckpointStore},
+    drain::{self, DrainStream},
+    history::{HistoryRecord, HistoryStore},
+    log_tail::{self, LogLineStream},
+    scheduler::{Permit, Scheduler},
+    self_test,
+    sink::EventSink,
+    stream::{EventStream, StreamSender},
+    trace::{RunTrace, TRACE_ID_HEADER, TRACEPARENT_HEADER},
+    tracker::{CAUSAL_REORDER_WINDOW, ProcessTracker, RunOptions, RunOutcome, SubscribeError, TrackedProcess},
 };
+use crate::{
+    common::{VERSION_INFO, instance::resolve_instance_id, shutdown::Shutdown},
+    config::{EventOrdering, ServerConfig},
+    proto::{
+        CancelAllRequest, CancelAllResponse, DrainRequest, Empty, EventCategory, ListWorkflowsRequest, ListWorkflowsResponse,
+        ReloadEngineRequest, ReloadEngineResponse, ReplayWorkflowRequest, RetryPolicy, RunWorkflowRequest, ServerStatsResponse,
+        SetAcceptingRequest, SetAcceptingResponse, StopWorkflowRequest, StopWorkflowResponse, SubscribeWorkflowRequest,
+        TailRequest, VersionResponse, WorkflowEvent, WorkflowInfo, workflow_event::Event as ProtoEvent,
+        workflow_service_server::WorkflowService,
+    },
+};
+
+/// Time given to the engine to report the abort of a stopped process before the server reports it itself
+const STOP_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Time the streams of running workflows get to take the shutdown notice
+const SHUTDOWN_NOTICE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Abort reason reported when the engine never confirmed a stop
+const STOP_FALLBACK_REASON: &str = "Aborted by command";
+
+/// Error reported on the stream of a workflow whose handling in the server panicked
+const PANIC_ERR_MSG: &str = "internal panic";
+
+/// Abort reason reported for a workflow stopped for being idle
+const IDLE_TIMEOUT_REASON: &str = "idle timeout";
+
+/// Abort reason reported for a workflow stopped at the deadline its client set
+const DEADLINE_EXCEEDED_REASON: &str = "deadline exceeded";
+
+/// Abort reason reported for a workflow stopped for running longer than the server allows
+const MAX_RUNTIME_REASON: &str = "server max runtime exceeded";
+
+/// Parts of engine abort reasons pointing at a failure inside the engine rather than a request to stop
+///
+/// The engine reports no category with an abort, so it is told apart by the reason. Reasons
+/// matching none of these are reported as a `WorkflowAbort`.
+const ENGINE_ERROR_REASONS: [&str; 3] = ["internal error", "engine error", "panicked"];
+
+/// Maximum number of retries a retry policy can ask for
+const MAX_RETRIES: u32 = 100;
+
+/// Maximum pause before a retry a retry policy can ask for
+const MAX_RETRY_INTERVAL_MS: u64 = 60 * 60 * 1000;
+
+/// Maximum nesting of arrays and objects in a workflow model
+///
+/// Below the recursion limit of serde_json, so a deeply nested model is rejected with a clear
+/// error before the deserializer recurses into it.
+const MAX_MODEL_DEPTH: usize = 64;
 
 pub struct WorkflowServer {
-    engine: Arc<Engine>,
+    /// Name of the server instance, reported in the workflow start events and the history
+    instance_id: Arc<str>,
+    /// Engine the workflows run on, replaced by `reload_engine`
+    engine: Arc<EngineSlot>,
+    /// Time a reloaded engine has to become ready
+    engine_ready_timeout: Duration,
+    tracker: Arc<ProcessTracker>,
+    /// Maximum size of a workflow model in bytes
+    max_model_bytes: usize,
+    /// How node events and logs are ordered on the streams
+    event_ordering: EventOrdering,
+    /// Maximum number of events buffered for a client that opted into replay, reloadable
+    replay_buffer_size: AtomicUsize,
+    /// Milliseconds a client without a replay buffer has to make room in its stream, reloadable
+    stream_send_timeout_ms: AtomicU64,
+    /// Queue the events of every run without bound until its client takes them
+    unbounded_events: bool,
+    /// Where running processes are checkpointed, `None` disables checkpointing
+    checkpoints: Option<Arc<dyn CheckpointStore>>,
+    /// Where the events of every process are forwarded to besides the client streams
+    sinks: Arc<[Arc<dyn EventSink>]>,
+    /// Send a `NodeStart` event with the inputs of a node before it runs
+    capture_node_inputs: bool,
+    max_captured_output_bytes: usize,
+    /// Whether `run_workflow` accepts new runs, cleared to quiesce the server before a restart
+    accepting: AtomicBool,
+    /// Reject a run whose workflow id is already running
+    reject_duplicate_wid: bool,
+    /// Node types workflows may use, empty allows every type
+    allowed_node_types: HashSet<String>,
+    /// Node types workflows may not use
+    denied_node_types: HashSet<String>,
+    /// Serializes the duplicate check with the tracking of the new process
+    launch_lock: Mutex<()>,
+    /// Maximum size of a streamed log line in bytes, `None` means unbounded
+    max_log_line_bytes: Option<usize>,
+    /// Recently started runs, kept so they can be replayed
+    history: HistoryStore,
+    /// End the streams with the `WorkflowPause` event
+    close_stream_on_pause: bool,
+    /// Maximum number of subscriber streams per process
+    max_subscribers_per_workflow: usize,
+    /// When the server was created, for the uptime
+    started_at: Instant,
+    /// Limits the number of running workflows
+    scheduler: Arc<Scheduler>,
+    /// How long a workflow may go without events or logs before it is aborted, `None` never aborts it
+    idle_timeout: Option<Duration>,
+    /// How long any workflow may run before it is aborted, `None` never aborts it
+    max_workflow_runtime: Option<Duration>,
+    /// Log file streamed by `tail_server_log`, `None` when the server does not write one
+    server_log_file: Option<PathBuf>,
+    /// Ends the log tail and drain streams when the server shuts down
+    shutdown: Shutdown,
 }
 
 impl WorkflowServer {
-    pub fn new(engine: Arc<Engine>) -> Self {
+    pub fn new(
+        engine: Arc<EngineSlot>,
+        config: &ServerConfig,
+        checkpoints: Option<Arc<dyn CheckpointStore>>,
+        sinks: Vec<Arc<dyn EventSink>>,
+        shutdown: Shutdown,
+    ) -> Self {
         Self {
+            instance_id: config.instance_id.clone().unwrap_or_else(|| resolve_instance_id(None)).into(),
             engine,
+            engine_ready_timeout: Duration::from_millis(config.engine_ready_timeout_ms),
+            tracker: Arc::new(ProcessTracker::new()),
+            max_model_bytes: config.max_model_bytes,
+            event_ordering: config.event_ordering,
+            replay_buffer_size: AtomicUsize::new(config.replay_buffer_size),
+            stream_send_timeout_ms: AtomicU64::new(config.stream_send_timeout_ms),
+            unbounded_events: config.unbounded_events,
+            checkpoints,
+            sinks: sinks.into(),
+            capture_node_inputs: config.capture_node_inputs,
+            max_captured_output_bytes: config.max_captured_output_bytes,
+            accepting: AtomicBool::new(true),
+            reject_duplicate_wid: config.reject_duplicate_wid,
+            allowed_node_types: config.allowed_node_types.iter().cloned().collect(),
+            denied_node_types: config.denied_node_types.iter().cloned().collect(),
+            launch_lock: Mutex::new(()),
+            max_log_line_bytes: config.max_log_line_bytes,
+            history: HistoryStore::new(config.history_size),
+            close_stream_on_pause: config.close_stream_on_pause,
+            max_subscribers_per_workflow: config.max_subscribers_per_workflow,
+            started_at: Instant::now(),
+            scheduler: Arc::new(Scheduler::new(config.max_concurrent_workflows, config.max_queued_workflows)),
+            idle_timeout: config.workflow_idle_timeout_secs.map(Duration::from_secs),
+            max_workflow_runtime: config.max_workflow_runtime_secs.map(Duration::from_secs),
+            server_log_file: config.server_log_file.clone(),
+            shutdown,
+        }
+    }
+
+    /// Stops the running process of a workflow for a client that does not know its pid
+    ///
+    /// Unless duplicate wids are rejected several processes may run the workflow, which one the
+    /// client meant is then unknown, so nothing is stopped.
+    fn stop_by_wid(
+        &self,
+        wid: &str,
+        peer: &str,
+    ) -> RR<StopWorkflowResponse> {
+        if wid.is_empty() {
+            return Err(Status::invalid_argument("pid or wid is required"));
+        }
+        let procs = self.tracker.by_wid(wid);
+        match procs.len() {
+            0 => return Err(Status::not_found(format!("no running process of workflow {}", wid))),
+            1 => {}
+            n => {
+                return Err(Status::failed_precondition(format!(
+                    "{} processes run workflow {}, stop them by pid",
+                    n, wid
+                )));
+            }
+        }
+
+        info!("stopping workflow {} (process {}) for {}", wid, procs[0].pid, peer);
+        Ok(Response::new(match self.stop_tracked(&procs[0]) {
+            Ok(()) => StopWorkflowResponse {
+                success: true,
+                err_msg: "".to_string(),
+                stopped: 1,
+            },
+            Err(err) => StopWorkflowResponse {
+                success: false,
+                err_msg: err.to_string(),
+                stopped: 0,
+            },
+        }))
+    }
+
+    /// Applies the workflow limits of `config` that take effect without a restart
+    ///
+    /// The concurrency and queue limits apply to runs asking for a slot from now on, the stream
+    /// settings to runs started from now on. Running workflows keep going either way.
+    pub fn apply_limits(
+        &self,
+        config: &ServerConfig,
+    ) {
+        self.scheduler.set_limits(config.max_concurrent_workflows, config.max_queued_workflows);
+        self.replay_buffer_size.store(config.replay_buffer_size, Ordering::SeqCst);
+        self.stream_send_timeout_ms.store(config.stream_send_timeout_ms, Ordering::SeqCst);
+    }
+
+    /// Builds a new engine and makes it the current one once it is ready, shutting down the old one
+    async fn replace_engine(&self) -> Result<(), ServerError> {
+        let engine = self.engine.build()?;
+        if let Err(e) = self_test::wait_ready(&engine, self.engine_ready_timeout).await {
+            engine.shutdown();
+            return Err(e);
+        }
+        self.engine.replace(engine).shutdown();
+        Ok(())
+    }
+
+    fn supervisor(&self) -> Supervisor {
+        Supervisor {
+            engine: self.engine.current(),
+            tracker: self.tracker.clone(),
+            checkpoints: self.checkpoints.clone(),
+            sinks: self.sinks.clone(),
+            instance_id: self.instance_id.clone(),
+        }
+    }
+
+    /// Rejects a model with a node of a type the server does not allow, naming the first such node
+    fn check_node_types(
+        &self,
+        model: &actflow::WorkflowModel,
+    ) -> Result<(), ServerError> {
+        let denied = model.nodes.iter().find(|node| {
+            self.denied_node_types.contains(&node.uses)
+                || (!self.allowed_node_types.is_empty() && !self.allowed_node_types.contains(&node.uses))
+        });
+        match denied {
+            Some(node) => Err(ServerError::NodeTypeDenied(node.id.clone(), node.uses.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Builds and starts a workflow process, returning its event stream
+    fn launch(
+        &self,
+        request: LaunchRequest,
+    ) -> Result<EventStream, ServerError> {
+        let LaunchRequest {
+            workflow_model,
+            node_filter,
+            labels,
+            replay,
+            peer,
+            replay_of,
+            permit,
+            idle_timeout,
+            deadline,
+            retry_policy,
+            client_pid,
+            event_mask,
+            log_level,
+            capture_outputs,
+            trace,
+        } = request;
+        let mut model = parse_model(workflow_model)?;
+        self.check_node_types(&model)?;
+        // the history and checkpoints keep the model as run, so replays and resumes retry the same way
+        let workflow_model = match retry_policy {
+            Some(policy) => {
+                apply_retry_policy(&mut model, &policy);
+                Cow::Owned(serde_json::to_string(&model).map_err(|e| ServerError::Internal(e.to_string()))?)
+            }
+            None => Cow::Borrowed(workflow_model),
+        };
+        let wid = model.id.clone();
+        let total_nodes = model.nodes.len() as u32;
+        let node_inputs = self.capture_node_inputs.then(|| NodeInputs::new(&model));
+
+        // held until the process is tracked, so concurrent runs of the same wid or client pid cannot both pass the check
+        let _launch_guard = (self.reject_duplicate_wid || client_pid.is_some()).then(|| self.launch_lock.lock().unwrap());
+        if self.reject_duplicate_wid && self.tracker.is_running_wid(&wid) {
+            return Err(ServerError::DuplicateWorkflow(wid));
+        }
+        if let Some(client_pid) = &client_pid
+            && self.tracker.get(client_pid).is_some()
+        {
+            return Err(ServerError::DuplicateProcess(client_pid.clone()));
+        }
+
+        match peer {
+            Some(peer) => info!("running workflow: {} from {}", wid, peer),
+            None => info!("running workflow: {}", wid),
+        }
+
+        let engine = self.engine.current();
+        let porc = engine.build_workflow_process(&model).map_err(ServerError::WorkflowBuild)?;
+        let pid = porc.id();
+        trace.set_process(pid, &wid);
+        if let Some(original) = replay_of {
+            info!("workflow process {} replays {}", pid, original);
+        }
+        if let Some(client_pid) = &client_pid {
+            info!("workflow process {} is known to its client as {}", pid, client_pid);
+        }
+
+        self.history.record(HistoryRecord {
+            pid: pid.to_owned(),
+            wid: wid.clone(),
+            workflow_model: workflow_model.to_string(),
+            node_filter: node_filter.clone(),
+            labels: labels.clone(),
+            peer: peer.map(str::to_owned),
+            replay_of: replay_of.map(str::to_owned),
+            instance_id: self.instance_id.to_string(),
+        });
+
+        if let Some(checkpoints) = &self.checkpoints {
+            let checkpoint = Checkpoint {
+                pid: pid.to_owned(),
+                wid: wid.clone(),
+                workflow_model: workflow_model.to_string(),
+                node_filter: node_filter.clone(),
+                labels: labels.clone(),
+            };
+            if let Err(e) = checkpoints.save(&checkpoint) {
+                warn!("failed to checkpoint workflow process {}: {}", pid, e);
+            }
+        }
+
+        let (tx, rx) = mpsc::channel(100);
+        // the bounded replay buffer would drop events an unbounded stream keeps
+        let replay = replay && !self.unbounded_events;
+        // with a replay buffer the overflow waits there, however long the client takes
+        let tx = if replay {
+            StreamSender::direct(tx)
+        } else {
+            let send_timeout = Duration::from_millis(self.stream_send_timeout_ms.load(Ordering::SeqCst));
+            StreamSender::forwarded(
+                tx,
+                pid.to_owned(),
+                (!self.unbounded_events).then_some(send_timeout),
+                self.tracker.counters().events_dropped.clone(),
+            )
+        };
+        let options = RunOptions {
+            node_filter: node_filter.into_iter().collect(),
+            labels,
+            client_pid,
+            event_mask,
+            ordering: self.event_ordering,
+            replay_capacity: replay.then(|| self.replay_buffer_size.load(Ordering::SeqCst)),
+            close_on_pause: self.close_stream_on_pause,
+            permit: Some(permit),
+            events_dropped: self.tracker.counters().events_dropped.clone(),
+            log_level,
+            trace,
+        };
+        let closing = tx.closing();
+        let stalled = tx.stalled();
+        let proc = Arc::new(TrackedProcess::new(pid.to_owned(), wid, tx, total_nodes, options));
+        self.tracker.insert(proc.clone());
+
+        let supervisor = self.supervisor();
+        // the stream of a client that stopped reading only ends once the process lets go of it
+        {
+            let stalled_proc = proc.clone();
+            supervisor.spawn(&proc, async move {
+                tokio::select! {
+                    _ = stalled => stalled_proc.drop_stalled_stream(),
+                    _ = stalled_proc.finished() => {}
+                }
+            });
+        }
+        if proc.is_reordering() {
+            let flushed = proc.clone();
+            supervisor.spawn(&proc, async move {
+                let mut interval = tokio::time::interval(CAUSAL_REORDER_WINDOW / 2);
+                while !flushed.is_finished() {
+                    interval.tick().await;
+                    let watermark = chrono::Utc::now().timestamp_millis() - CAUSAL_REORDER_WINDOW.as_millis() as i64;
+                    flushed.flush(watermark);
+                }
+            });
+        }
+
+        if let Some(timeout) = idle_timeout.or(self.idle_timeout) {
+            let watched = proc.clone();
+            let watchdog = supervisor.clone();
+            supervisor.spawn(&proc, async move {
+                loop {
+                    // sleeps until the process would have been idle for the timeout
+                    tokio::select! {
+                        _ = tokio::time::sleep(timeout.saturating_sub(watched.idle_for())) => {}
+                        _ = watched.finished() => return,
+                    }
+                    if watched.is_finished() {
+                        return;
+                    }
+                    if watched.idle_for() >= timeout {
+                        break;
+                    }
+                }
+                warn!(
+                    "workflow process {} had no events for {} s, aborting it",
+                    watched.pid,
+                    timeout.as_secs()
+                );
+                watched.set_abort_reason(IDLE_TIMEOUT_REASON.to_owned());
+                if let Err(e) = watchdog.stop(&watched) {
+                    warn!("failed to stop idle workflow process {}: {}", watched.pid, e);
+                }
+            });
+        }
+
+        if let Some(deadline) = deadline {
+            let expiring = proc.clone();
+            let stopper = supervisor.clone();
+            supervisor.spawn(&proc, async move {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(deadline.into()) => {}
+                    _ = expiring.finished() => return,
+                }
+                if expiring.is_finished() {
+                    return;
+                }
+                warn!(
+                    "workflow process {} passed the deadline set by its client, aborting it",
+                    expiring.pid
+                );
+                expiring.set_abort_reason(DEADLINE_EXCEEDED_REASON.to_owned());
+                expiring.close_with_status(Status::deadline_exceeded(format!(
+                    "workflow process {} did not finish before the deadline",
+                    expiring.pid
+                )));
+                if let Err(e) = stopper.stop(&expiring) {
+                    warn!("failed to stop workflow process {}: {}", expiring.pid, e);
+                }
+            });
+        }
+
+        // a client deadline can only end the run earlier, the cap holds whatever the client asked for
+        if let Some(max_runtime) = self.max_workflow_runtime {
+            let capped = proc.clone();
+            let stopper = supervisor.clone();
+            supervisor.spawn(&proc, async move {
+                // ends with the run, instead of keeping the process alive for the whole cap
+                tokio::select! {
+                    _ = tokio::time::sleep(max_runtime) => {}
+                    _ = capped.finished() => return,
+                }
+                if capped.is_finished() {
+                    return;
+                }
+                warn!(
+                    "workflow process {} ran for {} s, the maximum the server allows, aborting it",
+                    capped.pid,
+                    max_runtime.as_secs()
+                );
+                capped.set_abort_reason(MAX_RUNTIME_REASON.to_owned());
+                if let Err(e) = stopper.stop(&capped) {
+                    warn!("failed to stop workflow process {}: {}", capped.pid, e);
+                }
+            });
+        }
+
+        let proc_event = proc.clone();
+        let event_supervisor = supervisor.clone();
+        let event_engine = engine.clone();
+        // kept here, the engine forgets the process as soon as it completed
+        let captured = capture_outputs.then(|| porc.clone());
+        let max_output_bytes = self.max_captured_output_bytes;
+        ChannelEvent::channel(engine.channel(), ChannelOptions::with_pid(pid.to_owned())).on_event(move |event| {
+            let supervisor = &event_supervisor;
+            let outputs = match (&event.event, &captured) {
+                (actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Succeeded), Some(process)) => {
+                    let outputs = serde_json::Value::from(process.get_outputs()).to_string();
+                    Some(truncate_with_marker(&outputs, max_output_bytes))
+                }
+                _ => None,
+            };
+            supervisor.catch(&proc_event, || {
+                handle_workflow_events(
+                    &supervisor.tracker,
+                    supervisor.checkpoints.as_deref(),
+                    &supervisor.sinks,
+                    &supervisor.instance_id,
+                    node_inputs.as_ref().map(|inputs| (inputs, &*event_engine)),
+                    outputs,
+                    &proc_event,
+                    event,
+                )
+            });
+        });
+
+        let proc_log = proc.clone();
+        let max_log_line_bytes = self.max_log_line_bytes;
+        ChannelEvent::channel(engine.channel(), ChannelOptions::with_pid(pid.to_owned())).on_log(move |log| {
+            supervisor.catch(&proc_log, || {
+                handle_workflow_logs(&supervisor.sinks, &proc_log, log, max_log_line_bytes)
+            });
+        });
+
+        porc.start();
+
+        Ok(EventStream::new(rx, proc.replay(), closing))
+    }
+
+    /// Ends the stream of every running workflow with a `ServerShuttingDown` event
+    ///
+    /// Waits at most [`SHUTDOWN_NOTICE_TIMEOUT`] for clients that are behind on their stream.
+    pub async fn announce_shutdown(
+        &self,
+        reason: &str,
+    ) {
+        let mut notices = JoinSet::new();
+        for proc in self.tracker.all() {
+            let event = workflow_event(
+                ProtoEvent::ServerShuttingDown(crate::proto::ServerShuttingDown {
+                    pid: proc.pid.clone(),
+                    reason: reason.to_owned(),
+                }),
+                chrono::Utc::now().timestamp_millis(),
+            );
+            notices.spawn(async move {
+                if !proc.cut_off(event, SHUTDOWN_NOTICE_TIMEOUT).await {
+                    warn!("failed to tell the client of workflow process {} about the shutdown", proc.pid);
+                }
+            });
+        }
+        let count = notices.len();
+        notices.join_all().await;
+        if count > 0 {
+            info!("told the clients of {} running workflows about the shutdown", count);
+        }
+    }
+
+    /// Runs the workflows checkpointed before the last shutdown again, or discards them
+    ///
+    /// Resumed workflows start over from their first node, their events are not streamed anywhere.
+    pub fn resume_checkpoints(
+        &self,
+        resume: bool,
+    ) {
+        let Some(checkpoints) = &self.checkpoints else {
+            return;
+        };
+        let interrupted = match checkpoints.list() {
+            Ok(interrupted) => interrupted,
+            Err(e) => {
+                warn!("failed to list workflow checkpoints: {}", e);
+                return;
+            }
+        };
+
+        for checkpoint in interrupted {
+            if resume {
+                info!("resuming workflow {} interrupted as process {}", checkpoint.wid, checkpoint.pid);
+                let node_filter = checkpoint.node_filter.clone();
+                let labels = checkpoint.labels.clone();
+                // resumed runs are not queued, but count towards the limit
+                let launch = LaunchRequest {
+                    workflow_model: &checkpoint.workflow_model,
+                    node_filter,
+                    labels,
+                    replay: false,
+                    peer: None,
+                    replay_of: None,
+                    permit: self.scheduler.acquire_now(),
+                    idle_timeout: None,
+                    deadline: None,
+                    retry_policy: None,
+                    client_pid: None,
+                    event_mask: HashSet::new(),
+                    log_level: None,
+                    capture_outputs: false,
+                    trace: RunTrace::start("resume_workflow", None),
+                };
+                match self.launch(launch) {
+                    // nobody listens to a resumed workflow, drain its events
+                    Ok(mut stream) => {
+                        tokio::spawn(async move { while stream.next().await.is_some() {} });
+                    }
+                    Err(e) => warn!("failed to resume workflow {}: {}", checkpoint.wid, e),
+                }
+            } else {
+                warn!(
+                    "discarding workflow {} interrupted as process {}, checkpoint resume is disabled",
+                    checkpoint.wid, checkpoint.pid
+                );
+            }
+            if let Err(e) = checkpoints.remove(&checkpoint.pid) {
+                warn!("failed to remove checkpoint of workflow process {}: {}", checkpoint.pid, e);
+            }
         }
     }
+
+    /// Stops a tracked process, see [`Supervisor::stop`]
+    fn stop_tracked(
+        &self,
+        proc: &Arc<TrackedProcess>,
+    ) -> Result<(), ActflowError> {
+        self.supervisor().stop(proc)
+    }
 }
 
 type RR<T> = Result<Response<T>, Status>;
 
 #[tonic::async_trait]
 impl WorkflowService for WorkflowServer {
-    type RunWorkflowStream = ReceiverStream<Result<WorkflowEvent, Status>>;
+    type RunWorkflowStream = EventStream;
 
     async fn run_workflow(
         &self,
         request: tonic::Request<RunWorkflowRequest>,
     ) -> RR<Self::RunWorkflowStream> {
+        if !self.accepting.load(Ordering::SeqCst) {
+            return Err(Status::unavailable("server is not accepting new workflow runs"));
+        }
+
+        let peer = describe_peer(&request);
+        let deadline = grpc_timeout(request.metadata()).map(|timeout| Instant::now() + timeout);
+        // started before the run is queued, so the span covers the time it waited
+        let trace = RunTrace::start("run_workflow", traceparent(request.metadata()));
         let request = request.into_inner();
 
-        let workflow_model: actflow::WorkflowModel = serde_json::from_str(&request.workflow_model)
-            .map_err(|e| Status::invalid_argument(format!("Invalid workflow model: {}", e)))?;
-        let wid = workflow_model.id.clone();
+        // checked before parsing so an oversized model is never deserialized
+        if request.workflow_model.len() > self.max_model_bytes {
+            return Err(Status::invalid_argument(format!(
+                "workflow model is {} bytes, exceeding the limit of {} bytes",
+                request.workflow_model.len(),
+                self.max_model_bytes
+            )));
+        }
 
-        info!("running workflow: {}", wid);
+        if let Some(policy) = &request.retry_policy {
+            validate_retry_policy(policy)?;
+        }
+        let event_mask = parse_event_mask(&request.event_mask)?;
+        let log_level = parse_run_log_level(&request.log_level)?;
 
-        let porc = self
-            .engine
-            .build_workflow_process(&workflow_model)
-            .map_err(|e| Status::internal(format!("Failed to build workflow process: {}", e)))?;
-        let pid = porc.id();
+        // waits here while the concurrency limit is reached
+        let acquire = self.scheduler.acquire(request.priority);
+        let permit = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline.into(), acquire)
+                .await
+                .map_err(|_| Status::deadline_exceeded("deadline exceeded while the run was queued"))??,
+            None => acquire.await?,
+        };
+        let trace_id = trace.trace_id();
+        let stream = self.launch(LaunchRequest {
+            workflow_model: &request.workflow_model,
+            node_filter: request.node_filter,
+            labels: request.labels,
+            replay: request.replay_buffer,
+            peer: Some(&peer),
+            replay_of: None,
+            permit,
+            idle_timeout: (request.idle_timeout_secs > 0).then(|| Duration::from_secs(request.idle_timeout_secs)),
+            deadline,
+            retry_policy: request.retry_policy,
+            client_pid: (!request.client_pid.is_empty()).then_some(request.client_pid),
+            event_mask,
+            log_level,
+            capture_outputs: request.capture_outputs,
+            trace,
+        })?;
 
-        let (tx, rx) = mpsc::channel(100);
-        let tx = Arc::new(Mutex::new(Some(tx)));
+        Ok(with_trace_id(Response::new(stream), trace_id))
+    }
 
-        let tx_event = tx.clone();
-        ChannelEvent::channel(self.engine.channel(), ChannelOptions::with_pid(pid.to_owned())).on_event(move |event| {
-            handle_workflow_events(&tx_event, &wid, &event);
-        });
+    type SubscribeWorkflowStream = EventStream;
 
-        let tx_log = tx.clone();
-        ChannelEvent::channel(self.engine.channel(), ChannelOptions::with_pid(pid.to_owned())).on_log(move |log| {
-            handle_workflow_logs(&tx_log, log);
-        });
+    async fn subscribe_workflow(
+        &self,
+        request: tonic::Request<SubscribeWorkflowRequest>,
+    ) -> RR<Self::SubscribeWorkflowStream> {
+        let peer = describe_peer(&request);
+        let request = request.into_inner();
+        let res = match self.tracker.get(&request.pid) {
+            Some(proc) => proc.subscribe(request.snapshot, self.max_subscribers_per_workflow),
+            None => Err(SubscribeError::Finished),
+        };
+        let rx = match res {
+            Ok(rx) => rx,
+            Err(SubscribeError::Finished) => {
+                return Err(Status::not_found(format!("workflow process {} is not running", request.pid)));
+            }
+            Err(SubscribeError::TooManySubscribers(count)) => {
+                return Err(Status::resource_exhausted(format!(
+                    "workflow process {} already has {} subscribers",
+                    request.pid, count
+                )));
+            }
+        };
+        info!("{} subscribed to workflow process {}", peer, request.pid);
 
-        porc.start();
+        Ok(Response::new(EventStream::new(rx, None, Default::default())))
+    }
+
+    type ReplayWorkflowStream = EventStream;
 
-        Ok(Response::new(ReceiverStream::new(rx)))
+    async fn replay_workflow(
+        &self,
+        request: tonic::Request<ReplayWorkflowRequest>,
+    ) -> RR<Self::ReplayWorkflowStream> {
+        if !self.accepting.load(Ordering::SeqCst) {
+            return Err(Status::unavailable("server is not accepting new workflow runs"));
+        }
+
+        let peer = describe_peer(&request);
+        let trace = RunTrace::start("replay_workflow", traceparent(request.metadata()));
+        let request = request.into_inner();
+        let Some(original) = self.history.get(&request.original_pid) else {
+            return Err(Status::not_found(format!(
+                "workflow process {} is not in the history",
+                request.original_pid
+            )));
+        };
+        let started_by = original.peer.as_deref().unwrap_or("the server");
+        match &original.replay_of {
+            Some(earlier) => info!(
+                "replaying workflow {} of process {} started by {} on {}, itself a replay of {}",
+                original.wid, original.pid, started_by, original.instance_id, earlier
+            ),
+            None => info!(
+                "replaying workflow {} of process {} started by {} on {}",
+                original.wid, original.pid, started_by, original.instance_id
+            ),
+        }
+
+        let permit = self.scheduler.acquire(request.priority).await?;
+        let trace_id = trace.trace_id();
+        let stream = self.launch(LaunchRequest {
+            workflow_model: &original.workflow_model,
+            node_filter: original.node_filter,
+            labels: original.labels,
+            replay: request.replay_buffer,
+            peer: Some(&peer),
+            replay_of: Some(&original.pid),
+            permit,
+            idle_timeout: None,
+            deadline: None,
+            retry_policy: None,
+            client_pid: None,
+            event_mask: HashSet::new(),
+            log_level: None,
+            capture_outputs: false,
+            trace,
+        })?;
+
+        Ok(with_trace_id(Response::new(stream), trace_id))
     }
 
     async fn stop_workflow(
         &self,
         request: tonic::Request<StopWorkflowRequest>,
     ) -> RR<StopWorkflowResponse> {
-        let pid = request.into_inner().pid;
-        match self.engine.stop(&pid) {
+        let peer = describe_peer(&request);
+        let request = request.into_inner();
+        if request.pid.is_empty() {
+            return self.stop_by_wid(&request.wid, &peer);
+        }
+        let pid = request.pid;
+        info!("stopping workflow process {} for {}", pid, peer);
+        let res = match self.tracker.get(&pid) {
+            Some(proc) => self.stop_tracked(&proc),
+            None if self.tracker.is_recently_finished(&pid) => {
+                Err(ActflowError::Process(format!("workflow process {} is not running", pid)))
+            }
+            None => {
+                let engine = self.engine.current();
+                match engine.get_process(&pid) {
+                    Some(_) => engine.stop(&pid),
+                    None => return Err(Status::not_found(format!("workflow process {} not found", pid))),
+                }
+            }
+        };
+        match res {
             Ok(()) => Ok(Response::new(StopWorkflowResponse {
                 success: true,
                 err_msg: "".to_string(),
+                stopped: 1,
             })),
             Err(err) => Ok(Response::new(StopWorkflowResponse {
                 success: false,
                 err_msg: err.to_string(),
+                stopped: 0,
             })),
         }
     }
+
+    async fn cancel_all(
+        &self,
+        request: tonic::Request<CancelAllRequest>,
+    ) -> RR<CancelAllResponse> {
+        require_client_certificate(&request, "cancelling every workflow")?;
+        let peer = describe_peer(&request);
+        let reason = request.into_inner().reason;
+        let procs = self.tracker.all();
+
+        info!("cancelling {} running workflows for {}, reason: {}", procs.len(), peer, reason);
+
+        let mut stopped = 0;
+        let mut failed = 0;
+        for proc in procs {
+            if !reason.is_empty() {
+                proc.set_abort_reason(reason.clone());
+            }
+            match self.stop_tracked(&proc) {
+                Ok(()) => stopped += 1,
+                Err(err) => {
+                    warn!("failed to stop workflow process {}: {}", proc.pid, err);
+                    failed += 1;
+                }
+            }
+        }
+
+        Ok(Response::new(CancelAllResponse {
+            stopped,
+            failed,
+        }))
+    }
+
+    async fn set_accepting(
+        &self,
+        request: tonic::Request<SetAcceptingRequest>,
+    ) -> RR<SetAcceptingResponse> {
+        let peer = describe_peer(&request);
+        let accepting = request.into_inner().accepting;
+        let was_accepting = self.accepting.swap(accepting, Ordering::SeqCst);
+        if accepting != was_accepting {
+            info!(
+                "{} new workflow runs for {}, {} workflows running",
+                if accepting {
+                    "accepting"
+                } else {
+                    "no longer accepting"
+                },
+                peer,
+                self.tracker.all().len()
+            );
+        }
+
+        Ok(Response::new(SetAcceptingResponse {
+            was_accepting,
+        }))
+    }
+
+    type DrainStream = DrainStream;
+
+    async fn drain(
+        &self,
+        request: tonic::Request<DrainRequest>,
+    ) -> RR<Self::DrainStream> {
+        let peer = describe_peer(&request);
+        let timeout_secs = request.into_inner().timeout_secs;
+        self.accepting.store(false, Ordering::SeqCst);
+        info!(
+            "draining for {}, no longer accepting new workflow runs, {} workflows running",
+            peer,
+            self.tracker.all().len()
+        );
+
+        let timeout = (timeout_secs > 0).then(|| Duration::from_secs(timeout_secs));
+        Ok(Response::new(drain::watch(
+            self.tracker.clone(),
+            self.scheduler.clone(),
+            timeout,
+            self.shutdown.clone(),
+        )))
+    }
+
+    /// Drains the server and swaps in a new engine
+    ///
+    /// New runs are refused until the reload ended, then accepted again if they were before. When
+    /// workflows are still running after the drain timeout the engine is kept.
+    async fn reload_engine(
+        &self,
+        request: tonic::Request<ReloadEngineRequest>,
+    ) -> RR<ReloadEngineResponse> {
+        let peer = describe_peer(&request);
+        let timeout_secs = request.into_inner().drain_timeout_secs;
+        let was_accepting = self.accepting.swap(false, Ordering::SeqCst);
+        info!(
+            "reloading the engine for {}, draining {} workflows first",
+            peer,
+            self.tracker.all().len()
+        );
+
+        let started = Instant::now();
+        let timeout = (timeout_secs > 0).then(|| Duration::from_secs(timeout_secs));
+        let remaining = drain::wait_idle(&self.tracker, &self.scheduler, timeout).await;
+        let drained = started.elapsed();
+        let res = if remaining > 0 {
+            Err(Status::failed_precondition(format!(
+                "{} workflows still running after {} s, the engine was not reloaded",
+                remaining, timeout_secs
+            )))
+        } else {
+            self.replace_engine().await.map_err(Status::from)
+        };
+        self.accepting.store(was_accepting, Ordering::SeqCst);
+        res?;
+
+        info!("engine reloaded for {} after draining for {} ms", peer, drained.as_millis());
+        Ok(Response::new(ReloadEngineResponse {
+            drained_ms: drained.as_millis() as u64,
+        }))
+    }
+
+    async fn list_workflows(
+        &self,
+        request: tonic::Request<ListWorkflowsRequest>,
+    ) -> RR<ListWorkflowsResponse> {
+        let selector = request.into_inner().label_selector;
+        let workflows = self
+            .tracker
+            .all()
+            .into_iter()
+            .filter(|proc| proc.matches_labels(&selector))
+            .map(|proc| WorkflowInfo {
+                pid: proc.pid.clone(),
+                wid: proc.wid.clone(),
+                labels: proc.labels.clone(),
+                elapsed_ms: proc.elapsed_ms(),
+                usage: Some(proc.usage()),
+                client_pid: proc.client_pid.clone().unwrap_or_default(),
+            })
+            .collect();
+
+        Ok(Response::new(ListWorkflowsResponse {
+            workflows,
+        }))
+    }
+
+    type TailServerLogStream = LogLineStream;
+
+    async fn tail_server_log(
+        &self,
+        request: tonic::Request<TailRequest>,
+    ) -> RR<Self::TailServerLogStream> {
+        // logs may contain sensitive details, only certificate-authenticated clients can read them
+        require_client_certificate(&request, "streaming the server log")?;
+        let Some(path) = &self.server_log_file else {
+            return Err(Status::failed_precondition("the server does not write a log file"));
+        };
+
+        let peer = describe_peer(&request);
+        let lines = request.into_inner().lines as usize;
+        info!("streaming the server log to {}", peer);
+        let stream = log_tail::tail(path.clone(), lines, self.shutdown.clone())
+            .await
+            .map_err(|e| Status::internal(format!("failed to open log file {}: {}", path.display(), e)))?;
+
+        Ok(Response::new(stream))
+    }
+
+    async fn get_version(
+        &self,
+        _request: tonic::Request<Empty>,
+    ) -> RR<VersionResponse> {
+        Ok(Response::new(VersionResponse {
+            name: VERSION_INFO.name.to_string(),
+            version: VERSION_INFO.version.to_string(),
+            branch: VERSION_INFO.branch.unwrap_or_default().to_string(),
+            commit_hash: VERSION_INFO.commit_hash.unwrap_or_default().to_string(),
+            compiler: VERSION_INFO.compiler.to_string(),
+            compile_time: VERSION_INFO.compile_time.to_string(),
+        }))
+    }
+
+    async fn get_server_stats(
+        &self,
+        _request: tonic::Request<Empty>,
+    ) -> RR<ServerStatsResponse> {
+        let procs = self.tracker.all();
+        let counters = self.tracker.counters();
+        Ok(Response::new(ServerStatsResponse {
+            active_workflows: procs.len() as u32,
+            started: counters.started.load(Ordering::SeqCst),
+            succeeded: counters.succeeded.load(Ordering::SeqCst),
+            failed: counters.failed.load(Ordering::SeqCst),
+            aborted: counters.aborted.load(Ordering::SeqCst),
+            queued_events: procs.iter().map(|proc| proc.queued_events() as u64).sum(),
+            uptime_ms: self.started_at.elapsed().as_millis() as u64,
+            queued_workflows: self.scheduler.queued() as u32,
+            worker_threads: Handle::current().metrics().num_workers() as u32,
+            events_dropped: counters.events_dropped.load(Ordering::SeqCst),
+        }))
+    }
+}
+
+/// Describes who sent a request for the logs, by certificate common name if known and peer address
+///
+/// Requests over transports without an IP address, such as Unix sockets, have no peer address.
+fn describe_peer<T>(request: &tonic::Request<T>) -> String {
+    let addr = request.remote_addr().map(|addr| addr.to_string()).unwrap_or_else(|| "unknown address".to_string());
+    match request.extensions().get::<ClientIdentity>() {
+        Some(client) => format!("{} ({})", client.common_name, addr),
+        None => addr,
+    }
+}
+
+/// Rejects a request of a client without a verified certificate, `action` names what it asked for
+///
+/// Guards the RPCs that affect every run or expose the server's internals.
+fn require_client_certificate<T>(
+    request: &tonic::Request<T>,
+    action: &str,
+) -> Result<(), Status> {
+    match request.extensions().get::<ClientIdentity>() {
+        Some(_) => Ok(()),
+        None => Err(Status::unauthenticated(format!("{} requires a client certificate", action))),
+    }
+}
+
+/// Returns the W3C trace context a client sent to continue its trace with the span of the run
+fn traceparent(metadata: &MetadataMap) -> Option<&str> {
+    metadata.get(TRACEPARENT_HEADER)?.to_str().ok()
+}
+
+/// Tells the client the trace id of the span of its run, if it has one
+fn with_trace_id<T>(
+    mut response: Response<T>,
+    trace_id: Option<String>,
+) -> Response<T> {
+    if let Some(value) = trace_id.and_then(|trace_id| MetadataValue::try_from(trace_id).ok()) {
+        response.metadata_mut().insert(TRACE_ID_HEADER, value);
+    }
+    response
 }
 
-type WorkflowEventTx = Arc<Mutex<Option<mpsc::Sender<Result<WorkflowEvent, Status>>>>>;
+/// Returns the timeout a client set with the `grpc-timeout` header, `None` without a valid one
+///
+/// tonic only applies the header until the response headers are sent, which for a stream is right away.
+fn grpc_timeout(metadata: &MetadataMap) -> Option<Duration> {
+    let value = metadata.get("grpc-timeout")?.to_str().ok()?;
+    // up to 8 digits followed by the unit
+    let (amount, unit) = value.split_at_checked(value.len().checked_sub(1)?)?;
+    if amount.is_empty() || amount.len() > 8 || !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = amount.parse().ok()?;
+    match unit {
+        "H" => Some(Duration::from_secs(amount * 60 * 60)),
+        "M" => Some(Duration::from_secs(amount * 60)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
 
+/// Streams an engine event of a process, `outputs` are the node outputs reported when it succeeded
+#[allow(clippy::too_many_arguments)]
 fn handle_workflow_events(
-    tx: &WorkflowEventTx,
-    workflow_id: &str,
+    tracker: &ProcessTracker,
+    checkpoints: Option<&dyn CheckpointStore>,
+    sinks: &[Arc<dyn EventSink>],
+    instance_id: &str,
+    node_inputs: Option<(&NodeInputs, &Engine)>,
+    outputs: Option<String>,
+    proc: &TrackedProcess,
     event: &actflow::Event<actflow::Message>,
 ) {
+    proc.touch();
+    proc.count_event();
+    let timestamp = proc.event_timestamp(event_timestamp(&event.event));
+    // reported to the client, unlike the ordering timestamp above it never borrows the previous event's time
+    let occurred_at = event_timestamp(&event.event).unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+
+    // Progress counts every node, including the ones hidden by the node filter
+    let progress = match &event.event {
+        actflow::GraphEvent::Node(
+            actflow::NodeEvent::Succeeded(_) | actflow::NodeEvent::Skipped | actflow::NodeEvent::Error(_),
+        ) => Some(workflow_event(
+            ProtoEvent::WorkflowProgress(crate::proto::WorkflowProgress {
+                pid: event.pid.clone(),
+                completed_nodes: proc.complete_node(),
+                total_nodes: proc.total_nodes(),
+            }),
+            occurred_at,
+        )),
+        _ => None,
+    };
+
+    if let (actflow::GraphEvent::Node(actflow::NodeEvent::Succeeded(_)), Some((inputs, _))) = (&event.event, node_inputs) {
+        inputs.node_succeeded(&event.nid);
+    }
+    let node_start = match (&event.event, node_inputs) {
+        (actflow::GraphEvent::Node(actflow::NodeEvent::Running(_)), Some((inputs, engine))) => Some(workflow_event(
+            ProtoEvent::NodeStart(inputs.snapshot(engine, &event.pid, &event.nid)),
+            occurred_at,
+        )),
+        _ => None,
+    };
+
+    // the engine does not say which node failed the workflow, it is the one that errored last
+    if let actflow::GraphEvent::Node(actflow::NodeEvent::Error(_)) = &event.event {
+        proc.set_errored_nid(&event.nid);
+    }
+
     // Check if the event is terminal
-    let is_terminal = matches!(
-        &event.event,
-        actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Succeeded)
-            | actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Failed(_))
-            | actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Aborted(_))
-    );
+    let outcome = match &event.event {
+        actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Succeeded) => Some(RunOutcome::Succeeded),
+        actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Failed(_)) => Some(RunOutcome::Failed),
+        actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Aborted(_)) => Some(RunOutcome::Aborted),
+        _ => None,
+    };
 
     let workflow_event = match &event.event {
         // Workflow events
-        actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Start(_)) => WorkflowEvent {
-            event: Some(ProtoEvent::WorkflowStart(crate::proto::WorkflowStart {
-                pid: event.pid.clone(),
-            })),
-        },
-        actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Succeeded) => WorkflowEvent {
-            event: Some(ProtoEvent::WorkflowSuccess(crate::proto::WorkflowSuccess {
+        actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Start(_)) => {
+            proc.mark_started();
+            workflow_event(
+                ProtoEvent::WorkflowStart(crate::proto::WorkflowStart {
+                    pid: event.pid.clone(),
+                    instance_id: instance_id.to_owned(),
+                }),
+                occurred_at,
+            )
+        }
+        actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Succeeded) => workflow_event(
+            ProtoEvent::WorkflowSuccess(crate::proto::WorkflowSuccess {
                 pid: event.pid.clone(),
-            })),
-        },
-        actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Failed(err)) => WorkflowEvent {
-            event: Some(ProtoEvent::WorkflowFailure(crate::proto::WorkflowFailure {
+                duration_ms: proc.elapsed_ms(),
+                usage: Some(proc.usage()),
+                outputs: outputs.unwrap_or_default(),
+            }),
+            occurred_at,
+        ),
+        actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Failed(err)) => workflow_event(
+            ProtoEvent::WorkflowFailure(crate::proto::WorkflowFailure {
                 pid: event.pid.clone(),
                 err_msg: err.error.clone(),
-            })),
-        },
-        actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Aborted(aborted)) => WorkflowEvent {
-            event: Some(ProtoEvent::WorkflowAbort(crate::proto::WorkflowAbort {
-                pid: event.pid.clone(),
-                reason: aborted.reason.clone(),
-            })),
+                duration_ms: proc.elapsed_ms(),
+                usage: Some(proc.usage()),
+                failed_nid: proc.errored_nid().unwrap_or_default(),
+            }),
+            occurred_at,
+        ),
+        actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Aborted(aborted)) => match proc.abort_reason() {
+            // aborts the server asked for are never engine errors
+            None if is_engine_error(&aborted.reason) => workflow_event(
+                ProtoEvent::WorkflowEngineError(crate::proto::WorkflowEngineError {
+                    pid: event.pid.clone(),
+                    detail: aborted.reason.clone(),
+                    duration_ms: proc.elapsed_ms(),
+                    usage: Some(proc.usage()),
+                }),
+                occurred_at,
+            ),
+            reason => workflow_event(
+                ProtoEvent::WorkflowAbort(crate::proto::WorkflowAbort {
+                    pid: event.pid.clone(),
+                    reason: reason.unwrap_or_else(|| aborted.reason.clone()),
+                    duration_ms: proc.elapsed_ms(),
+                    usage: Some(proc.usage()),
+                }),
+                occurred_at,
+            ),
         },
-        actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Paused(paused)) => WorkflowEvent {
-            event: Some(ProtoEvent::WorkflowPause(crate::proto::WorkflowPause {
+        actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Paused(paused)) => workflow_event(
+            ProtoEvent::WorkflowPause(crate::proto::WorkflowPause {
                 pid: event.pid.clone(),
                 reason: paused.reason.clone(),
-            })),
-        },
+            }),
+            occurred_at,
+        ),
         // Node events
-        actflow::GraphEvent::Node(actflow::NodeEvent::Running(_)) => WorkflowEvent {
-            event: Some(ProtoEvent::NodeRunning(crate::proto::NodeRunning {
+        actflow::GraphEvent::Node(actflow::NodeEvent::Running(_)) => workflow_event(
+            ProtoEvent::NodeRunning(crate::proto::NodeRunning {
                 pid: event.pid.clone(),
                 nid: event.nid.clone(),
-            })),
-        },
-        actflow::GraphEvent::Node(actflow::NodeEvent::Stopped(_)) => WorkflowEvent {
-            event: Some(ProtoEvent::NodeStopped(crate::proto::NodeStopped {
+            }),
+            occurred_at,
+        ),
+        actflow::GraphEvent::Node(actflow::NodeEvent::Stopped(_)) => workflow_event(
+            ProtoEvent::NodeStopped(crate::proto::NodeStopped {
                 pid: event.pid.clone(),
                 nid: event.nid.clone(),
-            })),
-        },
-        actflow::GraphEvent::Node(actflow::NodeEvent::Paused(_)) => WorkflowEvent {
-            event: Some(ProtoEvent::NodePaused(crate::proto::NodePaused {
+            }),
+            occurred_at,
+        ),
+        actflow::GraphEvent::Node(actflow::NodeEvent::Paused(_)) => workflow_event(
+            ProtoEvent::NodePaused(crate::proto::NodePaused {
                 pid: event.pid.clone(),
                 nid: event.nid.clone(),
                 reason: "Paused by user".to_string(),
-            })),
-        },
-        actflow::GraphEvent::Node(actflow::NodeEvent::Skipped) => WorkflowEvent {
-            event: Some(ProtoEvent::NodeSkipped(crate::proto::NodeSkipped {
+            }),
+            occurred_at,
+        ),
+        actflow::GraphEvent::Node(actflow::NodeEvent::Skipped) => workflow_event(
+            ProtoEvent::NodeSkipped(crate::proto::NodeSkipped {
                 pid: event.pid.clone(),
                 nid: event.nid.clone(),
-            })),
-        },
-        actflow::GraphEvent::Node(actflow::NodeEvent::Succeeded(_)) => WorkflowEvent {
-            event: Some(ProtoEvent::NodeSuccess(crate::proto::NodeSuccess {
+            }),
+            occurred_at,
+        ),
+        actflow::GraphEvent::Node(actflow::NodeEvent::Succeeded(_)) => workflow_event(
+            ProtoEvent::NodeSuccess(crate::proto::NodeSuccess {
                 pid: event.pid.clone(),
                 nid: event.nid.clone(),
-            })),
-        },
-        actflow::GraphEvent::Node(actflow::NodeEvent::Error(err)) => WorkflowEvent {
-            event: Some(ProtoEvent::NodeError(crate::proto::NodeError {
+            }),
+            occurred_at,
+        ),
+        actflow::GraphEvent::Node(actflow::NodeEvent::Error(err)) => workflow_event(
+            ProtoEvent::NodeError(crate::proto::NodeError {
                 pid: event.pid.clone(),
                 nid: event.nid.clone(),
                 err_msg: err.to_string(),
-            })),
-        },
-        actflow::GraphEvent::Node(actflow::NodeEvent::Retry) => WorkflowEvent {
-            event: Some(ProtoEvent::NodeRetry(crate::proto::NodeRetry {
+            }),
+            occurred_at,
+        ),
+        actflow::GraphEvent::Node(actflow::NodeEvent::Retry) => workflow_event(
+            ProtoEvent::NodeRetry(crate::proto::NodeRetry {
                 pid: event.pid.clone(),
                 nid: event.nid.clone(),
-            })),
-        },
+            }),
+            occurred_at,
+        ),
+        // keeps the stream going when a newer engine adds events
+        #[allow(unreachable_patterns)]
+        unknown => {
+            warn!("workflow process {} sent an unmapped engine event {:?}", event.pid, unknown);
+            workflow_event(
+                ProtoEvent::UnknownEvent(crate::proto::UnknownEvent {
+                    pid: event.pid.clone(),
+                    nid: event.nid.clone(),
+                    kind: format!("{:?}", unknown),
+                }),
+                occurred_at,
+            )
+        }
     };
 
-    if is_terminal {
-        if let Some(sender) = tx.lock().unwrap().take() {
-            if let Err(e) = sender.try_send(Ok(workflow_event)) {
-                error!("failed to send workflow event: {}", e);
-            }
-            info!("workflow [{}] execution completed", workflow_id);
+    proc.log_at(Level::Debug, || serde_json::to_string(&workflow_event).unwrap_or_default());
+    // the terminal event ends the span when it finishes the process
+    if let Some(node_start) = &node_start {
+        proc.trace().event(node_start);
+    }
+    if outcome.is_none() {
+        proc.trace().event(&workflow_event);
+    }
+
+    // sinks get every event, the node filter only applies to the client streams;
+    // the terminal event is forwarded once it won the race against a stop's fallback event
+    if let Some(node_start) = &node_start {
+        forward(sinks, node_start);
+    }
+    if outcome.is_none() {
+        forward(sinks, &workflow_event);
+    }
+    if let Some(progress) = &progress {
+        forward(sinks, progress);
+    }
+
+    // Workflow-level events are always streamed, node events only if the node passes the filter
+    if matches!(&event.event, actflow::GraphEvent::Node(_)) && !proc.accepts_node(&event.nid) {
+        if let Some(progress) = progress
+            && proc.accepts_event(&progress)
+        {
+            proc.send(progress, timestamp);
+        }
+        return;
+    }
+
+    let closes_stream =
+        proc.closes_on_pause() && matches!(&event.event, actflow::GraphEvent::Workflow(actflow::WorkflowEvent::Paused(_)));
+
+    if let Some(outcome) = outcome {
+        let terminal = workflow_event.clone();
+        if proc.finish(workflow_event) {
+            forward(sinks, &terminal);
+            info!("workflow [{}] execution completed", proc.wid);
+        }
+        tracker.remove(&proc.pid, outcome);
+        remove_checkpoint(checkpoints, &proc.pid);
+    } else if closes_stream {
+        // the process is still running, so it stays tracked and checkpointed
+        if proc.detach(workflow_event) {
+            info!("workflow [{}] paused, stream closed", proc.wid);
         }
     } else {
-        if let Some(sender) = tx.lock().unwrap().as_ref() {
-            if let Err(e) = sender.try_send(Ok(workflow_event)) {
-                error!("failed to send workflow event: {}", e);
+        // the event mask never holds back the events ending the stream, those are sent above
+        for event in node_start.into_iter().chain(Some(workflow_event)).chain(progress) {
+            if proc.accepts_event(&event) {
+                proc.send(event, timestamp);
+            }
+        }
+    }
+}
+
+/// Actions of the nodes of a workflow, for the `NodeStart` events of a process capturing node inputs
+struct NodeInputs {
+    /// JSON action of every node by node id
+    actions: HashMap<String, String>,
+    /// Nodes whose success event was handled
+    ///
+    /// The events are handled after the fact, when the engine may already have run later nodes,
+    /// so only the outputs of these nodes are reported.
+    succeeded: Mutex<HashSet<String>>,
+}
+
+impl NodeInputs {
+    fn new(model: &actflow::WorkflowModel) -> Self {
+        Self {
+            actions: model.nodes.iter().map(|node| (node.id.clone(), node.action.to_string())).collect(),
+            succeeded: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Records that node `nid` succeeded, its outputs are reported to the nodes running after it
+    fn node_succeeded(
+        &self,
+        nid: &str,
+    ) {
+        self.succeeded.lock().unwrap().insert(nid.to_owned());
+    }
+
+    /// Returns the inputs of node `nid` that is about to run
+    ///
+    /// The engine resolves the templates of an action inside the action and does not expose the
+    /// result, so the configured action is reported together with the outputs it can refer to.
+    fn snapshot(
+        &self,
+        engine: &Engine,
+        pid: &str,
+        nid: &str,
+    ) -> crate::proto::NodeStart {
+        let succeeded = self.succeeded.lock().unwrap();
+        let outputs: serde_json::Map<_, _> = engine
+            .get_process(&pid.to_owned())
+            .map(|process| process.get_outputs())
+            .into_iter()
+            .flat_map(|outputs| match serde_json::Value::from(outputs) {
+                serde_json::Value::Object(outputs) => outputs,
+                _ => serde_json::Map::new(),
+            })
+            .filter(|(nid, _)| succeeded.contains(nid))
+            .collect();
+        crate::proto::NodeStart {
+            pid: pid.to_owned(),
+            nid: nid.to_owned(),
+            action: self.actions.get(nid).cloned().unwrap_or_default(),
+            outputs: serde_json::Value::Object(outputs).to_string(),
+        }
+    }
+}
+
+/// Parses a JSON workflow model, rejecting models nested deeper than [`MAX_MODEL_DEPTH`]
+fn parse_model(json: &str) -> Result<actflow::WorkflowModel, ServerError> {
+    let depth = json_depth(json);
+    if depth > MAX_MODEL_DEPTH {
+        return Err(ServerError::InvalidModel(format!(
+            "model is nested {} levels deep, at most {} are allowed",
+            depth, MAX_MODEL_DEPTH
+        )));
+    }
+    // keeps the recursion limit of serde_json as a second line of defense
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let model = actflow::WorkflowModel::deserialize(&mut deserializer).map_err(|e| ServerError::InvalidModel(e.to_string()))?;
+    deserializer.end().map_err(|e| ServerError::InvalidModel(e.to_string()))?;
+    Ok(model)
+}
+
+/// Returns the deepest nesting of arrays and objects in `json`, without recursing
+///
+/// Brackets inside strings are skipped; malformed JSON is left for the parser to report.
+fn json_depth(json: &str) -> usize {
+    let (mut depth, mut max) = (0usize, 0);
+    let (mut in_string, mut escaped) = (false, false);
+    for byte in json.bytes() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                max = max.max(depth);
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    max
+}
+
+/// Wraps an event that happened at `timestamp`, its sequence number is set once it is sent
+fn workflow_event(
+    event: ProtoEvent,
+    timestamp: i64,
+) -> WorkflowEvent {
+    WorkflowEvent {
+        event: Some(event),
+        timestamp,
+        seq: 0,
+    }
+}
+
+/// Hands an event to every sink
+fn forward(
+    sinks: &[Arc<dyn EventSink>],
+    event: &WorkflowEvent,
+) {
+    for sink in sinks {
+        sink.send(event);
+    }
+}
+
+/// Checks whether an engine abort reason points at a failure inside the engine
+fn is_engine_error(reason: &str) -> bool {
+    let reason = reason.to_lowercase();
+    ENGINE_ERROR_REASONS.iter().any(|marker| reason.contains(marker))
+}
+
+/// Returns when an event happened in milliseconds since the epoch, the engine only timestamps some node events
+fn event_timestamp(event: &actflow::GraphEvent) -> Option<i64> {
+    match event {
+        actflow::GraphEvent::Node(
+            actflow::NodeEvent::Running(t)
+            | actflow::NodeEvent::Stopped(t)
+            | actflow::NodeEvent::Paused(t)
+            | actflow::NodeEvent::Succeeded(t),
+        ) => Some(*t),
+        _ => None,
+    }
+}
+
+/// What a workflow run is started with
+struct LaunchRequest<'a> {
+    workflow_model: &'a str,
+    /// Nodes whose events and logs are streamed, empty means all nodes
+    node_filter: Vec<String>,
+    labels: HashMap<String, String>,
+    /// Buffer the events that do not fit into the stream until the client reads them
+    replay: bool,
+    /// Client that asked for the run, `None` for runs the server starts itself
+    peer: Option<&'a str>,
+    /// Pid of the run this one replays
+    replay_of: Option<&'a str>,
+    /// Slot of the run in the scheduler
+    permit: Permit,
+    /// Idle timeout of the run, `None` uses the server setting
+    idle_timeout: Option<Duration>,
+    /// When the client stops waiting for the run, set with the `grpc-timeout` header
+    deadline: Option<Instant>,
+    /// Retry settings replacing those of every node, `None` keeps the model's
+    retry_policy: Option<RetryPolicy>,
+    /// Id the client chose for the run, also accepted where a pid is
+    client_pid: Option<String>,
+    /// Categories of the events streamed, empty means all
+    event_mask: HashSet<EventCategory>,
+    /// Most verbose level the server logs this run at regardless of its configured level
+    log_level: Option<Level>,
+    /// Report the outputs of every node when the run succeeds
+    capture_outputs: bool,
+    /// Span of the run, started by the call asking for it
+    trace: RunTrace,
+}
+
+/// Reads the event mask of a request, rejecting values that name no category
+fn parse_event_mask(mask: &[i32]) -> Result<HashSet<EventCategory>, Status> {
+    mask.iter()
+        .map(|&value| match EventCategory::try_from(value) {
+            Ok(EventCategory::Unspecified) | Err(_) => Err(Status::invalid_argument(format!(
+                "event mask holds {}, which is no event category",
+                value
+            ))),
+            Ok(category) => Ok(category),
+        })
+        .collect()
+}
+
+/// Reads the log level a run asked for, only the levels below the server's usual ones are accepted
+fn parse_run_log_level(level: &str) -> Result<Option<Level>, Status> {
+    match level.to_ascii_lowercase().as_str() {
+        "" => Ok(None),
+        "debug" => Ok(Some(Level::Debug)),
+        "trace" => Ok(Some(Level::Trace)),
+        _ => Err(Status::invalid_argument(format!(
+            "log level {} is not supported for a run, use debug or trace",
+            level
+        ))),
+    }
+}
+
+/// Rejects retry policies that would keep a failing run going for too long
+fn validate_retry_policy(policy: &RetryPolicy) -> Result<(), Status> {
+    if policy.max_retries > MAX_RETRIES {
+        return Err(Status::invalid_argument(format!(
+            "retry policy asks for {} retries, exceeding the limit of {}",
+            policy.max_retries, MAX_RETRIES
+        )));
+    }
+    if policy.interval_ms > MAX_RETRY_INTERVAL_MS {
+        return Err(Status::invalid_argument(format!(
+            "retry policy interval is {} ms, exceeding the limit of {} ms",
+            policy.interval_ms, MAX_RETRY_INTERVAL_MS
+        )));
+    }
+    Ok(())
+}
+
+/// Replaces the retry settings of every node, including those the model sets itself
+fn apply_retry_policy(
+    model: &mut actflow::WorkflowModel,
+    policy: &RetryPolicy,
+) {
+    for node in &mut model.nodes {
+        node.retry = Some(serde_json::json!({
+            "times": policy.max_retries,
+            "interval": policy.interval_ms,
+        }));
+    }
+}
+
+/// Ends a workflow whose handling in the server panicked, so its stream does not stall
+#[derive(Clone)]
+struct Supervisor {
+    engine: Arc<Engine>,
+    tracker: Arc<ProcessTracker>,
+    checkpoints: Option<Arc<dyn CheckpointStore>>,
+    sinks: Arc<[Arc<dyn EventSink>]>,
+    instance_id: Arc<str>,
+}
+
+impl Supervisor {
+    /// Spawns work for `proc`, failing the workflow if the task panics
+    fn spawn(
+        &self,
+        proc: &Arc<TrackedProcess>,
+        task: impl Future<Output = ()> + Send + 'static,
+    ) {
+        let handle = tokio::spawn(task);
+        let supervisor = self.clone();
+        let proc = proc.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle.await
+                && e.is_panic()
+            {
+                supervisor.fail(&proc);
+            }
+        });
+    }
+
+    /// Runs an engine callback for `proc`, failing the workflow if it panics
+    fn catch(
+        &self,
+        proc: &TrackedProcess,
+        callback: impl FnOnce(),
+    ) {
+        if panic::catch_unwind(AssertUnwindSafe(callback)).is_err() {
+            self.fail(proc);
+        }
+    }
+
+    /// Stops a tracked process, guaranteeing its stream gets exactly one terminal event
+    ///
+    /// The engine normally confirms the stop with an aborted event. If the process reached another
+    /// terminal state first, that event is the one reported. If the engine reports nothing within
+    /// [`STOP_GRACE_PERIOD`], a `WorkflowAbort` is sent and the stream is closed by the server.
+    fn stop(
+        &self,
+        proc: &Arc<TrackedProcess>,
+    ) -> Result<(), ActflowError> {
+        self.engine.stop(&proc.pid)?;
+
+        let tracker = self.tracker.clone();
+        let checkpoints = self.checkpoints.clone();
+        let sinks = self.sinks.clone();
+        let stopped = proc.clone();
+        self.spawn(proc, async move {
+            tokio::time::sleep(STOP_GRACE_PERIOD).await;
+            if stopped.is_finished() {
+                return;
+            }
+            warn!("workflow process {} did not confirm the stop, closing its stream", stopped.pid);
+            let reason = stopped.abort_reason().unwrap_or_else(|| STOP_FALLBACK_REASON.to_string());
+            let event = workflow_event(
+                ProtoEvent::WorkflowAbort(crate::proto::WorkflowAbort {
+                    pid: stopped.pid.clone(),
+                    reason,
+                    duration_ms: stopped.elapsed_ms(),
+                    usage: Some(stopped.usage()),
+                }),
+                chrono::Utc::now().timestamp_millis(),
+            );
+            // the engine's own terminal event may have come in meanwhile, only the first one is sent
+            if stopped.finish(event.clone()) {
+                forward(&sinks, &event);
             }
+            tracker.remove(&stopped.pid, RunOutcome::Aborted);
+            remove_checkpoint(checkpoints.as_deref(), &stopped.pid);
+        });
+
+        Ok(())
+    }
+
+    /// Stops the process and ends its stream with a `WorkflowFailure`
+    fn fail(
+        &self,
+        proc: &TrackedProcess,
+    ) {
+        error!("handling of workflow process {} panicked, failing it", proc.pid);
+        if let Err(e) = self.engine.stop(&proc.pid) {
+            warn!("failed to stop workflow process {}: {}", proc.pid, e);
+        }
+        let event = workflow_event(
+            ProtoEvent::WorkflowFailure(crate::proto::WorkflowFailure {
+                pid: proc.pid.clone(),
+                err_msg: PANIC_ERR_MSG.to_owned(),
+                duration_ms: proc.elapsed_ms(),
+                usage: Some(proc.usage()),
+                failed_nid: String::new(),
+            }),
+            chrono::Utc::now().timestamp_millis(),
+        );
+        if proc.finish(event.clone()) {
+            forward(&self.sinks, &event);
         }
+        self.tracker.remove(&proc.pid, RunOutcome::Failed);
+        remove_checkpoint(self.checkpoints.as_deref(), &proc.pid);
+    }
+}
+
+/// Drops the checkpoint of a finished process
+fn remove_checkpoint(
+    checkpoints: Option<&dyn CheckpointStore>,
+    pid: &str,
+) {
+    if let Some(checkpoints) = checkpoints
+        && let Err(e) = checkpoints.remove(pid)
+    {
+        warn!("failed to remove checkpoint of workflow process {}: {}", pid, e);
     }
 }
 
 fn handle_workflow_logs(
-    tx: &WorkflowEventTx,
+    sinks: &[Arc<dyn EventSink>],
+    proc: &TrackedProcess,
     log: &actflow::Log,
+    max_line_bytes: Option<usize>,
 ) {
-    let log_event = WorkflowEvent {
-        event: Some(ProtoEvent::NodeLog(crate::proto::NodeLog {
+    proc.touch();
+    proc.count_log_line();
+    proc.log_at(Level::Trace, || format!("node {} logged: {}", log.nid, log.content));
+    let streamed = proc.accepts_node(&log.nid) && proc.accepts_category(EventCategory::NodeLog);
+    if sinks.is_empty() && !streamed {
+        return;
+    }
+
+    // parsed before the cut, which would leave a long JSON line unparsable
+    let fields = log_fields(&log.content);
+    let content = match max_line_bytes {
+        Some(max) => truncate_with_marker(&log.content, max),
+        None => log.content.clone(),
+    };
+    let log_event = workflow_event(
+        ProtoEvent::NodeLog(crate::proto::NodeLog {
             pid: log.pid.clone(),
             nid: log.nid.clone(),
-            content: log.content.clone(),
+            fields,
+            content,
             timestamp: log.timestamp,
-        })),
-    };
-    if let Some(sender) = tx.lock().unwrap().as_ref() {
-        if let Err(e) = sender.try_send(Ok(log_event)) {
-            error!("failed to send workflow log event: {}", e);
-        }
+        }),
+        log.timestamp,
+    );
+    forward(sinks, &log_event);
+    if streamed {
+        proc.send(log_event, log.timestamp);
+    }
+}
+
+/// Cuts a log line or other text to at most `max` bytes of content followed by a marker with the number of bytes cut
+///
+/// The cut is moved back to a character boundary, so slightly less than `max` bytes may be kept.
+fn truncate_with_marker(
+    content: &str,
+    max: usize,
+) -> String {
+    if content.len() <= max {
+        return content.to_owned();
+    }
+    let mut end = max;
+    while !content.is_char_boundary(end) {
+        end -= 1;
     }
+    format!("{}…(truncated {} bytes)", &content[..end], content.len() - end)
+}
+
+/// Extracts the top-level keys of a JSON object log line, non-string values are kept as JSON
+fn log_fields(content: &str) -> HashMap<String, String> {
+    let Ok(serde_json::Value::Object(object)) = serde_json::from_str(content) else {
+        return HashMap::new();
+    };
+    object
+        .into_iter()
+        .map(|(key, value)| match value {
+            serde_json::Value::String(s) => (key, s),
+            value => (key, value.to_string()),
+        })
+        .collect()
 }
@@ -0,0 +1,72 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, LineWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use log::warn;
+
+use crate::proto::WorkflowEvent;
+
+/// Destination the events of every workflow process are forwarded to, whether or not a client streams them
+///
+/// Sinks get every event and log line of a process, including the ones of nodes hidden by the
+/// node filter of its client. The sequence number is left unset, it numbers a client stream.
+pub trait EventSink: Send + Sync {
+    /// Takes an event, called from the engine callbacks so it should not block for long
+    fn send(
+        &self,
+        event: &WorkflowEvent,
+    );
+}
+
+/// Sink dropping every event
+pub struct NoopSink;
+
+impl EventSink for NoopSink {
+    fn send(
+        &self,
+        _event: &WorkflowEvent,
+    ) {
+    }
+}
+
+/// Sink writing every event as a line of JSON
+pub struct JsonLinesSink {
+    /// Where the sink writes to, used in logs
+    name: String,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonLinesSink {
+    /// Writes to the standard output
+    pub fn stdout() -> Self {
+        Self {
+            name: "stdout".to_owned(),
+            out: Mutex::new(Box::new(io::stdout())),
+        }
+    }
+
+    /// Appends to the file at `path`, creating it if needed
+    pub fn open_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path.as_ref())?;
+        Ok(Self {
+            name: path.as_ref().display().to_string(),
+            out: Mutex::new(Box::new(LineWriter::new(file))),
+        })
+    }
+}
+
+impl EventSink for JsonLinesSink {
+    fn send(
+        &self,
+        event: &WorkflowEvent,
+    ) {
+        let res =
+            serde_json::to_string(event).map_err(io::Error::from).and_then(|line| writeln!(self.out.lock().unwrap(), "{}", line));
+        if let Err(e) = res {
+            warn!("failed to write workflow event to sink {}: {}", self.name, e);
+        }
+    }
+}
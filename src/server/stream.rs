@@ -0,0 +1,281 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use log::error;
+use tokio::{
+    sync::{
+        mpsc::{self, error::TrySendError},
+        watch,
+    },
+    task::JoinHandle,
+};
+use tokio_stream::Stream;
+use tonic::Status;
+
+use super::tracker::WorkflowEventTx;
+use crate::proto::WorkflowEvent;
+
+type Item = Result<WorkflowEvent, Status>;
+
+/// Status a stream ends with once the events in its channel are read, set when its client is cut off
+pub type ClosingStatus = Arc<Mutex<Option<Status>>>;
+
+/// Sending half of a `run_workflow` stream
+///
+/// Without a replay buffer, events are queued and a dedicated task moves them into the channel,
+/// waiting up to the send timeout for room for each. A client that is only briefly behind gets
+/// every event, one that reads nothing for the whole timeout gets the events already in the
+/// channel and then a `DATA_LOSS` status, once the stream is dropped as [`StreamSender::stalled`]
+/// asks. Without a send timeout the task waits as long as the client stays connected and the
+/// queue is unbounded.
+pub struct StreamSender {
+    tx: WorkflowEventTx,
+    /// Queue of the forwarding task, `None` when the events go straight into the channel
+    queue: Option<mpsc::UnboundedSender<Item>>,
+    /// Number of events in the queue
+    queued: Arc<AtomicUsize>,
+    /// Forwarding task, returns whether it moved every queued event into the channel
+    forwarder: Option<JoinHandle<bool>>,
+    closing: ClosingStatus,
+    /// Turns true when the forwarding task gave up on the client
+    stalled: watch::Receiver<bool>,
+}
+
+impl StreamSender {
+    /// Sends straight into `tx`, for streams whose overflow is kept by a [`ReplayBuffer`]
+    pub fn direct(tx: WorkflowEventTx) -> Self {
+        Self {
+            tx,
+            queue: None,
+            queued: Arc::new(AtomicUsize::new(0)),
+            forwarder: None,
+            closing: ClosingStatus::default(),
+            stalled: watch::channel(false).1,
+        }
+    }
+
+    /// Spawns the task forwarding the events of workflow process `pid` into `tx`
+    ///
+    /// The events lost when the stream is closed for a stuck client are added to `dropped`, `None`
+    /// never closes the stream however long the client reads nothing.
+    pub fn forwarded(
+        tx: WorkflowEventTx,
+        pid: String,
+        send_timeout: Option<Duration>,
+        dropped: Arc<AtomicU64>,
+    ) -> Self {
+        let (queue, mut rx) = mpsc::unbounded_channel();
+        let queued = Arc::new(AtomicUsize::new(0));
+        let closing = ClosingStatus::default();
+        let (stall, stalled) = watch::channel(false);
+        let forwarder = {
+            let tx = tx.clone();
+            let queued = queued.clone();
+            let closing = closing.clone();
+            tokio::spawn(async move {
+                while let Some(item) = rx.recv().await {
+                    queued.fetch_sub(1, Ordering::SeqCst);
+                    let reserved = match send_timeout {
+                        Some(send_timeout) => tokio::time::timeout(send_timeout, tx.reserve()).await,
+                        None => Ok(tx.reserve().await),
+                    };
+                    match reserved {
+                        Ok(Ok(permit)) => permit.send(item),
+                        // the client went away
+                        Ok(Err(_)) => return false,
+                        Err(_) => {
+                            let send_timeout = send_timeout.unwrap_or_default();
+                            error!(
+                                "client of workflow process {} read nothing for {:?}, closing its stream",
+                                pid, send_timeout
+                            );
+                            // this event and the ones still queued never reach the client
+                            dropped.fetch_add(1 + queued.load(Ordering::SeqCst) as u64, Ordering::SeqCst);
+                            *closing.lock().unwrap() = Some(Status::data_loss(format!(
+                                "client too slow, it read nothing for {:?} and the later events of workflow process {} were dropped",
+                                send_timeout, pid
+                            )));
+                            stall.send_replace(true);
+                            return false;
+                        }
+                    }
+                }
+                true
+            })
+        };
+        Self {
+            tx,
+            queue: Some(queue),
+            queued,
+            forwarder: Some(forwarder),
+            closing,
+            stalled,
+        }
+    }
+
+    /// Returns the status the stream ends with, to be handed to its [`EventStream`]
+    pub fn closing(&self) -> ClosingStatus {
+        self.closing.clone()
+    }
+
+    /// Resolves once the forwarding task gave up on a client that read nothing for the send timeout
+    ///
+    /// The stream only ends when every sender is gone, so the owner of this one has to drop it then.
+    /// Never resolves for a stream without a send timeout.
+    pub fn stalled(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut stalled = self.stalled.clone();
+        async move {
+            if stalled.wait_for(|stalled| *stalled).await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// Returns the channel of the stream
+    pub fn channel(&self) -> &WorkflowEventTx {
+        &self.tx
+    }
+
+    /// Sends an event or error, queueing it for the forwarding task if there is one
+    pub fn send(
+        &self,
+        item: Item,
+    ) -> Result<(), String> {
+        match &self.queue {
+            Some(queue) => {
+                self.queued.fetch_add(1, Ordering::SeqCst);
+                queue.send(item).map_err(|_| {
+                    self.queued.fetch_sub(1, Ordering::SeqCst);
+                    "stream closed".to_string()
+                })
+            }
+            None => self.tx.try_send(item).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Returns the number of events in the channel and the queue
+    pub fn pending(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity() + self.queued.load(Ordering::SeqCst)
+    }
+
+    /// Closes the queue and waits up to `timeout` for the forwarding task to move the queued events
+    ///
+    /// Returns whether every event made it into the channel.
+    pub async fn close(
+        mut self,
+        timeout: Duration,
+    ) -> bool {
+        self.queue = None;
+        match self.forwarder.take() {
+            Some(forwarder) => matches!(tokio::time::timeout(timeout, forwarder).await, Ok(Ok(true))),
+            None => true,
+        }
+    }
+}
+
+/// Events that did not fit into a stream's channel, replayed once the client catches up
+pub struct ReplayBuffer {
+    events: Mutex<VecDeque<Item>>,
+    /// Maximum number of buffered non-terminal events
+    capacity: usize,
+}
+
+impl ReplayBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: Mutex::new(VecDeque::new()),
+            capacity,
+        }
+    }
+
+    /// Sends an event or error through the channel, buffering it while the channel is full
+    ///
+    /// Once an event is buffered, later events are buffered behind it to keep their order. The
+    /// terminal event is always buffered so the stream is never left without one.
+    pub fn send(
+        &self,
+        tx: &WorkflowEventTx,
+        item: Item,
+        terminal: bool,
+    ) -> Result<(), String> {
+        let mut events = self.events.lock().unwrap();
+        let event = if events.is_empty() {
+            match tx.try_send(item) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Full(event)) => event,
+                Err(e @ TrySendError::Closed(_)) => return Err(e.to_string()),
+            }
+        } else if tx.is_closed() {
+            return Err("channel closed".to_string());
+        } else {
+            item
+        };
+
+        if events.len() >= self.capacity && !terminal {
+            return Err(format!("replay buffer is full ({} events)", self.capacity));
+        }
+        events.push_back(event);
+        Ok(())
+    }
+
+    /// Returns the number of buffered events
+    pub fn len(&self) -> usize {
+        self.events.lock().unwrap().len()
+    }
+
+    fn pop(&self) -> Option<Item> {
+        self.events.lock().unwrap().pop_front()
+    }
+}
+
+/// Event stream of a `run_workflow` call
+pub struct EventStream {
+    rx: mpsc::Receiver<Item>,
+    replay: Option<Arc<ReplayBuffer>>,
+    closing: ClosingStatus,
+}
+
+impl EventStream {
+    pub fn new(
+        rx: mpsc::Receiver<Item>,
+        replay: Option<Arc<ReplayBuffer>>,
+        closing: ClosingStatus,
+    ) -> Self {
+        Self {
+            rx,
+            replay,
+            closing,
+        }
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Item;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        // the channel always holds older events than the replay buffer
+        match this.rx.poll_recv(cx) {
+            Poll::Ready(Some(event)) => Poll::Ready(Some(event)),
+            Poll::Ready(None) => match this.replay.as_ref().and_then(|replay| replay.pop()) {
+                Some(event) => Poll::Ready(Some(event)),
+                None => Poll::Ready(this.closing.lock().unwrap().take().map(Err)),
+            },
+            Poll::Pending => match this.replay.as_ref().and_then(|replay| replay.pop()) {
+                Some(event) => Poll::Ready(Some(event)),
+                None => Poll::Pending,
+            },
+        }
+    }
+}
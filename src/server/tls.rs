@@ -0,0 +1,66 @@
+use std::fs;
+
+use tonic::{
+    Request, Status,
+    transport::{Certificate, Identity, ServerTlsConfig},
+};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use super::ServerError;
+use crate::config::TlsConfig;
+
+/// Client authenticated by its certificate, inserted into the request extensions
+#[derive(Clone, Debug)]
+pub struct ClientIdentity {
+    /// Common name of the client certificate subject
+    pub common_name: String,
+}
+
+/// Builds the TLS config shared by every listener
+pub fn load_tls_config(config: &TlsConfig) -> Result<ServerTlsConfig, ServerError> {
+    let cert = pem_from_file_or_inline(&config.cert_file, &config.cert_pem, "cert")?;
+    let key = pem_from_file_or_inline(&config.key_file, &config.key_pem, "key")?;
+
+    let identity = Identity::from_pem(cert, key);
+    let mut tls = ServerTlsConfig::new().identity(identity);
+    if let Some(client_ca_file) = &config.client_ca_file {
+        // clients without a certificate signed by this CA fail the handshake
+        tls = tls.client_ca_root(Certificate::from_pem(read_pem(client_ca_file)?));
+    }
+
+    Ok(tls)
+}
+
+/// Returns the PEM from `path` if set, otherwise the inline `pem`
+fn pem_from_file_or_inline(
+    path: &str,
+    pem: &str,
+    what: &str,
+) -> Result<Vec<u8>, ServerError> {
+    match (path.is_empty(), pem.is_empty()) {
+        (false, _) => read_pem(path),
+        (true, false) => Ok(pem.as_bytes().to_vec()),
+        (true, true) => Err(ServerError::Tls(format!("{}-file or {}-pem is required", what, what))),
+    }
+}
+
+fn read_pem(path: &str) -> Result<Vec<u8>, ServerError> {
+    fs::read(path).map_err(|e| ServerError::Tls(format!("{}: {}", path, e)))
+}
+
+/// Interceptor exposing the [`ClientIdentity`] of certificate-authenticated clients to handlers
+pub fn client_identity(mut req: Request<()>) -> Result<Request<()>, Status> {
+    let common_name = req.peer_certs().and_then(|certs| certs.first().and_then(|cert| common_name(cert)));
+    if let Some(common_name) = common_name {
+        req.extensions_mut().insert(ClientIdentity {
+            common_name,
+        });
+    }
+    Ok(req)
+}
+
+/// Returns the subject common name of a DER-encoded certificate
+pub fn common_name(der: &[u8]) -> Option<String> {
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+    cert.subject().iter_common_name().next()?.as_str().ok().map(str::to_owned)
+}
@@ -0,0 +1,223 @@
+#[cfg(not(feature = "otel"))]
+use crate::proto::WorkflowEvent;
+
+/// Response header carrying the trace id of the span of a run, as 32 lowercase hex digits
+pub const TRACE_ID_HEADER: &str = "trace-id";
+
+/// Request header of a W3C trace context the span of a run continues
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// The OpenTelemetry span of a run, from the call starting it until its terminal event
+///
+/// Spans are only recorded in builds with the `otel` feature once an [`OtelExporter`] is
+/// installed, otherwise every method does nothing and the run has no trace id.
+#[derive(Default)]
+pub struct RunTrace {
+    #[cfg(feature = "otel")]
+    span: std::sync::Mutex<Option<opentelemetry::global::BoxedSpan>>,
+}
+
+// without the `otel` feature no run has a span
+#[cfg(not(feature = "otel"))]
+impl RunTrace {
+    pub fn start(
+        _name: &'static str,
+        _traceparent: Option<&str>,
+    ) -> Self {
+        Self::default()
+    }
+
+    pub fn trace_id(&self) -> Option<String> {
+        None
+    }
+
+    pub fn set_process(
+        &self,
+        _pid: &str,
+        _wid: &str,
+    ) {
+    }
+
+    pub fn event(
+        &self,
+        _event: &WorkflowEvent,
+    ) {
+    }
+
+    pub fn end(
+        &self,
+        _event: &WorkflowEvent,
+    ) {
+    }
+}
+
+#[cfg(feature = "otel")]
+pub use otel::OtelExporter;
+
+#[cfg(feature = "otel")]
+mod otel {
+    use std::collections::HashMap;
+
+    use log::warn;
+    use opentelemetry::{
+        Context, KeyValue, global,
+        propagation::TextMapPropagator,
+        trace::{Span, Status as SpanStatus, Tracer},
+    };
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider};
+
+    use super::{RunTrace, TRACEPARENT_HEADER};
+    use crate::{
+        config::OtelConfig,
+        proto::{WorkflowEvent, workflow_event::Event as ProtoEvent},
+        server::ServerError,
+    };
+
+    /// Name of the tracer the spans of the runs are recorded with
+    const TRACER_NAME: &str = "actflow-server";
+
+    /// Exports the spans of the runs to the OTLP collector of an [`OtelConfig`]
+    ///
+    /// Installed as the global tracer provider, so it has to be created on a tokio runtime and
+    /// shut down before the runtime stops to export the last spans.
+    pub struct OtelExporter {
+        provider: SdkTracerProvider,
+    }
+
+    impl OtelExporter {
+        pub fn install(config: &OtelConfig) -> Result<Self, ServerError> {
+            let exporter = SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint(&config.endpoint)
+                .build()
+                .map_err(|e| ServerError::Internal(format!("failed to build the OTLP exporter: {}", e)))?;
+            let provider = SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(Resource::builder().with_service_name(config.service_name.clone()).build())
+                .build();
+            global::set_tracer_provider(provider.clone());
+            Ok(Self {
+                provider,
+            })
+        }
+
+        /// Exports the spans not exported yet and stops exporting
+        pub fn shutdown(self) {
+            if let Err(e) = self.provider.shutdown() {
+                warn!("failed to export the last spans: {}", e);
+            }
+        }
+    }
+
+    impl RunTrace {
+        /// Starts the span of a run, continuing the trace of `traceparent` if it is a valid W3C trace context
+        pub fn start(
+            name: &'static str,
+            traceparent: Option<&str>,
+        ) -> Self {
+            let parent = match traceparent {
+                Some(traceparent) => {
+                    let carrier = HashMap::from([(TRACEPARENT_HEADER.to_owned(), traceparent.to_owned())]);
+                    TraceContextPropagator::new().extract(&carrier)
+                }
+                None => Context::new(),
+            };
+            let span = global::tracer(TRACER_NAME).start_with_context(name, &parent);
+            Self {
+                span: std::sync::Mutex::new(Some(span)),
+            }
+        }
+
+        /// Returns the trace id of the span, `None` when no exporter records it
+        pub fn trace_id(&self) -> Option<String> {
+            let span = self.span.lock().unwrap();
+            let context = span.as_ref()?.span_context();
+            context.is_valid().then(|| context.trace_id().to_string())
+        }
+
+        /// Attaches the process the run got to the span, once the engine assigned it
+        pub fn set_process(
+            &self,
+            pid: &str,
+            wid: &str,
+        ) {
+            if let Some(span) = self.span.lock().unwrap().as_mut() {
+                span.set_attribute(KeyValue::new("actflow.pid", pid.to_owned()));
+                span.set_attribute(KeyValue::new("actflow.wid", wid.to_owned()));
+            }
+        }
+
+        /// Adds an event of the run to the span
+        pub fn event(
+            &self,
+            event: &WorkflowEvent,
+        ) {
+            if let Some(span) = self.span.lock().unwrap().as_mut() {
+                add_event(span, event);
+            }
+        }
+
+        /// Adds the terminal event of the run to the span and ends it, later calls do nothing
+        pub fn end(
+            &self,
+            event: &WorkflowEvent,
+        ) {
+            let Some(mut span) = self.span.lock().unwrap().take() else {
+                return;
+            };
+            add_event(&mut span, event);
+            match &event.event {
+                Some(ProtoEvent::WorkflowSuccess(_)) => span.set_status(SpanStatus::Ok),
+                Some(ProtoEvent::WorkflowFailure(e)) => span.set_status(SpanStatus::error(e.err_msg.clone())),
+                Some(ProtoEvent::WorkflowEngineError(e)) => span.set_status(SpanStatus::error(e.detail.clone())),
+                _ => {}
+            }
+            span.end();
+        }
+    }
+
+    fn add_event(
+        span: &mut impl Span,
+        event: &WorkflowEvent,
+    ) {
+        let Some(proto_event) = &event.event else {
+            return;
+        };
+        let (name, nid) = describe(proto_event);
+        let mut attributes = Vec::new();
+        if let Some(nid) = nid {
+            attributes.push(KeyValue::new("actflow.nid", nid.to_owned()));
+        }
+        match proto_event {
+            ProtoEvent::NodeError(e) => attributes.push(KeyValue::new("error.message", e.err_msg.clone())),
+            ProtoEvent::WorkflowAbort(e) => attributes.push(KeyValue::new("actflow.abort_reason", e.reason.clone())),
+            _ => {}
+        }
+        span.add_event(name, attributes);
+    }
+
+    /// Returns the name of the span event for an event, with the node it is about
+    fn describe(event: &ProtoEvent) -> (&'static str, Option<&str>) {
+        match event {
+            ProtoEvent::WorkflowStart(_) => ("workflow_start", None),
+            ProtoEvent::WorkflowSuccess(_) => ("workflow_success", None),
+            ProtoEvent::WorkflowFailure(_) => ("workflow_failure", None),
+            ProtoEvent::WorkflowAbort(_) => ("workflow_abort", None),
+            ProtoEvent::WorkflowEngineError(_) => ("workflow_engine_error", None),
+            ProtoEvent::WorkflowPause(_) => ("workflow_pause", None),
+            ProtoEvent::WorkflowProgress(_) => ("workflow_progress", None),
+            ProtoEvent::ServerShuttingDown(_) => ("server_shutting_down", None),
+            ProtoEvent::UnknownEvent(e) => ("unknown_event", (!e.nid.is_empty()).then_some(e.nid.as_str())),
+            ProtoEvent::NodeStart(e) => ("node_start", Some(&e.nid)),
+            ProtoEvent::NodeRunning(e) => ("node_running", Some(&e.nid)),
+            ProtoEvent::NodeStopped(e) => ("node_stopped", Some(&e.nid)),
+            ProtoEvent::NodePaused(e) => ("node_paused", Some(&e.nid)),
+            ProtoEvent::NodeSkipped(e) => ("node_skipped", Some(&e.nid)),
+            ProtoEvent::NodeSuccess(e) => ("node_success", Some(&e.nid)),
+            ProtoEvent::NodeError(e) => ("node_error", Some(&e.nid)),
+            ProtoEvent::NodeRetry(e) => ("node_retry", Some(&e.nid)),
+            ProtoEvent::NodeLog(e) => ("node_log", Some(&e.nid)),
+        }
+    }
+}
@@ -0,0 +1,869 @@
+use std::{
+    cmp::{Ordering as CmpOrdering, Reverse},
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicI64, AtomicU32, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use log::{Level, error, warn};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    watch,
+};
+use tonic::Status;
+
+use super::{
+    scheduler::Permit,
+    stream::{ReplayBuffer, StreamSender},
+    trace::RunTrace,
+};
+use crate::{
+    config::EventOrdering,
+    logger::RUN_LOG_TARGET,
+    proto::{EventCategory, ResourceUsage, WorkflowEvent, workflow_event::Event as ProtoEvent},
+};
+
+/// How long events are held back in causal ordering mode for earlier ones to arrive
+pub const CAUSAL_REORDER_WINDOW: Duration = Duration::from_millis(50);
+
+/// Fill level of an event stream, in percent of its capacity, that logs a warning
+const STREAM_WARN_PERCENT: usize = 80;
+
+/// Number of live events a subscriber stream holds, on top of its snapshot
+const SUBSCRIBER_STREAM_SIZE: usize = 100;
+
+/// Sender half of a `run_workflow` event stream
+pub type WorkflowEventTx = mpsc::Sender<Result<WorkflowEvent, Status>>;
+
+/// Why a process cannot be subscribed to
+pub enum SubscribeError {
+    /// The terminal event was already sent
+    Finished,
+    /// The process has as many subscribers as allowed
+    TooManySubscribers(usize),
+}
+
+/// Settings of a single run
+#[derive(Default)]
+pub struct RunOptions {
+    /// Nodes whose events and logs are streamed, empty means all nodes
+    pub node_filter: HashSet<String>,
+    /// Labels the client attached to the run
+    pub labels: HashMap<String, String>,
+    /// Id the client chose for the run, looked up like the pid
+    pub client_pid: Option<String>,
+    /// Categories of the events streamed, empty means all
+    pub event_mask: HashSet<EventCategory>,
+    /// How node events and logs are ordered on the stream
+    pub ordering: EventOrdering,
+    /// Capacity of the buffer for the events that do not fit into the stream, `None` drops them instead
+    pub replay_capacity: Option<usize>,
+    /// Close the stream with the `WorkflowPause` event
+    pub close_on_pause: bool,
+    /// Slot of the run in the scheduler, released when the run stops being tracked
+    pub permit: Option<Permit>,
+    /// Counter of the events the streams of the run drop, see [`RunCounters::events_dropped`]
+    pub events_dropped: Arc<AtomicU64>,
+    /// Most verbose level written to the server log for this run on [`RUN_LOG_TARGET`], `None` writes nothing
+    pub log_level: Option<Level>,
+    /// Span of the run, ended with its terminal event
+    pub trace: RunTrace,
+}
+
+/// Returns the category the event mask of a run picks the event by, `None` for events ending the stream
+fn event_category(event: &WorkflowEvent) -> Option<EventCategory> {
+    match event.event.as_ref()? {
+        ProtoEvent::WorkflowStart(_) | ProtoEvent::WorkflowPause(_) | ProtoEvent::UnknownEvent(_) => {
+            Some(EventCategory::Workflow)
+        }
+        ProtoEvent::NodeStart(_)
+        | ProtoEvent::NodeRunning(_)
+        | ProtoEvent::NodeStopped(_)
+        | ProtoEvent::NodePaused(_)
+        | ProtoEvent::NodeSkipped(_)
+        | ProtoEvent::NodeSuccess(_)
+        | ProtoEvent::NodeError(_)
+        | ProtoEvent::NodeRetry(_) => Some(EventCategory::Node),
+        ProtoEvent::NodeLog(_) => Some(EventCategory::NodeLog),
+        ProtoEvent::WorkflowProgress(_) => Some(EventCategory::Progress),
+        ProtoEvent::WorkflowSuccess(_)
+        | ProtoEvent::WorkflowFailure(_)
+        | ProtoEvent::WorkflowAbort(_)
+        | ProtoEvent::WorkflowEngineError(_)
+        | ProtoEvent::ServerShuttingDown(_) => None,
+    }
+}
+
+/// How a tracked run ended
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunOutcome {
+    Succeeded,
+    Failed,
+    Aborted,
+}
+
+/// Numbers of runs since the server started
+#[derive(Default)]
+pub struct RunCounters {
+    pub started: AtomicU64,
+    pub succeeded: AtomicU64,
+    pub failed: AtomicU64,
+    pub aborted: AtomicU64,
+    /// Events a client or subscriber stream missed because it did not keep up, shared with the streams
+    pub events_dropped: Arc<AtomicU64>,
+}
+
+/// A workflow process started through `run_workflow`
+pub struct TrackedProcess {
+    /// Process id assigned by the engine
+    pub pid: String,
+    /// Id of the workflow model the process runs
+    pub wid: String,
+    /// Labels the client attached to the run
+    pub labels: HashMap<String, String>,
+    /// Id the client chose for the run, looked up like the pid
+    pub client_pid: Option<String>,
+    events_dropped: Arc<AtomicU64>,
+    log_level: Option<Level>,
+    trace: RunTrace,
+    /// Event stream sender, taken once the terminal event has been sent
+    tx: Mutex<Option<StreamSender>>,
+    /// Abort reason reported instead of the engine's when the server stops the process
+    abort_reason: Mutex<Option<String>>,
+    /// When the workflow start event was observed
+    started_at: Mutex<Option<Instant>>,
+    /// Nodes whose events and logs are streamed, empty means all nodes
+    node_filter: HashSet<String>,
+    event_mask: HashSet<EventCategory>,
+    /// Number of nodes in the workflow
+    total_nodes: u32,
+    /// Number of nodes that reached a terminal state
+    completed_nodes: AtomicU32,
+    /// Events held back for reordering, `None` when events are sent as they arrive
+    reorder: Option<Mutex<BinaryHeap<Reverse<PendingEvent>>>>,
+    /// Sequence number keeping events with equal timestamps in arrival order
+    seq: AtomicU64,
+    /// Timestamp of the latest engine event
+    last_event_timestamp: AtomicI64,
+    /// Holds the events that do not fit into the stream, `None` drops them instead
+    replay: Option<Arc<ReplayBuffer>>,
+    /// Whether the stream nearing its capacity has been logged
+    warned_full: AtomicBool,
+    /// Whether the stream was dropped for a client that stopped reading, the events it misses count as dropped
+    stalled: AtomicBool,
+    /// Close the stream with the `WorkflowPause` event
+    close_on_pause: bool,
+    /// Slot of the run in the scheduler, released when the run stops being tracked
+    permit: Mutex<Option<Permit>>,
+    /// When the latest event or log of the process arrived
+    last_activity: Mutex<Instant>,
+    /// Number of engine events of the process
+    events: AtomicU64,
+    /// Number of log lines of the process
+    log_lines: AtomicU64,
+    /// Clients following the process through `subscribe_workflow`
+    watchers: Mutex<Watchers>,
+    /// Flipped once the streams closed, wakes the tasks watching over the run
+    finished: watch::Sender<bool>,
+    /// Latest node that reported an error
+    errored_nid: Mutex<Option<String>>,
+    /// Sequence number of the latest event sent, taken while holding `watchers` so numbers follow the send order
+    sent_seq: AtomicU64,
+}
+
+/// Subscribers of a process and the latest state a new subscriber starts from
+#[derive(Default)]
+struct Watchers {
+    subscribers: Vec<WorkflowEventTx>,
+    /// Latest workflow start or pause event
+    workflow: Option<WorkflowEvent>,
+    /// Latest event of every node, logs excluded
+    nodes: BTreeMap<String, WorkflowEvent>,
+    /// Latest progress event
+    progress: Option<WorkflowEvent>,
+    /// Set once the terminal event was sent
+    closed: bool,
+    /// Set when a shutdown closed the streams while the process kept running
+    cut_off: bool,
+}
+
+impl Watchers {
+    /// Remembers the event as the latest state of the workflow or its node
+    fn record(
+        &mut self,
+        event: &WorkflowEvent,
+    ) {
+        let nid = match &event.event {
+            Some(ProtoEvent::WorkflowStart(_) | ProtoEvent::WorkflowPause(_)) => {
+                self.workflow = Some(event.clone());
+                return;
+            }
+            Some(ProtoEvent::WorkflowProgress(_)) => {
+                self.progress = Some(event.clone());
+                return;
+            }
+            Some(ProtoEvent::NodeRunning(e)) => &e.nid,
+            Some(ProtoEvent::NodeStopped(e)) => &e.nid,
+            Some(ProtoEvent::NodePaused(e)) => &e.nid,
+            Some(ProtoEvent::NodeSkipped(e)) => &e.nid,
+            Some(ProtoEvent::NodeSuccess(e)) => &e.nid,
+            Some(ProtoEvent::NodeError(e)) => &e.nid,
+            Some(ProtoEvent::NodeRetry(e)) => &e.nid,
+            _ => return,
+        };
+        self.nodes.insert(nid.clone(), event.clone());
+    }
+
+    /// Returns the latest state, workflow first, then every node, then the progress
+    fn snapshot(&self) -> Vec<WorkflowEvent> {
+        self.workflow.iter().chain(self.nodes.values()).chain(self.progress.iter()).cloned().collect()
+    }
+
+    /// Sends an event to every subscriber, forgetting the ones that went away
+    ///
+    /// Returns the number of subscribers that missed the event because their stream was full.
+    fn publish(
+        &mut self,
+        pid: &str,
+        event: &WorkflowEvent,
+    ) -> u64 {
+        let mut dropped = 0;
+        self.subscribers.retain(|tx| match tx.try_send(Ok(event.clone())) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                error!(
+                    "failed to send workflow event to a subscriber of workflow process {}: channel full",
+                    pid
+                );
+                dropped += 1;
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        });
+        dropped
+    }
+}
+
+/// An event waiting in the reorder buffer
+struct PendingEvent {
+    /// Milliseconds since the epoch the event happened at
+    timestamp: i64,
+    seq: u64,
+    event: WorkflowEvent,
+}
+
+impl PartialEq for PendingEvent {
+    fn eq(
+        &self,
+        other: &Self,
+    ) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for PendingEvent {}
+
+impl PartialOrd for PendingEvent {
+    fn partial_cmp(
+        &self,
+        other: &Self,
+    ) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PendingEvent {
+    fn cmp(
+        &self,
+        other: &Self,
+    ) -> CmpOrdering {
+        (self.timestamp, self.seq).cmp(&(other.timestamp, other.seq))
+    }
+}
+
+impl TrackedProcess {
+    pub fn new(
+        pid: String,
+        wid: String,
+        tx: StreamSender,
+        total_nodes: u32,
+        options: RunOptions,
+    ) -> Self {
+        Self {
+            pid,
+            wid,
+            labels: options.labels,
+            client_pid: options.client_pid,
+            events_dropped: options.events_dropped,
+            log_level: options.log_level,
+            trace: options.trace,
+            tx: Mutex::new(Some(tx)),
+            abort_reason: Mutex::new(None),
+            started_at: Mutex::new(None),
+            node_filter: options.node_filter,
+            event_mask: options.event_mask,
+            total_nodes,
+            completed_nodes: AtomicU32::new(0),
+            reorder: match options.ordering {
+                EventOrdering::BestEffort => None,
+                EventOrdering::Causal => Some(Mutex::new(BinaryHeap::new())),
+            },
+            seq: AtomicU64::new(0),
+            last_event_timestamp: AtomicI64::new(0),
+            replay: options.replay_capacity.map(|capacity| Arc::new(ReplayBuffer::new(capacity))),
+            warned_full: AtomicBool::new(false),
+            stalled: AtomicBool::new(false),
+            close_on_pause: options.close_on_pause,
+            permit: Mutex::new(options.permit),
+            last_activity: Mutex::new(Instant::now()),
+            events: AtomicU64::new(0),
+            log_lines: AtomicU64::new(0),
+            watchers: Mutex::new(Watchers::default()),
+            finished: watch::Sender::new(false),
+            errored_nid: Mutex::new(None),
+            sent_seq: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the buffer for the events that do not fit into the stream, if the client asked for one
+    pub fn replay(&self) -> Option<Arc<ReplayBuffer>> {
+        self.replay.clone()
+    }
+
+    /// Checks whether every label of `selector` is attached to the run with the same value
+    pub fn matches_labels(
+        &self,
+        selector: &HashMap<String, String>,
+    ) -> bool {
+        selector.iter().all(|(key, value)| self.labels.get(key) == Some(value))
+    }
+
+    /// Returns the timestamp to send an engine event with
+    ///
+    /// The engine delivers its events in order, so an event is never stamped earlier than the one
+    /// before it; events the engine did not timestamp take the timestamp of the previous event.
+    pub fn event_timestamp(
+        &self,
+        timestamp: Option<i64>,
+    ) -> i64 {
+        let timestamp = timestamp.unwrap_or(i64::MIN);
+        self.last_event_timestamp.fetch_max(timestamp, Ordering::SeqCst).max(timestamp)
+    }
+
+    /// Returns the number of events waiting for the client, in the stream and the replay buffer
+    pub fn queued_events(&self) -> usize {
+        let in_stream = match self.tx.lock().unwrap().as_ref() {
+            Some(sender) => sender.pending(),
+            None => 0,
+        };
+        in_stream + self.replay.as_ref().map(|replay| replay.len()).unwrap_or(0)
+    }
+
+    /// Records that an event or log of the process arrived
+    pub fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    /// Returns how long ago the latest event or log of the process arrived
+    pub fn idle_for(&self) -> Duration {
+        self.last_activity.lock().unwrap().elapsed()
+    }
+
+    /// Counts an engine event of the process
+    pub fn count_event(&self) {
+        self.events.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Counts a log line of the process
+    pub fn count_log_line(&self) {
+        self.log_lines.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Records that a node reported an error
+    pub fn set_errored_nid(
+        &self,
+        nid: &str,
+    ) {
+        *self.errored_nid.lock().unwrap() = Some(nid.to_owned());
+    }
+
+    /// Returns the latest node that reported an error, if any
+    pub fn errored_nid(&self) -> Option<String> {
+        self.errored_nid.lock().unwrap().clone()
+    }
+
+    /// Returns what the process emitted so far
+    pub fn usage(&self) -> ResourceUsage {
+        ResourceUsage {
+            events: self.events.load(Ordering::SeqCst),
+            log_lines: self.log_lines.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Checks whether the stream ends with the `WorkflowPause` event
+    pub fn closes_on_pause(&self) -> bool {
+        self.close_on_pause
+    }
+
+    /// Checks whether events are held back and sent in timestamp order
+    pub fn is_reordering(&self) -> bool {
+        self.reorder.is_some()
+    }
+
+    /// Records that a node reached a terminal state, returns the number of completed nodes
+    pub fn complete_node(&self) -> u32 {
+        self.completed_nodes.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Returns the number of nodes in the workflow
+    pub fn total_nodes(&self) -> u32 {
+        self.total_nodes
+    }
+
+    /// Checks whether events and logs of the given node are streamed to the client
+    pub fn accepts_node(
+        &self,
+        nid: &str,
+    ) -> bool {
+        self.node_filter.is_empty() || self.node_filter.contains(nid)
+    }
+
+    /// Checks whether events of the given category are streamed to the client
+    pub fn accepts_category(
+        &self,
+        category: EventCategory,
+    ) -> bool {
+        self.event_mask.is_empty() || self.event_mask.contains(&category)
+    }
+
+    /// Checks whether the event passes the event mask, events ending the stream always do
+    pub fn accepts_event(
+        &self,
+        event: &WorkflowEvent,
+    ) -> bool {
+        event_category(event).is_none_or(|category| self.accepts_category(category))
+    }
+
+    /// Writes a line about this run to the server log if the run asked for `level`
+    ///
+    /// The line goes to [`RUN_LOG_TARGET`], so it is written whatever level the server logs at.
+    pub fn log_at(
+        &self,
+        level: Level,
+        line: impl FnOnce() -> String,
+    ) {
+        if self.log_level.is_some_and(|max| level <= max) {
+            log::log!(target: RUN_LOG_TARGET, level, "workflow process {}: {}", self.pid, line());
+        }
+    }
+
+    /// Returns the span of the run
+    pub fn trace(&self) -> &RunTrace {
+        &self.trace
+    }
+
+    /// Records that the workflow has started
+    pub fn mark_started(&self) {
+        *self.started_at.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Returns the milliseconds elapsed since the workflow started, 0 if it never started
+    pub fn elapsed_ms(&self) -> u64 {
+        self.started_at.lock().unwrap().map(|t| t.elapsed().as_millis() as u64).unwrap_or(0)
+    }
+
+    /// Sends a non-terminal event that happened at `timestamp` (milliseconds since the epoch)
+    ///
+    /// In causal ordering mode the event is buffered until [`TrackedProcess::flush`] releases it.
+    pub fn send(
+        &self,
+        event: WorkflowEvent,
+        timestamp: i64,
+    ) {
+        match &self.reorder {
+            Some(reorder) => reorder.lock().unwrap().push(Reverse(PendingEvent {
+                timestamp,
+                seq: self.seq.fetch_add(1, Ordering::SeqCst),
+                event,
+            })),
+            None => self.send_now(event),
+        }
+    }
+
+    /// Sends the buffered events older than `watermark` (milliseconds since the epoch) in timestamp order
+    pub fn flush(
+        &self,
+        watermark: i64,
+    ) {
+        let Some(reorder) = &self.reorder else {
+            return;
+        };
+        let mut reorder = reorder.lock().unwrap();
+        while reorder.peek().is_some_and(|Reverse(pending)| pending.timestamp <= watermark) {
+            let Reverse(pending) = reorder.pop().unwrap();
+            self.send_now(pending.event);
+        }
+    }
+
+    fn send_now(
+        &self,
+        mut event: WorkflowEvent,
+    ) {
+        // held until the client stream has the event too, so both see the same sequence
+        let mut watchers = self.watchers.lock().unwrap();
+        event.seq = self.next_seq();
+        watchers.record(&event);
+        self.count_dropped(watchers.publish(&self.pid, &event));
+        match self.tx.lock().unwrap().as_ref() {
+            Some(sender) => self.deliver(sender, event, false),
+            None => self.count_stalled(),
+        }
+    }
+
+    /// Counts an event a client that stopped reading misses
+    fn count_stalled(&self) {
+        if self.stalled.load(Ordering::SeqCst) {
+            self.count_dropped(1);
+        }
+    }
+
+    fn count_dropped(
+        &self,
+        events: u64,
+    ) {
+        if events > 0 {
+            self.events_dropped.fetch_add(events, Ordering::SeqCst);
+        }
+    }
+
+    /// Returns the sequence number of the next event sent, starting at 1
+    fn next_seq(&self) -> u64 {
+        self.sent_seq.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    fn deliver(
+        &self,
+        sender: &StreamSender,
+        event: WorkflowEvent,
+        terminal: bool,
+    ) {
+        let res = match &self.replay {
+            Some(replay) => replay.send(sender.channel(), Ok(event), terminal),
+            None => sender.send(Ok(event)),
+        };
+        if let Err(e) = res {
+            error!("failed to send workflow event: {}", e);
+            // a client that went away missed nothing it still wanted
+            if !sender.channel().is_closed() {
+                self.count_dropped(1);
+            }
+        }
+
+        // warn once per process, before the client falls far enough behind for events to be dropped
+        let channel = sender.channel();
+        let used = channel.max_capacity() - channel.capacity();
+        if used * 100 >= channel.max_capacity() * STREAM_WARN_PERCENT && !self.warned_full.swap(true, Ordering::SeqCst) {
+            warn!(
+                "event stream of workflow process {} is {}% full, the client is not keeping up",
+                self.pid,
+                used * 100 / channel.max_capacity()
+            );
+        }
+    }
+
+    /// Sends the terminal event and closes the client and subscriber streams
+    ///
+    /// Buffered events are sent first. Only the first terminal event of a process is sent, so a stop
+    /// racing with the engine's own terminal event still ends the streams with exactly one. Returns
+    /// `false` if the process had already finished.
+    pub fn finish(
+        &self,
+        mut event: WorkflowEvent,
+    ) -> bool {
+        self.flush(i64::MAX);
+        let mut watchers = self.watchers.lock().unwrap();
+        if watchers.closed {
+            // the streams of a run cut off by a shutdown are gone, but its span still ends with the run
+            if watchers.cut_off {
+                self.trace.end(&event);
+            }
+            return false;
+        }
+        event.seq = self.next_seq();
+        watchers.closed = true;
+        self.finished.send_replace(true);
+        // under the lock, so the span ends with the same terminal event as the streams
+        self.trace.end(&event);
+        self.count_dropped(watchers.publish(&self.pid, &event));
+        watchers.subscribers.clear();
+        self.close_stream(event);
+        true
+    }
+
+    /// Ends the client and subscriber streams with `event` while the process is still running
+    ///
+    /// Unlike [`TrackedProcess::finish`] it waits up to `timeout` for the event to make it into the client stream.
+    /// Returns `false` if the event could not be sent on the client stream.
+    pub async fn cut_off(
+        &self,
+        mut event: WorkflowEvent,
+        timeout: Duration,
+    ) -> bool {
+        self.flush(i64::MAX);
+        let sender = {
+            let mut watchers = self.watchers.lock().unwrap();
+            event.seq = self.next_seq();
+            watchers.closed = true;
+            watchers.cut_off = true;
+            self.finished.send_replace(true);
+            self.count_dropped(watchers.publish(&self.pid, &event));
+            watchers.subscribers.clear();
+            self.tx.lock().unwrap().take()
+        };
+        let Some(sender) = sender else {
+            return false;
+        };
+        match &self.replay {
+            Some(replay) => replay.send(sender.channel(), Ok(event), true).is_ok(),
+            None => sender.send(Ok(event)).is_ok() && sender.close(timeout).await,
+        }
+    }
+
+    /// Ends the client stream with an error, subscribers keep following the process
+    ///
+    /// Buffered events are sent first. Returns `false` if the client stream was already closed.
+    pub fn close_with_status(
+        &self,
+        status: Status,
+    ) -> bool {
+        self.flush(i64::MAX);
+        let Some(sender) = self.tx.lock().unwrap().take() else {
+            return false;
+        };
+        let res = match &self.replay {
+            Some(replay) => replay.send(sender.channel(), Err(status), true),
+            None => sender.send(Err(status)),
+        };
+        if let Err(e) = &res {
+            error!("failed to send the closing status of workflow process {}: {}", self.pid, e);
+        }
+        res.is_ok()
+    }
+
+    /// Sends a last event on the client stream and closes it, subscribers keep following the process
+    ///
+    /// Buffered events are sent first. Returns `false` if the client stream was already closed.
+    pub fn detach(
+        &self,
+        mut event: WorkflowEvent,
+    ) -> bool {
+        self.flush(i64::MAX);
+        let _watchers = self.watchers.lock().unwrap();
+        event.seq = self.next_seq();
+        self.close_stream(event)
+    }
+
+    /// Sends the last event on the client stream and closes it
+    fn close_stream(
+        &self,
+        event: WorkflowEvent,
+    ) -> bool {
+        match self.tx.lock().unwrap().take() {
+            Some(sender) => {
+                self.deliver(&sender, event, true);
+                true
+            }
+            None => {
+                self.count_stalled();
+                false
+            }
+        }
+    }
+
+    /// Drops the client stream once its sender gave up on a client that read nothing for the send timeout
+    ///
+    /// The client gets the events already in the stream, then the `DATA_LOSS` status the sender left.
+    /// Subscribers keep following the process.
+    pub fn drop_stalled_stream(&self) {
+        let _watchers = self.watchers.lock().unwrap();
+        if self.tx.lock().unwrap().take().is_some() {
+            self.stalled.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Checks whether the terminal event has already been sent
+    pub fn is_finished(&self) -> bool {
+        self.watchers.lock().unwrap().closed
+    }
+
+    /// Resolves once [`TrackedProcess::is_finished`] turns true, so a task watching over the run can stop early
+    pub fn finished(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut finished = self.finished.subscribe();
+        async move {
+            // the sender lives as long as the process, which the caller keeps
+            let _ = finished.wait_for(|finished| *finished).await;
+        }
+    }
+
+    /// Opens another stream of the events of the process, unless it finished or has `max_subscribers` already
+    ///
+    /// Subscribers get the events the client stream gets, after its node filter. With `snapshot`
+    /// the stream starts with the latest workflow state, the latest event of every node and the
+    /// progress, followed by the live events.
+    pub fn subscribe(
+        &self,
+        snapshot: bool,
+        max_subscribers: usize,
+    ) -> Result<mpsc::Receiver<Result<WorkflowEvent, Status>>, SubscribeError> {
+        let mut watchers = self.watchers.lock().unwrap();
+        if watchers.closed {
+            return Err(SubscribeError::Finished);
+        }
+        // subscribers that went away only leave on the next event otherwise
+        watchers.subscribers.retain(|tx| !tx.is_closed());
+        if watchers.subscribers.len() >= max_subscribers {
+            return Err(SubscribeError::TooManySubscribers(watchers.subscribers.len()));
+        }
+        let snapshot = if snapshot {
+            watchers.snapshot()
+        } else {
+            Vec::new()
+        };
+        let (tx, rx) = mpsc::channel(SUBSCRIBER_STREAM_SIZE + snapshot.len());
+        for event in snapshot {
+            let _ = tx.try_send(Ok(event));
+        }
+        watchers.subscribers.push(tx);
+        Ok(rx)
+    }
+
+    /// Overrides the abort reason reported to the client
+    pub fn set_abort_reason(
+        &self,
+        reason: String,
+    ) {
+        *self.abort_reason.lock().unwrap() = Some(reason);
+    }
+
+    /// Returns the abort reason set by the server, if any
+    pub fn abort_reason(&self) -> Option<String> {
+        self.abort_reason.lock().unwrap().clone()
+    }
+}
+
+/// Number of finished process ids remembered to tell them apart from unknown ones
+const FINISHED_HISTORY: usize = 1024;
+
+/// Registry of the workflow processes that are still running
+#[derive(Default)]
+pub struct ProcessTracker {
+    procs: Mutex<HashMap<String, Arc<TrackedProcess>>>,
+    /// Pids of the tracked processes by the id their client chose
+    client_pids: Mutex<HashMap<String, String>>,
+    /// Ids of the most recently finished processes, oldest first
+    finished: Mutex<VecDeque<String>>,
+    /// Numbers of runs since the server started
+    counters: RunCounters,
+}
+
+impl ProcessTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts tracking a process
+    pub fn insert(
+        &self,
+        proc: Arc<TrackedProcess>,
+    ) {
+        self.counters.started.fetch_add(1, Ordering::SeqCst);
+        if let Some(client_pid) = &proc.client_pid {
+            self.client_pids.lock().unwrap().insert(client_pid.clone(), proc.pid.clone());
+        }
+        self.procs.lock().unwrap().insert(proc.pid.clone(), proc);
+    }
+
+    /// Returns the tracked process with the given pid or client pid
+    pub fn get(
+        &self,
+        pid: &str,
+    ) -> Option<Arc<TrackedProcess>> {
+        let procs = self.procs.lock().unwrap();
+        if let Some(proc) = procs.get(pid) {
+            return Some(proc.clone());
+        }
+        let client_pids = self.client_pids.lock().unwrap();
+        client_pids.get(pid).and_then(|pid| procs.get(pid)).cloned()
+    }
+
+    /// Stops tracking a process, remembering it as finished with the given outcome
+    ///
+    /// Only the first removal of a process is counted.
+    pub fn remove(
+        &self,
+        pid: &str,
+        outcome: RunOutcome,
+    ) -> Option<Arc<TrackedProcess>> {
+        let proc = self.procs.lock().unwrap().remove(pid)?;
+        if let Some(client_pid) = &proc.client_pid {
+            self.client_pids.lock().unwrap().remove(client_pid);
+        }
+        // lets the next queued run start
+        proc.permit.lock().unwrap().take();
+        let counter = match outcome {
+            RunOutcome::Succeeded => &self.counters.succeeded,
+            RunOutcome::Failed => &self.counters.failed,
+            RunOutcome::Aborted => &self.counters.aborted,
+        };
+        counter.fetch_add(1, Ordering::SeqCst);
+        let mut finished = self.finished.lock().unwrap();
+        if finished.len() >= FINISHED_HISTORY {
+            finished.pop_front();
+        }
+        finished.push_back(proc.pid.clone());
+        if let Some(client_pid) = &proc.client_pid {
+            if finished.len() >= FINISHED_HISTORY {
+                finished.pop_front();
+            }
+            finished.push_back(client_pid.clone());
+        }
+        Some(proc)
+    }
+
+    /// Checks whether the process was tracked and has finished recently
+    pub fn is_recently_finished(
+        &self,
+        pid: &str,
+    ) -> bool {
+        self.finished.lock().unwrap().iter().any(|finished| finished == pid)
+    }
+
+    /// Returns the tracked processes of the given workflow
+    pub fn by_wid(
+        &self,
+        wid: &str,
+    ) -> Vec<Arc<TrackedProcess>> {
+        self.procs.lock().unwrap().values().filter(|proc| proc.wid == wid).cloned().collect()
+    }
+
+    /// Checks whether a process of the given workflow is tracked
+    pub fn is_running_wid(
+        &self,
+        wid: &str,
+    ) -> bool {
+        self.procs.lock().unwrap().values().any(|proc| proc.wid == wid)
+    }
+
+    /// Returns the numbers of runs since the server started
+    pub fn counters(&self) -> &RunCounters {
+        &self.counters
+    }
+
+    /// Returns a snapshot of all tracked processes
+    pub fn all(&self) -> Vec<Arc<TrackedProcess>> {
+        self.procs.lock().unwrap().values().cloned().collect()
+    }
+}
@@ -0,0 +1,24 @@
+use http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Request headers a gRPC-Web client sends besides the simple ones
+const ALLOWED_HEADERS: [&str; 4] = ["content-type", "grpc-timeout", "x-grpc-web", "x-user-agent"];
+
+/// Response headers a gRPC-Web client has to read the status from
+const EXPOSED_HEADERS: [&str; 3] = ["grpc-status", "grpc-message", "grpc-status-details-bin"];
+
+/// Builds the CORS layer letting browsers on `origins` call the server, "*" allows any origin
+///
+/// The origins are validated when the config is loaded.
+pub fn cors_layer(origins: &[String]) -> CorsLayer {
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origins.iter().filter_map(|origin| HeaderValue::from_str(origin).ok()))
+    };
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::POST])
+        .allow_headers(ALLOWED_HEADERS.map(HeaderName::from_static))
+        .expose_headers(EXPOSED_HEADERS.map(HeaderName::from_static))
+}
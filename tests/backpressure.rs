@@ -0,0 +1,156 @@
+mod common;
+
+use actflow_server::proto::{
+    Empty, ListWorkflowsRequest, RunWorkflowRequest, WorkflowEvent, workflow_event::Event as ProtoEvent,
+    workflow_service_client::WorkflowServiceClient,
+};
+use common::{TestServer, chain_workflow};
+use serde_json::{Value, json};
+use std::time::Duration;
+use tonic::{
+    Code, Streaming,
+    transport::{Channel, Endpoint},
+};
+
+/// Number of nodes of the workflows run, enough events to fill the stream of a client that does not read
+const NODES: usize = 400;
+
+/// A workflow that emits enough events to fill the stream, then waits on `url` for a minute
+fn stalling_workflow(url: &str) -> String {
+    let mut model: Value = serde_json::from_str(&chain_workflow(NODES)).unwrap();
+    model["nodes"].as_array_mut().unwrap().push(json!({
+        "id": "wait", "title": "wait", "desc": "", "uses": "http_request", "action": {
+            "url": url, "method": "GET", "auth": {"auth_type": "no_auth"}, "headers": {}, "params": {},
+            "body": {"content_type": "none"}, "timeout": 60000
+        }
+    }));
+    model["edges"].as_array_mut().unwrap().push(json!({
+        "id": "wait", "source": format!("n{}", NODES - 1), "target": "wait", "source_handle": "source"
+    }));
+    model.to_string()
+}
+
+/// Connects a client whose HTTP/2 window only lets a few events through before it reads them
+async fn small_window_client(server: &TestServer) -> WorkflowServiceClient<Channel> {
+    // waits for the listener to come up
+    server.client().await;
+    let channel = Endpoint::from_shared(server.addr.clone())
+        .unwrap()
+        .initial_stream_window_size(1024)
+        .initial_connection_window_size(1024)
+        .connect()
+        .await
+        .unwrap();
+    WorkflowServiceClient::new(channel)
+}
+
+async fn run(client: &mut WorkflowServiceClient<Channel>) -> Streaming<WorkflowEvent> {
+    client
+        .run_workflow(RunWorkflowRequest {
+            workflow_model: chain_workflow(NODES),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner()
+}
+
+async fn read_to_end(stream: &mut Streaming<WorkflowEvent>) -> Vec<ProtoEvent> {
+    let mut events = Vec::new();
+    while let Some(event) = tokio::time::timeout(Duration::from_secs(10), stream.message()).await.unwrap().unwrap() {
+        events.push(event.event.unwrap());
+    }
+    events
+}
+
+#[test]
+fn fast_reader_gets_every_event() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = small_window_client(&server).await;
+        let mut stream = run(&mut client).await;
+
+        let events = read_to_end(&mut stream).await;
+        // start, running, success and progress for every node, then the terminal event
+        assert_eq!(events.len(), 1 + NODES * 3 + 1);
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+
+        let stats = client.get_server_stats(Empty {}).await.unwrap().into_inner();
+        assert_eq!(stats.events_dropped, 0);
+    });
+}
+
+#[test]
+fn briefly_stalled_reader_gets_every_event() {
+    let server = TestServer::start_with(|config| config.stream_send_timeout_ms = 5000);
+    server.runtime.block_on(async {
+        let mut client = small_window_client(&server).await;
+        let mut stream = run(&mut client).await;
+
+        // the run finishes while the stream is full
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let events = read_to_end(&mut stream).await;
+        assert_eq!(events.len(), 1 + NODES * 3 + 1);
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+    });
+}
+
+#[test]
+fn stuck_reader_has_its_stream_closed_while_the_run_goes_on() {
+    let server = TestServer::start_with(|config| config.stream_send_timeout_ms = 100);
+    let model = stalling_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = small_window_client(&server).await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        // the run waits on its last node for a minute, so the stream has to end on its own
+        let mut events = Vec::new();
+        let status = loop {
+            match tokio::time::timeout(Duration::from_secs(10), stream.message()).await.unwrap() {
+                Ok(Some(event)) => events.push(event.event.unwrap()),
+                Ok(None) => panic!("stream ended without a status after {} events", events.len()),
+                Err(status) => break status,
+            }
+        };
+        assert_eq!(status.code(), Code::DataLoss, "{}", status);
+        assert!(events.len() < 1 + NODES * 3, "{} events", events.len());
+        assert!(tokio::time::timeout(Duration::from_secs(1), stream.message()).await.unwrap().unwrap().is_none());
+
+        let stats = server.client().await.get_server_stats(Empty {}).await.unwrap().into_inner();
+        assert!(stats.events_dropped > 0);
+        let running = server.client().await.list_workflows(ListWorkflowsRequest::default()).await.unwrap().into_inner();
+        assert_eq!(running.workflows.len(), 1, "the run ended with the stream");
+    });
+}
+
+#[test]
+fn unbounded_stream_keeps_every_event_of_a_stuck_reader() {
+    let server = TestServer::start_with(|config| {
+        config.stream_send_timeout_ms = 100;
+        config.unbounded_events = true;
+    });
+    server.runtime.block_on(async {
+        let mut client = small_window_client(&server).await;
+        let mut stream = run(&mut client).await;
+
+        // far beyond the send timeout, which no longer applies
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let events = read_to_end(&mut stream).await;
+        assert_eq!(events.len(), 1 + NODES * 3 + 1);
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+
+        let stats = server.client().await.get_server_stats(Empty {}).await.unwrap().into_inner();
+        assert_eq!(stats.events_dropped, 0);
+    });
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use actflow_server::proto::RunWorkflowRequest;
+use common::TestServer;
+use tonic::{Code, Status};
+use tonic_types::StatusExt;
+
+const UNKNOWN_EDGE_TARGET: &str = r#"{
+    "id": "broken", "name": "broken", "desc": "", "env": {},
+    "nodes": [{"id": "n1", "title": "start", "desc": "", "uses": "start", "action": {}}],
+    "edges": [{"id": "e1", "source": "n1", "target": "missing", "source_handle": "source"}]
+}"#;
+
+const INVALID_ACTION: &str = r#"{
+    "id": "broken", "name": "broken", "desc": "", "env": {},
+    "nodes": [{"id": "n1", "title": "request", "desc": "", "uses": "http_request", "action": {"method": "GET"}}],
+    "edges": []
+}"#;
+
+async fn run(
+    server: &TestServer,
+    workflow_model: &str,
+) -> Status {
+    let mut client = server.client().await;
+    client
+        .run_workflow(RunWorkflowRequest {
+            workflow_model: workflow_model.to_owned(),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err()
+}
+
+fn category(status: &Status) -> String {
+    let info = status.get_details_error_info().expect("error info details");
+    assert_eq!(info.reason, "WORKFLOW_BUILD_FAILED");
+    info.metadata["category"].clone()
+}
+
+#[test]
+fn model_mistakes_are_invalid_arguments() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let status = run(&server, UNKNOWN_EDGE_TARGET).await;
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(category(&status), "edge");
+
+        let status = run(&server, INVALID_ACTION).await;
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(category(&status), "convert");
+    });
+}
@@ -0,0 +1,53 @@
+use std::{collections::HashMap, env, fs, path::PathBuf, process};
+
+use actflow_server::server::{Checkpoint, CheckpointStore, FsCheckpointStore};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("actflow-server-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn checkpoint(pid: &str) -> Checkpoint {
+    Checkpoint {
+        pid: pid.to_owned(),
+        wid: "simple".to_owned(),
+        workflow_model: "{}".to_owned(),
+        node_filter: vec!["n1".to_owned()],
+        labels: HashMap::from([("team".to_owned(), "billing".to_owned())]),
+    }
+}
+
+#[test]
+fn fs_store_round_trip() {
+    let dir = temp_dir("checkpoint-round-trip");
+    let store = FsCheckpointStore::open(&dir).unwrap();
+
+    store.save(&checkpoint("p1")).unwrap();
+    store.save(&checkpoint("p2")).unwrap();
+    let mut pids: Vec<_> = store.list().unwrap().into_iter().map(|c| c.pid).collect();
+    pids.sort();
+    assert_eq!(pids, ["p1", "p2"]);
+
+    store.remove("p1").unwrap();
+    // removing a missing checkpoint is not an error
+    store.remove("p1").unwrap();
+    let listed = store.list().unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].pid, "p2");
+    assert_eq!(listed[0].node_filter, ["n1"]);
+    assert_eq!(listed[0].labels["team"], "billing");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn fs_store_survives_reopen() {
+    let dir = temp_dir("checkpoint-reopen");
+    FsCheckpointStore::open(&dir).unwrap().save(&checkpoint("p1")).unwrap();
+
+    let reopened = FsCheckpointStore::open(&dir).unwrap();
+    assert_eq!(reopened.list().unwrap().len(), 1);
+
+    fs::remove_dir_all(dir).unwrap();
+}
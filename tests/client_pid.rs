@@ -0,0 +1,57 @@
+mod common;
+
+use actflow_server::proto::{
+    ListWorkflowsRequest, RunWorkflowRequest, StopWorkflowRequest, SubscribeWorkflowRequest, workflow_event::Event as ProtoEvent,
+};
+use common::{TestServer, blocking_workflow};
+use tonic::Code;
+
+#[test]
+fn client_pid_is_accepted_in_place_of_the_pid() {
+    let server = TestServer::start();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let request = RunWorkflowRequest {
+            workflow_model: model,
+            client_pid: "order-42".to_owned(),
+            ..Default::default()
+        };
+        let mut stream = client.run_workflow(request.clone()).await.unwrap().into_inner();
+        let Some(ProtoEvent::WorkflowStart(start)) = stream.message().await.unwrap().unwrap().event else {
+            panic!("expected a workflow start event");
+        };
+        assert_ne!(start.pid, "order-42");
+
+        // taken while the run is going
+        let err = client.run_workflow(request).await.unwrap_err();
+        assert_eq!(err.code(), Code::AlreadyExists);
+
+        let workflows = client.list_workflows(ListWorkflowsRequest::default()).await.unwrap().into_inner().workflows;
+        assert_eq!(workflows[0].pid, start.pid);
+        assert_eq!(workflows[0].client_pid, "order-42");
+
+        client
+            .subscribe_workflow(SubscribeWorkflowRequest {
+                pid: "order-42".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let stopped = client
+            .stop_workflow(StopWorkflowRequest {
+                pid: "order-42".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(stopped.success, "{}", stopped.err_msg);
+        while let Some(event) = stream.message().await.unwrap() {
+            if matches!(event.event, Some(ProtoEvent::WorkflowAbort(_))) {
+                break;
+            }
+        }
+    });
+}
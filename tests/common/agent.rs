@@ -0,0 +1,153 @@
+//! Fake agent service for the `agent` action, the only action that writes node logs
+//!
+//! The messages mirror the agent proto bundled with actflow, fields the tests do not need are left out.
+
+use std::{
+    convert::Infallible,
+    future::{Ready, ready},
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use tonic::{
+    Status,
+    body::Body,
+    server::{Grpc, NamedService, ServerStreamingService},
+    transport::{Server, server::TcpIncoming},
+};
+use tonic_prost::ProstCodec;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RunRequest {
+    #[prost(string, tag = "1")]
+    pub pid: String,
+    #[prost(string, tag = "2")]
+    pub nid: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AgentUpdate {
+    #[prost(oneof = "RelayMessage", tags = "1, 2")]
+    pub relay_message: Option<RelayMessage>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum RelayMessage {
+    #[prost(string, tag = "1")]
+    Log(String),
+    #[prost(message, tag = "2")]
+    Output(AgentOutput),
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AgentOutput {
+    /// `NodeExecutionStatus`, 1 is SUCCEEDED
+    #[prost(int32, tag = "1")]
+    pub status: i32,
+}
+
+/// Agent that answers every run with the given log lines and then succeeds
+#[derive(Clone)]
+pub struct FakeAgent {
+    logs: Arc<Vec<String>>,
+}
+
+impl FakeAgent {
+    pub fn new(logs: Vec<String>) -> Self {
+        Self {
+            logs: Arc::new(logs),
+        }
+    }
+
+    /// Serves the agent on a free local port, returns the endpoint for the `agent` action
+    pub async fn serve(self) -> String {
+        let incoming = TcpIncoming::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let addr = incoming.local_addr().unwrap();
+        tokio::spawn(Server::builder().add_service(self).serve_with_incoming(incoming));
+        format!("http://{}", addr)
+    }
+}
+
+struct Run(Arc<Vec<String>>);
+
+type UpdateStream = tokio_stream::Iter<std::vec::IntoIter<Result<AgentUpdate, Status>>>;
+
+impl ServerStreamingService<RunRequest> for Run {
+    type Response = AgentUpdate;
+    type ResponseStream = UpdateStream;
+    type Future = Ready<Result<tonic::Response<UpdateStream>, Status>>;
+
+    fn call(
+        &mut self,
+        _request: tonic::Request<RunRequest>,
+    ) -> Self::Future {
+        let updates = self
+            .0
+            .iter()
+            .map(|log| RelayMessage::Log(log.clone()))
+            .chain([RelayMessage::Output(AgentOutput {
+                status: 1,
+            })])
+            .map(|message| {
+                Ok(AgentUpdate {
+                    relay_message: Some(message),
+                })
+            })
+            .collect::<Vec<_>>();
+        ready(Ok(tonic::Response::new(tokio_stream::iter(updates))))
+    }
+}
+
+impl tower::Service<http::Request<Body>> for FakeAgent {
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Infallible>> + Send>>;
+
+    fn poll_ready(
+        &mut self,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(
+        &mut self,
+        request: http::Request<Body>,
+    ) -> Self::Future {
+        let logs = self.logs.clone();
+        Box::pin(async move {
+            match request.uri().path() {
+                "/agent.AgentService/Run" => {
+                    let mut grpc = Grpc::new(ProstCodec::<AgentUpdate, RunRequest>::default());
+                    Ok(grpc.server_streaming(Run(logs), request).await)
+                }
+                _ => Ok(Status::unimplemented("not supported by the fake agent").into_http()),
+            }
+        })
+    }
+}
+
+impl NamedService for FakeAgent {
+    const NAME: &'static str = "agent.AgentService";
+}
+
+/// A workflow whose middle node runs the agent at `endpoint`
+pub fn agent_workflow(endpoint: &str) -> String {
+    format!(
+        r#"{{
+    "id": "agent", "name": "agent", "desc": "", "env": {{}},
+    "nodes": [
+        {{"id": "n1", "title": "start", "desc": "", "uses": "start", "action": {{}}}},
+        {{"id": "n2", "title": "agent", "desc": "", "uses": "agent", "action": {{"endpoint": "{}", "inputs": {{}}}}}},
+        {{"id": "n3", "title": "end", "desc": "", "uses": "end", "action": {{}}}}
+    ],
+    "edges": [
+        {{"id": "e1", "source": "n1", "target": "n2", "source_handle": "source"}},
+        {{"id": "e2", "source": "n2", "target": "n3", "source_handle": "source"}}
+    ]
+}}"#,
+        endpoint
+    )
+}
@@ -0,0 +1,246 @@
+// each test crate only uses part of the helpers
+#![allow(dead_code)]
+
+pub mod agent;
+pub mod pki;
+
+use std::{
+    fs,
+    net::TcpListener,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use actflow_server::{
+    config::{ListenerConfig, ServerConfig, TlsConfig},
+    proto::{
+        RunWorkflowRequest, WorkflowEvent, workflow_event::Event as ProtoEvent, workflow_service_client::WorkflowServiceClient,
+    },
+    server::{ServerError, ServerHandle},
+};
+use pki::Pki;
+use tokio::runtime::{Builder, Runtime};
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
+
+/// A two node workflow that succeeds right away
+pub const SIMPLE_WORKFLOW: &str = r#"{
+    "id": "simple", "name": "simple", "desc": "", "env": {},
+    "nodes": [
+        {"id": "n1", "title": "start", "desc": "", "uses": "start", "action": {}},
+        {"id": "n2", "title": "end", "desc": "", "uses": "end", "action": {}}
+    ],
+    "edges": [{"id": "e1", "source": "n1", "target": "n2", "source_handle": "source"}]
+}"#;
+
+/// A workflow of `len` nodes run one after the other
+pub fn chain_workflow(len: usize) -> String {
+    let nodes: Vec<_> = (0..len)
+        .map(|i| {
+            format!(
+                r#"{{"id": "n{}", "title": "n{}", "desc": "", "uses": "start", "action": {{}}}}"#,
+                i, i
+            )
+        })
+        .collect();
+    let edges: Vec<_> = (1..len)
+        .map(|i| {
+            format!(
+                r#"{{"id": "e{}", "source": "n{}", "target": "n{}", "source_handle": "source"}}"#,
+                i,
+                i - 1,
+                i
+            )
+        })
+        .collect();
+    format!(
+        r#"{{"id": "chain", "name": "chain", "desc": "", "env": {{}}, "nodes": [{}], "edges": [{}]}}"#,
+        nodes.join(","),
+        edges.join(",")
+    )
+}
+
+/// A workflow whose middle node waits for an HTTP response from `url` for up to a minute
+pub fn blocking_workflow(url: &str) -> String {
+    format!(
+        r#"{{
+    "id": "blocking", "name": "blocking", "desc": "", "env": {{}},
+    "nodes": [
+        {{"id": "n1", "title": "start", "desc": "", "uses": "start", "action": {{}}}},
+        {{"id": "n2", "title": "wait", "desc": "", "uses": "http_request", "action": {{
+            "url": "{}", "method": "GET", "auth": {{"auth_type": "no_auth"}}, "headers": {{}}, "params": {{}},
+            "body": {{"content_type": "none"}}, "timeout": 60000
+        }}}},
+        {{"id": "n3", "title": "end", "desc": "", "uses": "end", "action": {{}}}}
+    ],
+    "edges": [
+        {{"id": "e1", "source": "n1", "target": "n2", "source_handle": "source"}},
+        {{"id": "e2", "source": "n2", "target": "n3", "source_handle": "source"}}
+    ]
+}}"#,
+        url
+    )
+}
+
+/// Server running on a free local port, stopped when dropped
+pub struct TestServer {
+    pub runtime: Arc<Runtime>,
+    pub addr: String,
+    config: ServerConfig,
+    handle: ServerHandle,
+    /// Certificates the clients authenticate with, `None` for a plaintext server
+    pki: Option<Pki>,
+    client_ca_file: Option<PathBuf>,
+}
+
+impl TestServer {
+    pub fn start() -> Self {
+        Self::start_with(|_| {})
+    }
+
+    /// Starts a server after letting `configure` adjust its config
+    pub fn start_with(configure: impl FnOnce(&mut ServerConfig)) -> Self {
+        let runtime = Arc::new(Builder::new_multi_thread().worker_threads(2).enable_all().build().unwrap());
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let mut config = ServerConfig {
+            listeners: vec![ListenerConfig {
+                name: "test".to_owned(),
+                address: format!("127.0.0.1:{}", port),
+                allowed_rpcs: Vec::new(),
+            }],
+            ..Default::default()
+        };
+        configure(&mut config);
+
+        let handle = ServerHandle::start(&config, runtime.clone()).unwrap();
+
+        Self {
+            runtime,
+            addr: format!("http://127.0.0.1:{}", port),
+            config,
+            handle,
+            pki: None,
+            client_ca_file: None,
+        }
+    }
+
+    pub fn start_authenticated() -> Self {
+        Self::start_authenticated_with(|_| {})
+    }
+
+    /// Starts a server requiring client certificates, [`TestServer::client`] then presents one
+    pub fn start_authenticated_with(configure: impl FnOnce(&mut ServerConfig)) -> Self {
+        let pki = pki::pki();
+        static SERVERS: AtomicUsize = AtomicUsize::new(0);
+        let client_ca_file = std::env::temp_dir().join(format!(
+            "actflow-server-client-ca-{}-{}.pem",
+            std::process::id(),
+            SERVERS.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&client_ca_file, &pki.ca).unwrap();
+        let mut server = Self::start_with(|config| {
+            config.tls = Some(TlsConfig {
+                cert_pem: pki.server_cert.clone(),
+                key_pem: pki.server_key.clone(),
+                client_ca_file: Some(client_ca_file.to_string_lossy().into_owned()),
+                ..Default::default()
+            });
+            configure(config);
+        });
+        server.addr = server.addr.replace("http://", "https://");
+        server.pki = Some(pki);
+        server.client_ca_file = Some(client_ca_file);
+        server
+    }
+
+    /// Starts an HTTP server that accepts connections but never answers, returns its URL
+    pub fn hanging_http_url(&self) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        listener.set_nonblocking(true).unwrap();
+        self.runtime.spawn(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            let mut connections = Vec::new();
+            while let Ok((connection, _)) = listener.accept().await {
+                connections.push(connection);
+            }
+        });
+        format!("http://{}/", addr)
+    }
+
+    /// Re-applies the reloadable limits after letting `configure` adjust the config, like a SIGHUP would
+    pub fn reload_with(
+        &mut self,
+        configure: impl FnOnce(&mut ServerConfig),
+    ) {
+        configure(&mut self.config);
+        self.handle.limits_reloader().reload(&self.config);
+    }
+
+    /// Stops the server like an embedding program would
+    pub fn shutdown(&self) {
+        self.handle.shutdown();
+    }
+
+    /// Waits until the server stopped, returning why it failed
+    pub async fn wait(&mut self) -> Result<(), ServerError> {
+        self.handle.wait().await
+    }
+
+    /// Connects a client, retrying until the listener is up
+    pub async fn client(&self) -> WorkflowServiceClient<Channel> {
+        let mut endpoint = Channel::from_shared(self.addr.clone()).unwrap();
+        if let Some(pki) = &self.pki {
+            let tls = ClientTlsConfig::new()
+                .ca_certificate(Certificate::from_pem(&pki.ca))
+                .identity(Identity::from_pem(&pki.client_cert, &pki.client_key))
+                .domain_name("localhost");
+            endpoint = endpoint.tls_config(tls).unwrap();
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            match endpoint.connect().await {
+                Ok(channel) => return WorkflowServiceClient::new(channel),
+                Err(e) if Instant::now() > deadline => panic!("server did not come up: {}", e),
+                Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+            }
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.handle.shutdown();
+        if let Some(client_ca_file) = &self.client_ca_file {
+            let _ = fs::remove_file(client_ca_file);
+        }
+    }
+}
+
+/// Runs a workflow and collects its events until the stream closes
+pub async fn run_to_end(
+    client: &mut WorkflowServiceClient<Channel>,
+    workflow_model: &str,
+) -> Vec<ProtoEvent> {
+    let mut stream = client
+        .run_workflow(RunWorkflowRequest {
+            workflow_model: workflow_model.to_owned(),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let mut events = Vec::new();
+    while let Some(WorkflowEvent {
+        event: Some(event),
+        ..
+    }) = tokio::time::timeout(Duration::from_secs(10), stream.message()).await.unwrap().unwrap()
+    {
+        events.push(event);
+    }
+    events
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use actflow_server::proto::{
+    RunWorkflowRequest, workflow_event::Event as ProtoEvent, workflow_service_client::WorkflowServiceClient,
+};
+use common::{SIMPLE_WORKFLOW, TestServer};
+use tonic::{codec::CompressionEncoding, transport::Channel};
+
+/// Runs the simple workflow to its end, returning the encoding of the response and the events
+async fn run(client: &mut WorkflowServiceClient<Channel>) -> (Option<String>, Vec<ProtoEvent>) {
+    let response = client
+        .run_workflow(RunWorkflowRequest {
+            workflow_model: SIMPLE_WORKFLOW.to_owned(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let encoding = response.metadata().get("grpc-encoding").map(|value| value.to_str().unwrap().to_owned());
+    let mut stream = response.into_inner();
+    let mut events = Vec::new();
+    while let Some(event) = stream.message().await.unwrap() {
+        events.extend(event.event);
+    }
+    (encoding, events)
+}
+
+#[test]
+fn gzip_client_runs_a_workflow() {
+    let server = TestServer::start_with(|config| config.compression = true);
+    server.runtime.block_on(async {
+        let mut client =
+            server.client().await.send_compressed(CompressionEncoding::Gzip).accept_compressed(CompressionEncoding::Gzip);
+        let (encoding, events) = run(&mut client).await;
+        assert_eq!(encoding.as_deref(), Some("gzip"));
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))), "{:?}", events);
+    });
+}
+
+#[test]
+fn client_without_compression_still_runs_a_workflow() {
+    let server = TestServer::start_with(|config| config.compression = true);
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let (encoding, events) = run(&mut client).await;
+        assert_eq!(encoding, None);
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))), "{:?}", events);
+    });
+}
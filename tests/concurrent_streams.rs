@@ -0,0 +1,49 @@
+mod common;
+
+use std::time::Duration;
+
+use actflow_server::proto::{RunWorkflowRequest, StopWorkflowRequest, workflow_event::Event as ProtoEvent};
+use common::{SIMPLE_WORKFLOW, TestServer, blocking_workflow, run_to_end};
+
+#[test]
+fn second_stream_on_a_connection_waits_for_the_first() {
+    let server = TestServer::start_with(|config| config.max_concurrent_streams = Some(1));
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let Some(ProtoEvent::WorkflowStart(start)) = stream.message().await.unwrap().unwrap().event else {
+            panic!("expected a workflow start event");
+        };
+
+        // the clone shares the connection, whose only stream is taken by the blocked run
+        let mut second = client.clone();
+        let mut queued = tokio::spawn(async move { run_to_end(&mut second, SIMPLE_WORKFLOW).await });
+        assert!(
+            tokio::time::timeout(Duration::from_millis(500), &mut queued).await.is_err(),
+            "a second stream ran next to the first"
+        );
+
+        // stopped over another connection, which has a stream of its own
+        server
+            .client()
+            .await
+            .stop_workflow(StopWorkflowRequest {
+                pid: start.pid,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        while stream.message().await.unwrap().is_some() {}
+
+        let events = tokio::time::timeout(Duration::from_secs(5), queued).await.unwrap().unwrap();
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))), "{:?}", events);
+    });
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use actflow_server::proto::{CancelAllRequest, RunWorkflowRequest, workflow_event::Event as ProtoEvent};
+use common::{TestServer, blocking_workflow};
+use tokio::{io::AsyncReadExt, net::TcpStream};
+
+#[test]
+fn connection_without_requests_is_closed_after_the_idle_timeout() {
+    let server = TestServer::start_with(|config| config.connection_idle_timeout_secs = Some(1));
+    server.runtime.block_on(async {
+        // waits for the listener to come up
+        server.client().await;
+        let mut conn = TcpStream::connect(server.addr.trim_start_matches("http://")).await.unwrap();
+        let started = Instant::now();
+        let mut buf = [0; 1024];
+        loop {
+            match tokio::time::timeout(Duration::from_secs(5), conn.read(&mut buf)).await.unwrap() {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+        assert!(started.elapsed() >= Duration::from_millis(900), "{:?}", started.elapsed());
+    });
+}
+
+#[test]
+fn open_workflow_stream_keeps_its_connection() {
+    let server = TestServer::start_authenticated_with(|config| config.connection_idle_timeout_secs = Some(1));
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        stream.message().await.unwrap().unwrap();
+
+        // long past the idle timeout without an event
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        client
+            .cancel_all(CancelAllRequest {
+                reason: "test done".to_owned(),
+            })
+            .await
+            .unwrap();
+        let mut last = None;
+        while let Some(event) = stream.message().await.unwrap() {
+            last = event.event;
+        }
+        assert!(matches!(last, Some(ProtoEvent::WorkflowAbort(_))), "{:?}", last);
+    });
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use std::time::Duration;
+
+use actflow_server::proto::{ListWorkflowsRequest, RunWorkflowRequest, workflow_event::Event as ProtoEvent};
+use common::{SIMPLE_WORKFLOW, TestServer, blocking_workflow, run_to_end};
+use tonic::{Code, Request};
+
+fn request_with_timeout(
+    workflow_model: String,
+    timeout: Duration,
+) -> Request<RunWorkflowRequest> {
+    let mut request = Request::new(RunWorkflowRequest {
+        workflow_model,
+        ..Default::default()
+    });
+    request.set_timeout(timeout);
+    request
+}
+
+#[test]
+fn run_past_the_client_deadline_is_aborted() {
+    let server = TestServer::start();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client.run_workflow(request_with_timeout(model, Duration::from_secs(1))).await.unwrap().into_inner();
+
+        let status = loop {
+            match stream.message().await {
+                Ok(Some(_)) => {}
+                Ok(None) => panic!("the stream ended without an error"),
+                Err(status) => break status,
+            }
+        };
+        assert_eq!(status.code(), Code::DeadlineExceeded);
+        assert!(
+            status.message().contains("did not finish before the deadline"),
+            "{}",
+            status.message()
+        );
+
+        // the process is stopped as well
+        for _ in 0..100 {
+            let running = client.list_workflows(ListWorkflowsRequest::default()).await.unwrap().into_inner().workflows;
+            if running.is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("the workflow process is still running");
+    });
+}
+
+#[test]
+fn run_within_the_client_deadline_is_unaffected() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(request_with_timeout(SIMPLE_WORKFLOW.to_owned(), Duration::from_secs(30)))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let mut last = None;
+        while let Some(event) = stream.message().await.unwrap() {
+            last = event.event;
+        }
+        assert!(matches!(last, Some(ProtoEvent::WorkflowSuccess(_))));
+    });
+}
+
+#[test]
+fn server_max_runtime_caps_a_longer_client_deadline() {
+    let server = TestServer::start_with(|config| config.max_workflow_runtime_secs = Some(1));
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client.run_workflow(request_with_timeout(model, Duration::from_secs(60))).await.unwrap().into_inner();
+
+        let mut last = None;
+        while let Some(event) = stream.message().await.unwrap() {
+            last = event.event;
+        }
+        match last {
+            Some(ProtoEvent::WorkflowAbort(abort)) => assert_eq!(abort.reason, "server max runtime exceeded"),
+            event => panic!("expected a workflow abort, got {:?}", event),
+        }
+    });
+}
+
+#[test]
+fn finished_runs_leave_no_watcher_tasks_behind() {
+    let server = TestServer::start_with(|config| config.max_workflow_runtime_secs = Some(3600));
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        // the first run starts the tasks every later run shares
+        run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let before = server.runtime.metrics().num_alive_tasks();
+
+        for _ in 0..20 {
+            let request = request_with_timeout(SIMPLE_WORKFLOW.to_owned(), Duration::from_secs(3600));
+            let mut stream = client.run_workflow(request).await.unwrap().into_inner();
+            while stream.message().await.unwrap().is_some() {}
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let after = server.runtime.metrics().num_alive_tasks();
+        assert!(after < before + 20, "{} tasks before the runs, {} after", before, after);
+    });
+}
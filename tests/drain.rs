@@ -0,0 +1,109 @@
+mod common;
+
+use actflow_server::proto::{DrainRequest, RunWorkflowRequest, StopWorkflowRequest, workflow_event::Event as ProtoEvent};
+use common::{SIMPLE_WORKFLOW, TestServer, blocking_workflow, run_to_end};
+use tonic::Code;
+
+#[test]
+fn drain_of_an_idle_server_ends_right_away() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+
+        let mut progress = client
+            .drain(DrainRequest {
+                timeout_secs: 10,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let last = progress.message().await.unwrap().unwrap();
+        assert!(last.done);
+        assert_eq!(last.remaining, 0);
+        assert!(progress.message().await.unwrap().is_none());
+
+        let status = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: SIMPLE_WORKFLOW.to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable);
+    });
+}
+
+#[test]
+fn drain_times_out_with_the_remaining_workflows() {
+    let server = TestServer::start();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        stream.message().await.unwrap().unwrap();
+
+        let mut progress = client
+            .drain(DrainRequest {
+                timeout_secs: 1,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let mut messages = Vec::new();
+        while let Some(message) = progress.message().await.unwrap() {
+            messages.push(message);
+        }
+        assert_eq!(messages.first().map(|m| (m.remaining, m.done)), Some((1, false)));
+        assert_eq!(messages.last().map(|m| (m.remaining, m.done)), Some((1, true)));
+    });
+}
+
+#[test]
+fn drain_ends_once_the_running_workflows_finished() {
+    let server = TestServer::start();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let Some(ProtoEvent::WorkflowStart(start)) = stream.message().await.unwrap().unwrap().event else {
+            panic!("expected a workflow start event");
+        };
+
+        let mut progress = client
+            .drain(DrainRequest {
+                timeout_secs: 0,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let first = progress.message().await.unwrap().unwrap();
+        assert_eq!(first.remaining, 1);
+        assert!(!first.done);
+
+        client
+            .stop_workflow(StopWorkflowRequest {
+                pid: start.pid,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let last = progress.message().await.unwrap().unwrap();
+        assert_eq!(last.remaining, 0);
+        assert!(last.done);
+    });
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use actflow_server::proto::{CancelAllRequest, RunWorkflowRequest};
+use common::{TestServer, blocking_workflow};
+use tonic::Code;
+
+#[test]
+fn duplicate_wid_is_rejected_while_running() {
+    let server = TestServer::start_authenticated_with(|config| config.reject_duplicate_wid = true);
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+
+        let request = RunWorkflowRequest {
+            workflow_model: model.clone(),
+            ..Default::default()
+        };
+        let mut first = client.run_workflow(request.clone()).await.unwrap().into_inner();
+        first.message().await.unwrap().unwrap();
+
+        let err = client.run_workflow(request).await.unwrap_err();
+        assert_eq!(err.code(), Code::AlreadyExists);
+
+        client
+            .cancel_all(CancelAllRequest {
+                reason: "test done".to_owned(),
+            })
+            .await
+            .unwrap();
+    });
+}
+
+#[test]
+fn duplicate_wid_runs_by_default() {
+    let server = TestServer::start_authenticated();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+
+        let request = RunWorkflowRequest {
+            workflow_model: model,
+            ..Default::default()
+        };
+        let mut first = client.run_workflow(request.clone()).await.unwrap().into_inner();
+        first.message().await.unwrap().unwrap();
+        let mut second = client.run_workflow(request).await.unwrap().into_inner();
+        second.message().await.unwrap().unwrap();
+
+        let stopped = client
+            .cancel_all(CancelAllRequest {
+                reason: "test done".to_owned(),
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .stopped;
+        assert_eq!(stopped, 2);
+    });
+}
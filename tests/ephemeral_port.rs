@@ -0,0 +1,32 @@
+mod common;
+
+use std::sync::Arc;
+
+use actflow_server::{
+    config::ServerConfig,
+    proto::{workflow_event::Event as ProtoEvent, workflow_service_client::WorkflowServiceClient},
+    server::ServerHandle,
+};
+use common::{SIMPLE_WORKFLOW, run_to_end};
+use tokio::runtime::Builder;
+
+#[test]
+fn port_zero_binds_a_port_picked_by_the_os() {
+    let runtime = Arc::new(Builder::new_multi_thread().worker_threads(2).enable_all().build().unwrap());
+    let config = ServerConfig {
+        port: 0,
+        ..Default::default()
+    };
+    let handle = ServerHandle::start(&config, runtime.clone()).unwrap();
+
+    runtime.block_on(async {
+        let addrs = handle.local_addrs().await.unwrap();
+        assert_eq!(addrs.len(), 1);
+        assert_ne!(addrs[0].port(), 0);
+
+        let mut client = WorkflowServiceClient::connect(format!("http://127.0.0.1:{}", addrs[0].port())).await.unwrap();
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+    });
+    handle.shutdown();
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use actflow_server::proto::{EventCategory, RunWorkflowRequest, workflow_event::Event as ProtoEvent};
+use common::{SIMPLE_WORKFLOW, TestServer};
+use tonic::Code;
+
+#[test]
+fn event_mask_only_streams_the_picked_categories_and_the_terminal_event() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: SIMPLE_WORKFLOW.to_owned(),
+                event_mask: vec![EventCategory::Progress as i32],
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let mut events = Vec::new();
+        while let Some(event) = stream.message().await.unwrap() {
+            events.push(event.event.unwrap());
+        }
+
+        assert_eq!(events.len(), 3, "{:?}", events);
+        assert!(events[..2].iter().all(|event| matches!(event, ProtoEvent::WorkflowProgress(_))));
+        assert!(matches!(events[2], ProtoEvent::WorkflowSuccess(_)));
+    });
+}
+
+#[test]
+fn unknown_event_category_is_rejected() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        for value in [EventCategory::Unspecified as i32, 42] {
+            let status = client
+                .run_workflow(RunWorkflowRequest {
+                    workflow_model: SIMPLE_WORKFLOW.to_owned(),
+                    event_mask: vec![value],
+                    ..Default::default()
+                })
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), Code::InvalidArgument);
+        }
+    });
+}
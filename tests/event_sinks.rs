@@ -0,0 +1,50 @@
+mod common;
+
+use actflow_server::{
+    config::EventSinkConfig,
+    proto::{RunWorkflowRequest, workflow_event::Event as ProtoEvent},
+};
+use common::{SIMPLE_WORKFLOW, TestServer};
+
+#[test]
+fn file_sink_gets_every_event_regardless_of_the_node_filter() {
+    let path = std::env::temp_dir().join(format!("actflow-event-sink-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let server = TestServer::start_with(|config| {
+        config.event_sinks = vec![EventSinkConfig::File {
+            path: path.display().to_string(),
+        }]
+    });
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: SIMPLE_WORKFLOW.to_owned(),
+                node_filter: vec!["none".to_owned()],
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let mut streamed = Vec::new();
+        while let Some(event) = stream.message().await.unwrap() {
+            streamed.push(event.event.unwrap());
+        }
+        assert!(!streamed.iter().any(|event| matches!(event, ProtoEvent::NodeSuccess(_))));
+        assert!(matches!(streamed.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+    });
+
+    let lines: Vec<serde_json::Value> =
+        std::fs::read_to_string(&path).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    let kinds: Vec<&str> = lines
+        .iter()
+        .filter_map(|line| line["event"].as_object())
+        .filter_map(|event| event.keys().next().map(String::as_str))
+        .collect();
+    assert_eq!(kinds.first(), Some(&"workflow_start"));
+    assert_eq!(kinds.last(), Some(&"workflow_success"));
+    assert_eq!(kinds.iter().filter(|kind| **kind == "node_success").count(), 2);
+    assert_eq!(lines[1]["event"]["node_running"]["nid"], "n1");
+
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use actflow_server::proto::{
+    self, Empty, RunWorkflowRequest, WorkflowEvent, workflow_event::Event as ProtoEvent,
+    workflow_service_client::WorkflowServiceClient,
+};
+use common::{SIMPLE_WORKFLOW, TestServer};
+use http::{Method, Request, Uri};
+use hyper_util::{client::legacy::Client, rt::TokioExecutor};
+use tonic::body::Body;
+use tonic_web::{GrpcWebCall, GrpcWebClientLayer};
+use tower::ServiceBuilder;
+
+const ORIGIN: &str = "https://dashboard.example.com";
+
+fn grpc_web_server() -> TestServer {
+    TestServer::start_with(|config| {
+        config.grpc_web = true;
+        config.cors_allowed_origins = vec![ORIGIN.to_owned()];
+    })
+}
+
+#[test]
+fn run_workflow_streams_over_grpc_web() {
+    let server = grpc_web_server();
+    server.runtime.block_on(async {
+        // wait for the listener with a native client, which must keep working as well
+        server.client().await.get_version(Empty {}).await.unwrap();
+
+        let http = Client::builder(TokioExecutor::new()).build_http::<GrpcWebCall<Body>>();
+        let service = ServiceBuilder::new().layer(GrpcWebClientLayer::new()).service(http);
+        let mut client = WorkflowServiceClient::with_origin(service, server.addr.parse::<Uri>().unwrap());
+
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: SIMPLE_WORKFLOW.to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let mut events = Vec::new();
+        while let Some(WorkflowEvent {
+            event: Some(event),
+            ..
+        }) = stream.message().await.unwrap()
+        {
+            events.push(event);
+        }
+
+        assert!(matches!(events.first(), Some(ProtoEvent::WorkflowStart(_))));
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+    });
+}
+
+#[test]
+fn preflight_allows_only_configured_origins() {
+    let server = grpc_web_server();
+    server.runtime.block_on(async {
+        server.client().await;
+        let http = Client::builder(TokioExecutor::new()).build_http::<Body>();
+
+        let preflight = |origin: &str| {
+            Request::builder()
+                .method(Method::OPTIONS)
+                .uri(format!("{}/{}.WorkflowService/RunWorkflow", server.addr, proto::PACKAGE))
+                .header("origin", origin)
+                .header("access-control-request-method", "POST")
+                .header("access-control-request-headers", "content-type,x-grpc-web")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let allowed = http.request(preflight(ORIGIN)).await.unwrap();
+        assert_eq!(allowed.headers()["access-control-allow-origin"], ORIGIN);
+
+        let denied = http.request(preflight("https://evil.example.com")).await.unwrap();
+        assert!(!denied.headers().contains_key("access-control-allow-origin"));
+    });
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use actflow_server::proto;
+use common::TestServer;
+use tonic::transport::Channel;
+use tonic_health::{
+    ServingStatus,
+    pb::{HealthCheckRequest, health_client::HealthClient},
+};
+
+/// Waits until the health service reports the server as serving
+async fn wait_serving(server: &TestServer) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        if let Ok(channel) = Channel::from_shared(server.addr.clone()).unwrap().connect().await {
+            let status = HealthClient::new(channel)
+                .check(HealthCheckRequest {
+                    service: format!("{}.WorkflowService", proto::PACKAGE),
+                })
+                .await
+                .map(|res| res.into_inner().status);
+            if status.is_ok_and(|status| status == ServingStatus::Serving as i32) {
+                return;
+            }
+        }
+        assert!(Instant::now() < deadline, "server never reported serving");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[test]
+fn health_reports_serving_once_engine_is_ready() {
+    let server = TestServer::start();
+    server.runtime.block_on(wait_serving(&server));
+}
+
+#[test]
+fn health_is_served_on_filtered_listeners() {
+    let server = TestServer::start_with(|config| config.listeners[0].allowed_rpcs = vec!["GetVersion".to_owned()]);
+    server.runtime.block_on(wait_serving(&server));
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use actflow_server::proto::{
+    RunWorkflowRequest, WorkflowEvent, workflow_event::Event as ProtoEvent, workflow_service_client::WorkflowServiceClient,
+};
+use common::{SIMPLE_WORKFLOW, TestServer, blocking_workflow, run_to_end};
+use tonic::{Streaming, transport::Channel};
+
+async fn last_event(mut stream: Streaming<WorkflowEvent>) -> ProtoEvent {
+    let mut last = None;
+    while let Some(event) = stream.message().await.unwrap() {
+        last = event.event;
+    }
+    last.expect("the stream ended without events")
+}
+
+async fn run(
+    client: &mut WorkflowServiceClient<Channel>,
+    model: String,
+    idle_timeout_secs: u64,
+) -> ProtoEvent {
+    let stream = client
+        .run_workflow(RunWorkflowRequest {
+            workflow_model: model,
+            idle_timeout_secs,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    last_event(stream).await
+}
+
+#[test]
+fn idle_workflow_is_aborted() {
+    let server = TestServer::start_with(|config| config.workflow_idle_timeout_secs = Some(1));
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        match run(&mut client, model, 0).await {
+            ProtoEvent::WorkflowAbort(abort) => assert_eq!(abort.reason, "idle timeout"),
+            event => panic!("expected a workflow abort, got {:?}", event),
+        }
+    });
+}
+
+#[test]
+fn request_overrides_idle_timeout() {
+    let server = TestServer::start_with(|config| config.workflow_idle_timeout_secs = Some(3600));
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        match run(&mut client, model, 1).await {
+            ProtoEvent::WorkflowAbort(abort) => assert_eq!(abort.reason, "idle timeout"),
+            event => panic!("expected a workflow abort, got {:?}", event),
+        }
+    });
+}
+
+#[test]
+fn busy_workflow_is_not_aborted() {
+    let server = TestServer::start_with(|config| config.workflow_idle_timeout_secs = Some(1));
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        assert!(
+            matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))),
+            "unexpected events: {:?}",
+            events
+        );
+    });
+}
@@ -0,0 +1,46 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use actflow_server::{
+    common::shutdown::ShutdownReason,
+    config::ServerConfig,
+    server::{LifecycleHooks, ServerHandle},
+};
+use tokio::runtime::Builder;
+
+#[test]
+fn hooks_run_in_lifecycle_order() {
+    let runtime = Arc::new(Builder::new_multi_thread().worker_threads(2).enable_all().build().unwrap());
+    let config = ServerConfig {
+        port: 0,
+        ..Default::default()
+    };
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let hooks = {
+        let (ready, start, complete) = (calls.clone(), calls.clone(), calls.clone());
+        LifecycleHooks::default()
+            .on_ready(move || ready.lock().unwrap().push("ready".to_owned()))
+            .on_shutdown_start(move |reason| start.lock().unwrap().push(format!("shutdown start: {}", reason)))
+            .on_shutdown_complete(move || complete.lock().unwrap().push("shutdown complete".to_owned()))
+    };
+    let mut handle = ServerHandle::start_with_hooks(&config, runtime.clone(), hooks).unwrap();
+
+    runtime.block_on(async {
+        for _ in 0..500 {
+            if !calls.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(*calls.lock().unwrap(), ["ready"]);
+
+        handle.shutdown_with_reason(ShutdownReason::Sigterm);
+        handle.wait().await.unwrap();
+    });
+    assert_eq!(
+        *calls.lock().unwrap(),
+        ["ready", "shutdown start: SIGTERM", "shutdown complete"]
+    );
+}
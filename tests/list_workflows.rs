@@ -0,0 +1,66 @@
+mod common;
+
+use std::collections::HashMap;
+
+use actflow_server::proto::{CancelAllRequest, ListWorkflowsRequest, RunWorkflowRequest, workflow_event::Event as ProtoEvent};
+use common::{TestServer, blocking_workflow};
+
+fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+#[test]
+fn list_workflows_filters_by_labels() {
+    let server = TestServer::start_authenticated();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+
+        let mut streams = Vec::new();
+        for team in ["billing", "search"] {
+            let mut stream = client
+                .run_workflow(RunWorkflowRequest {
+                    workflow_model: model.clone(),
+                    labels: labels(&[("team", team), ("env", "test")]),
+                    ..Default::default()
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            let first = stream.message().await.unwrap().unwrap();
+            assert!(matches!(first.event, Some(ProtoEvent::WorkflowStart(_))));
+            streams.push(stream);
+        }
+
+        let list = |selector: HashMap<String, String>| {
+            let mut client = client.clone();
+            async move {
+                client
+                    .list_workflows(ListWorkflowsRequest {
+                        label_selector: selector,
+                    })
+                    .await
+                    .unwrap()
+                    .into_inner()
+                    .workflows
+            }
+        };
+
+        assert_eq!(list(HashMap::new()).await.len(), 2);
+        assert_eq!(list(labels(&[("env", "test")])).await.len(), 2);
+
+        let billing = list(labels(&[("team", "billing")])).await;
+        assert_eq!(billing.len(), 1);
+        assert_eq!(billing[0].wid, "blocking");
+        assert_eq!(billing[0].labels["team"], "billing");
+
+        assert!(list(labels(&[("team", "billing"), ("env", "prod")])).await.is_empty());
+
+        client
+            .cancel_all(CancelAllRequest {
+                reason: "test done".to_owned(),
+            })
+            .await
+            .unwrap();
+    });
+}
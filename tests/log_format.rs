@@ -0,0 +1,36 @@
+use std::fs;
+
+use actflow_server::{
+    config::{LogColor, LogConfig, LogTimezone},
+    logger::init_logger,
+};
+
+#[test]
+fn log_lines_are_stamped_in_the_configured_format_and_timezone_and_tagged_with_the_instance() {
+    let dir = std::env::temp_dir().join(format!("actflow-log-format-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let log_config = LogConfig {
+        log_file: dir.join("server.log").display().to_string(),
+        create_symlink: false,
+        log_to_stderr: false,
+        timestamp_format: "%Y|%Z".to_owned(),
+        timezone: LogTimezone::Utc,
+        color: LogColor::Never,
+        instance_id: Some("instance-7".to_owned()),
+        ..Default::default()
+    };
+    let handle = init_logger(&log_config).unwrap().start().unwrap();
+    log::warn!("stamped line");
+    handle.flush();
+
+    let contents: String = fs::read_dir(&dir).unwrap().map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap()).collect();
+    let line = contents.lines().find(|line| line.contains("stamped line")).unwrap();
+    let year = chrono::Utc::now().format("%Y").to_string();
+    assert!(line.contains(&format!("{}|UTC", year)), "{}", line);
+    assert!(line.contains("[instance-7] "), "{}", line);
+    assert!(!line.contains('\x1b'), "{}", line);
+
+    handle.shutdown();
+    let _ = fs::remove_dir_all(&dir);
+}
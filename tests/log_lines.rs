@@ -0,0 +1,92 @@
+mod common;
+
+use actflow_server::proto::workflow_event::Event as ProtoEvent;
+use common::{
+    TestServer,
+    agent::{FakeAgent, agent_workflow},
+    run_to_end,
+};
+use serde_json::json;
+
+fn node_logs(events: &[ProtoEvent]) -> Vec<String> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            ProtoEvent::NodeLog(log) => Some(log.content.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn long_log_lines_are_truncated() {
+    let server = TestServer::start_with(|config| config.max_log_line_bytes = Some(8));
+    server.runtime.block_on(async {
+        let endpoint = FakeAgent::new(vec!["short".to_owned(), "0123456789abcdef".to_owned(), "ééééé".to_owned()]).serve().await;
+        let mut client = server.client().await;
+        let events = run_to_end(&mut client, &agent_workflow(&endpoint)).await;
+
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+        assert_eq!(
+            node_logs(&events),
+            ["short", "01234567…(truncated 8 bytes)", "éééé…(truncated 2 bytes)"]
+        );
+    });
+}
+
+#[test]
+fn truncated_json_log_line_keeps_its_fields() {
+    let server = TestServer::start_with(|config| config.max_log_line_bytes = Some(16));
+    server.runtime.block_on(async {
+        let payload = "x".repeat(100);
+        let line = json!({"level": "warn", "payload": payload}).to_string();
+        let endpoint = FakeAgent::new(vec![line]).serve().await;
+        let mut client = server.client().await;
+        let events = run_to_end(&mut client, &agent_workflow(&endpoint)).await;
+
+        let log = events
+            .iter()
+            .find_map(|event| match event {
+                ProtoEvent::NodeLog(log) => Some(log),
+                _ => None,
+            })
+            .expect("a node log event");
+        assert!(log.content.contains("…(truncated"), "{}", log.content);
+        assert_eq!(log.fields.get("level").map(String::as_str), Some("warn"));
+        assert_eq!(log.fields.get("payload"), Some(&payload));
+    });
+}
+
+#[test]
+fn log_lines_are_unbounded_by_default() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let line = "x".repeat(100_000);
+        let endpoint = FakeAgent::new(vec![line.clone()]).serve().await;
+        let mut client = server.client().await;
+        let events = run_to_end(&mut client, &agent_workflow(&endpoint)).await;
+
+        assert_eq!(node_logs(&events), [line]);
+    });
+}
+
+#[test]
+fn terminal_event_reports_usage() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let endpoint = FakeAgent::new(vec!["one".to_owned(), "two".to_owned(), "three".to_owned()]).serve().await;
+        let mut client = server.client().await;
+        let events = run_to_end(&mut client, &agent_workflow(&endpoint)).await;
+
+        let Some(ProtoEvent::WorkflowSuccess(success)) = events.last() else {
+            panic!("expected a workflow success event, got {:?}", events);
+        };
+        // progress events come from the server rather than the engine
+        let engine_events =
+            events.iter().filter(|event| !matches!(event, ProtoEvent::NodeLog(_) | ProtoEvent::WorkflowProgress(_))).count();
+        let usage = success.usage.unwrap();
+        assert_eq!(usage.events, engine_events as u64);
+        // logs arrive apart from the events, the last line may still be in flight at the end
+        assert!((2..=3).contains(&usage.log_lines), "{:?}", usage);
+    });
+}
@@ -0,0 +1,65 @@
+use std::fs;
+
+use actflow_server::{
+    config::LogConfig,
+    logger::{apply_log_retention, init_logger},
+};
+
+#[test]
+fn lowering_retention_deletes_excess_log_files() {
+    let dir = std::env::temp_dir().join(format!("actflow-log-retention-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let mut log_config = LogConfig {
+        log_file: dir.join("server.log").display().to_string(),
+        retention: 5,
+        create_symlink: false,
+        ..Default::default()
+    };
+    let handle = init_logger(&log_config).unwrap().start().unwrap();
+    // the writer only picks up the existing files once it wrote
+    log::warn!("logger started");
+
+    // files left behind by earlier rotations
+    for day in 1..=5 {
+        fs::write(dir.join(format!("server_r2024-01-0{}_00-00-00.log", day)), "old\n").unwrap();
+    }
+
+    log_config.retention = 2;
+    apply_log_retention(&handle, &log_config).unwrap();
+
+    let mut rotated: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with("server_r2024"))
+        .collect();
+    rotated.sort();
+    assert_eq!(rotated, ["server_r2024-01-04_00-00-00.log", "server_r2024-01-05_00-00-00.log"]);
+
+    handle.shutdown();
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn unwritable_log_dir_fails_when_file_logging_is_required() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("actflow-log-readonly-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o555)).unwrap();
+    let mut log_config = LogConfig {
+        log_file: dir.join("server.log").display().to_string(),
+        ..Default::default()
+    };
+
+    // falls back to stderr by default
+    assert!(init_logger(&log_config).is_ok());
+
+    log_config.require_file_logging = true;
+    let err = init_logger(&log_config).err().unwrap();
+    assert!(err.to_string().contains("not writable"), "{}", err);
+
+    let _ = fs::remove_dir_all(&dir);
+}
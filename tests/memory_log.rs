@@ -0,0 +1,43 @@
+use std::collections::BTreeMap;
+
+use actflow_server::{
+    config::{LogColor, LogConfig},
+    logger::{init_logger, take_memory_log},
+};
+
+#[test]
+fn memory_log_keeps_the_lines_passing_the_configured_levels() {
+    let log_config = LogConfig {
+        level: "info".to_owned(),
+        log_file: ":memory:".to_owned(),
+        log_to_stderr: false,
+        color: LogColor::Never,
+        module_levels: BTreeMap::from([("memory_log".to_owned(), "warn".to_owned())]),
+        instance_id: Some("instance-3".to_owned()),
+        ..Default::default()
+    };
+    let handle = init_logger(&log_config).unwrap().start().unwrap();
+
+    log::info!(target: "actflow_server::server", "kept info line");
+    log::debug!(target: "actflow_server::server", "filtered debug line");
+    // this test's own module is lowered to warn
+    log::info!("filtered module line");
+    log::warn!("kept module line");
+
+    let lines = take_memory_log();
+    assert_eq!(lines.len(), 2, "{:?}", lines);
+    assert!(
+        lines[0].contains(" INFO ") && lines[0].ends_with("kept info line"),
+        "{:?}",
+        lines
+    );
+    assert!(
+        lines[1].contains(" WARN ") && lines[1].ends_with("kept module line"),
+        "{:?}",
+        lines
+    );
+    assert!(lines.iter().all(|line| line.contains("[instance-3] ")), "{:?}", lines);
+    assert!(take_memory_log().is_empty());
+
+    handle.shutdown();
+}
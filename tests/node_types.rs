@@ -0,0 +1,51 @@
+mod common;
+
+use actflow_server::proto::{RunWorkflowRequest, workflow_event::Event as ProtoEvent};
+use common::{SIMPLE_WORKFLOW, TestServer, blocking_workflow, run_to_end};
+use tonic::Code;
+
+#[test]
+fn denied_node_type_is_rejected_naming_the_node() {
+    let server = TestServer::start_with(|config| config.denied_node_types = vec!["http_request".to_owned()]);
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let status = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert!(status.message().contains("n2"), "unexpected message: {}", status.message());
+        assert!(
+            status.message().contains("http_request"),
+            "unexpected message: {}",
+            status.message()
+        );
+
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+    });
+}
+
+#[test]
+fn only_allowed_node_types_run() {
+    let server = TestServer::start_with(|config| config.allowed_node_types = vec!["start".to_owned(), "end".to_owned()]);
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let status = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+    });
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use actflow_server::{
+    proto::RunWorkflowRequest,
+    server::{TRACE_ID_HEADER, TRACEPARENT_HEADER},
+};
+use common::{SIMPLE_WORKFLOW, TestServer};
+
+/// Runs the simple workflow to its end, returning the trace id header of the response
+async fn run_traced(
+    server: &TestServer,
+    traceparent: Option<&str>,
+) -> Option<String> {
+    let mut client = server.client().await;
+    let mut request = tonic::Request::new(RunWorkflowRequest {
+        workflow_model: SIMPLE_WORKFLOW.to_owned(),
+        ..Default::default()
+    });
+    if let Some(traceparent) = traceparent {
+        request.metadata_mut().insert(TRACEPARENT_HEADER, traceparent.parse().unwrap());
+    }
+    let response = client.run_workflow(request).await.unwrap();
+    let trace_id = response.metadata().get(TRACE_ID_HEADER).map(|value| value.to_str().unwrap().to_owned());
+    let mut stream = response.into_inner();
+    while stream.message().await.unwrap().is_some() {}
+    trace_id
+}
+
+#[cfg(not(feature = "otel"))]
+#[test]
+fn runs_have_no_trace_id_without_the_otel_feature() {
+    let server = TestServer::start();
+    assert_eq!(server.runtime.block_on(run_traced(&server, None)), None);
+}
+
+#[cfg(feature = "otel")]
+mod exported {
+    use std::{
+        sync::OnceLock,
+        time::{Duration, Instant},
+    };
+
+    use actflow_server::{
+        proto::{RunWorkflowRequest, StopWorkflowRequest, WorkflowEvent, workflow_event::Event as ProtoEvent},
+        server::TRACE_ID_HEADER,
+    };
+    use opentelemetry::trace::{SpanId, Status};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+    use tonic::Streaming;
+
+    use super::{TestServer, run_traced};
+    use crate::common::blocking_workflow;
+
+    /// Records the spans of every run of this test binary in memory
+    fn exporter() -> &'static InMemorySpanExporter {
+        static EXPORTER: OnceLock<InMemorySpanExporter> = OnceLock::new();
+        EXPORTER.get_or_init(|| {
+            let exporter = InMemorySpanExporter::default();
+            let provider = SdkTracerProvider::builder().with_simple_exporter(exporter.clone()).build();
+            opentelemetry::global::set_tracer_provider(provider);
+            exporter
+        })
+    }
+
+    fn span_of(trace_id: &str) -> SpanData {
+        let spans = exporter().get_finished_spans().unwrap();
+        spans
+            .into_iter()
+            .find(|span| span.span_context.trace_id().to_string() == trace_id)
+            .unwrap_or_else(|| panic!("no span was exported for trace {}", trace_id))
+    }
+
+    /// Waits for the span of a run that may still be ending after its stream closed
+    async fn ended_span_of(trace_id: &str) -> SpanData {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let spans = exporter().get_finished_spans().unwrap();
+            if let Some(span) = spans.into_iter().find(|span| span.span_context.trace_id().to_string() == trace_id) {
+                return span;
+            }
+            assert!(Instant::now() < deadline, "no span was exported for trace {}", trace_id);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    /// Starts a run that blocks on `model`, returning its trace id, pid and stream
+    async fn start_blocking(
+        server: &TestServer,
+        model: String,
+    ) -> (String, String, Streaming<WorkflowEvent>) {
+        let mut client = server.client().await;
+        let response = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let trace_id = response.metadata().get(TRACE_ID_HEADER).unwrap().to_str().unwrap().to_owned();
+        let mut stream = response.into_inner();
+        let Some(ProtoEvent::WorkflowStart(start)) = stream.message().await.unwrap().unwrap().event else {
+            panic!("expected a workflow start event");
+        };
+        (trace_id, start.pid, stream)
+    }
+
+    async fn last_event(stream: &mut Streaming<WorkflowEvent>) -> Option<ProtoEvent> {
+        let mut last = None;
+        while let Some(event) = stream.message().await.unwrap() {
+            last = event.event;
+        }
+        last
+    }
+
+    #[test]
+    fn run_span_holds_the_events_of_the_run() {
+        exporter();
+        let server = TestServer::start();
+        let trace_id = server.runtime.block_on(run_traced(&server, None)).expect("a trace id header");
+        assert_eq!(trace_id.len(), 32, "{}", trace_id);
+
+        let span = span_of(&trace_id);
+        assert_eq!(span.name, "run_workflow");
+        assert_eq!(span.status, Status::Ok);
+        assert!(span.attributes.iter().any(|kv| kv.key.as_str() == "actflow.wid" && kv.value.as_str() == "simple"));
+        let events: Vec<_> = span.events.iter().map(|event| event.name.as_ref()).collect();
+        assert_eq!(events.first(), Some(&"workflow_start"), "{:?}", events);
+        assert!(events.contains(&"node_success"), "{:?}", events);
+        assert_eq!(events.last(), Some(&"workflow_success"), "{:?}", events);
+    }
+
+    #[test]
+    fn traceparent_continues_the_client_trace() {
+        exporter();
+        let server = TestServer::start();
+        let trace_id = server.runtime.block_on(run_traced(
+            &server,
+            Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        ));
+        assert_eq!(trace_id.as_deref(), Some("4bf92f3577b34da6a3ce929d0e0e4736"));
+
+        let span = span_of("4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(span.parent_span_id, SpanId::from_hex("00f067aa0ba902b7").unwrap());
+        assert!(span.parent_span_is_remote);
+    }
+
+    #[test]
+    fn stopped_run_span_ends_with_the_streamed_terminal_event() {
+        exporter();
+        let server = TestServer::start();
+        server.runtime.block_on(async {
+            let (trace_id, pid, mut stream) = start_blocking(&server, blocking_workflow(&server.hanging_http_url())).await;
+            server
+                .client()
+                .await
+                .stop_workflow(StopWorkflowRequest {
+                    pid,
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            let Some(ProtoEvent::WorkflowAbort(abort)) = last_event(&mut stream).await else {
+                panic!("expected the stream to end with a workflow abort");
+            };
+
+            let span = ended_span_of(&trace_id).await;
+            let last = span.events.iter().last().unwrap();
+            assert_eq!(last.name, "workflow_abort");
+            assert!(
+                last.attributes.iter().any(|kv| kv.key.as_str() == "actflow.abort_reason" && kv.value.as_str() == abort.reason),
+                "{:?}",
+                last.attributes
+            );
+        });
+    }
+
+    #[test]
+    fn run_cut_off_by_a_shutdown_still_ends_its_span() {
+        exporter();
+        let server = TestServer::start();
+        server.runtime.block_on(async {
+            // the request times out a second into the shutdown, failing the run
+            let model = blocking_workflow(&server.hanging_http_url()).replace("60000", "1000");
+            let (trace_id, _, mut stream) = start_blocking(&server, model).await;
+            server.shutdown();
+            assert!(matches!(last_event(&mut stream).await, Some(ProtoEvent::ServerShuttingDown(_))));
+
+            let span = ended_span_of(&trace_id).await;
+            let events: Vec<_> = span.events.iter().map(|event| event.name.as_ref()).collect();
+            assert_eq!(events.last(), Some(&"workflow_failure"), "{:?}", events);
+        });
+    }
+}
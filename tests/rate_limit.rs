@@ -0,0 +1,57 @@
+mod common;
+
+use actflow_server::proto::{Empty, StopWorkflowRequest};
+use common::TestServer;
+use tonic::Code;
+
+fn stop_unknown() -> StopWorkflowRequest {
+    StopWorkflowRequest {
+        pid: "unknown".to_owned(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn requests_beyond_the_rate_limit_are_rejected() {
+    let server = TestServer::start_with(|config| config.rate_limit_per_sec = Some(2));
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+
+        for _ in 0..2 {
+            let status = client.stop_workflow(stop_unknown()).await.unwrap_err();
+            assert_eq!(status.code(), Code::NotFound);
+        }
+        let status = client.stop_workflow(stop_unknown()).await.unwrap_err();
+        assert_eq!(status.code(), Code::ResourceExhausted);
+
+        // read-only RPCs are exempt by default
+        for _ in 0..5 {
+            client.get_server_stats(Empty {}).await.unwrap();
+        }
+
+        // the bucket refills over time
+        tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+        let status = client.stop_workflow(stop_unknown()).await.unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    });
+}
+
+#[test]
+fn exempt_rpcs_are_configurable() {
+    let server = TestServer::start_with(|config| {
+        config.rate_limit_per_sec = Some(1);
+        config.rate_limit_exempt_rpcs = vec!["StopWorkflow".to_owned()];
+    });
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+
+        client.get_version(Empty {}).await.unwrap();
+        let status = client.get_version(Empty {}).await.unwrap_err();
+        assert_eq!(status.code(), Code::ResourceExhausted);
+
+        for _ in 0..5 {
+            let status = client.stop_workflow(stop_unknown()).await.unwrap_err();
+            assert_eq!(status.code(), Code::NotFound);
+        }
+    });
+}
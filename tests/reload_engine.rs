@@ -0,0 +1,63 @@
+mod common;
+
+use actflow_server::proto::{ReloadEngineRequest, RunWorkflowRequest, SetAcceptingRequest, workflow_event::Event as ProtoEvent};
+use common::{SIMPLE_WORKFLOW, TestServer, blocking_workflow, run_to_end};
+use tonic::Code;
+
+#[test]
+fn workflows_run_on_the_reloaded_engine() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+
+        client
+            .reload_engine(ReloadEngineRequest {
+                drain_timeout_secs: 10,
+            })
+            .await
+            .unwrap();
+
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+        let was_accepting = client
+            .set_accepting(SetAcceptingRequest {
+                accepting: true,
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .was_accepting;
+        assert!(was_accepting);
+    });
+}
+
+#[test]
+fn reload_is_refused_while_workflows_keep_running() {
+    let server = TestServer::start();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        stream.message().await.unwrap().unwrap();
+
+        let status = client
+            .reload_engine(ReloadEngineRequest {
+                drain_timeout_secs: 1,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+
+        // the running workflow is untouched and new runs are accepted again
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+    });
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use std::time::Duration;
+
+use actflow_server::proto::{Empty, RunWorkflowRequest, StopWorkflowRequest, workflow_service_client::WorkflowServiceClient};
+use common::{TestServer, blocking_workflow};
+use tonic::{Code, transport::Channel};
+
+async fn wait_for_running(
+    client: &mut WorkflowServiceClient<Channel>,
+    running: u32,
+) {
+    for _ in 0..100 {
+        let stats = client.get_server_stats(Empty {}).await.unwrap().into_inner();
+        if stats.active_workflows == running && stats.queued_workflows == 0 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("expected {} running runs and none queued", running);
+}
+
+#[test]
+fn raised_concurrency_limit_starts_queued_runs() {
+    let mut server = TestServer::start_with(|config| config.max_concurrent_workflows = Some(1));
+    let model = blocking_workflow(&server.hanging_http_url());
+    let runtime = server.runtime.clone();
+    let mut client = runtime.block_on(server.client());
+    let request = RunWorkflowRequest {
+        workflow_model: model,
+        ..Default::default()
+    };
+    let _running = runtime.block_on(client.run_workflow(request.clone())).unwrap();
+    let queued = {
+        let mut client = client.clone();
+        let request = request.clone();
+        runtime.spawn(async move { client.run_workflow(request).await })
+    };
+    runtime.block_on(async {
+        for _ in 0..100 {
+            if client.get_server_stats(Empty {}).await.unwrap().into_inner().queued_workflows == 1 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("expected a queued run");
+    });
+
+    server.reload_with(|config| config.max_concurrent_workflows = Some(2));
+    runtime.block_on(async {
+        wait_for_running(&mut client, 2).await;
+        queued.await.unwrap().unwrap();
+    });
+}
+
+#[test]
+fn reloaded_rate_limit_applies_to_new_requests() {
+    let mut server = TestServer::start();
+    let runtime = server.runtime.clone();
+    let mut client = runtime.block_on(server.client());
+    let stop_unknown = StopWorkflowRequest {
+        pid: "unknown".to_owned(),
+        ..Default::default()
+    };
+    for _ in 0..5 {
+        let status = runtime.block_on(client.stop_workflow(stop_unknown.clone())).unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    }
+
+    server.reload_with(|config| config.rate_limit_per_sec = Some(1));
+    // the limiter is updated by a task, give it a moment
+    std::thread::sleep(Duration::from_millis(100));
+    runtime.block_on(async {
+        let status = client.stop_workflow(stop_unknown.clone()).await.unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+        let status = client.stop_workflow(stop_unknown).await.unwrap_err();
+        assert_eq!(status.code(), Code::ResourceExhausted);
+    });
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use actflow_server::proto::{ReplayWorkflowRequest, WorkflowEvent, workflow_event::Event as ProtoEvent};
+use common::{SIMPLE_WORKFLOW, TestServer, run_to_end};
+use tonic::Code;
+
+fn start_pid(events: &[ProtoEvent]) -> String {
+    match events.first() {
+        Some(ProtoEvent::WorkflowStart(start)) => start.pid.clone(),
+        other => panic!("expected a workflow start, got {:?}", other),
+    }
+}
+
+#[test]
+fn replay_runs_the_recorded_model_again() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let original = start_pid(&run_to_end(&mut client, SIMPLE_WORKFLOW).await);
+
+        let mut stream = client
+            .replay_workflow(ReplayWorkflowRequest {
+                original_pid: original.clone(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let mut events = Vec::new();
+        while let Some(WorkflowEvent {
+            event: Some(event),
+            ..
+        }) = stream.message().await.unwrap()
+        {
+            events.push(event);
+        }
+
+        assert_ne!(start_pid(&events), original);
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+    });
+}
+
+#[test]
+fn replay_of_unknown_pid_is_not_found() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let err = client
+            .replay_workflow(ReplayWorkflowRequest {
+                original_pid: "unknown".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+    });
+}
+
+#[test]
+fn replay_is_not_found_without_history() {
+    let server = TestServer::start_with(|config| config.history_size = 0);
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let original = start_pid(&run_to_end(&mut client, SIMPLE_WORKFLOW).await);
+
+        let err = client
+            .replay_workflow(ReplayWorkflowRequest {
+                original_pid: original,
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+    });
+}
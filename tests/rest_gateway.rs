@@ -0,0 +1,150 @@
+mod common;
+
+use std::{
+    net::TcpListener,
+    time::{Duration, Instant},
+};
+
+use actflow_server::config::{RestConfig, ServerConfig};
+use axum::body::{Body, Bytes};
+use common::{SIMPLE_WORKFLOW, TestServer, blocking_workflow};
+use http::{Method, Request, Response, StatusCode, header};
+use http_body_util::{BodyExt, Full};
+use hyper_util::{
+    client::legacy::{Client, connect::HttpConnector},
+    rt::TokioExecutor,
+};
+use serde_json::{Value, json};
+
+/// Starts a server with the REST gateway on a free local port, returning the gateway URL
+fn rest_server() -> (TestServer, String) {
+    rest_server_with(|_| {})
+}
+
+fn rest_server_with(configure: impl FnOnce(&mut ServerConfig)) -> (TestServer, String) {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let server = TestServer::start_with(|config| {
+        config.rest = Some(RestConfig {
+            address: format!("127.0.0.1:{}", port),
+        });
+        configure(config);
+    });
+    (server, format!("http://127.0.0.1:{}", port))
+}
+
+/// Posts `body` to the gateway, retrying until it is up
+async fn post(
+    url: &str,
+    body: Value,
+) -> Response<Body> {
+    let http: Client<HttpConnector, Full<Bytes>> = Client::builder(TokioExecutor::new()).build_http();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Full::from(body.to_string()))
+            .unwrap();
+        match http.request(request).await {
+            Ok(response) => return response.map(Body::new),
+            Err(e) if Instant::now() > deadline => panic!("REST gateway did not come up: {}", e),
+            Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    }
+}
+
+/// Reads server-sent events until the one matching `until`, or until the stream ends
+async fn read_events(
+    response: &mut Response<Body>,
+    until: impl Fn(&Value) -> bool,
+) -> Vec<Value> {
+    let mut events = Vec::new();
+    let mut buffered = String::new();
+    while let Some(frame) = response.body_mut().frame().await {
+        let Ok(data) = frame.unwrap().into_data() else {
+            continue;
+        };
+        buffered.push_str(std::str::from_utf8(&data).unwrap());
+        while let Some(end) = buffered.find("\n\n") {
+            let message: String = buffered.drain(..end + 2).collect();
+            for line in message.lines() {
+                if let Some(data) = line.strip_prefix("data: ") {
+                    let event: Value = serde_json::from_str(data).unwrap();
+                    let done = until(&event);
+                    events.push(event);
+                    if done {
+                        return events;
+                    }
+                }
+            }
+        }
+    }
+    events
+}
+
+fn kind(event: &Value) -> &str {
+    event["event"].as_object().and_then(|event| event.keys().next()).map(String::as_str).unwrap_or_default()
+}
+
+#[test]
+fn run_workflow_streams_server_sent_events() {
+    let (server, url) = rest_server();
+    server.runtime.block_on(async {
+        let model: Value = serde_json::from_str(SIMPLE_WORKFLOW).unwrap();
+        let mut response = post(&format!("{}/workflows", url), json!({"workflow_model": model})).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/event-stream");
+
+        let events = read_events(&mut response, |_| false).await;
+        assert_eq!(events.first().map(kind), Some("workflow_start"), "{:?}", events);
+        assert_eq!(events.last().map(kind), Some("workflow_success"), "{:?}", events);
+    });
+}
+
+#[test]
+fn invalid_model_is_a_bad_request() {
+    let (server, url) = rest_server();
+    server.runtime.block_on(async {
+        let response = post(&format!("{}/workflows", url), json!({"workflow_model": "not a model"})).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: Value = serde_json::from_slice(&response.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(body["code"], "InvalidArgument");
+    });
+}
+
+#[test]
+fn stop_ends_the_stream_of_a_run() {
+    let (server, url) = rest_server();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut response = post(&format!("{}/workflows", url), json!({"workflow_model": model})).await;
+        let events = read_events(&mut response, |event| kind(event) == "workflow_start").await;
+        let pid = events[0]["event"]["workflow_start"]["pid"].as_str().unwrap().to_owned();
+
+        let stopped = post(&format!("{}/workflows/{}/stop", url, pid), json!({})).await;
+        assert_eq!(stopped.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(&stopped.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(body["success"], true, "{}", body);
+
+        let events = read_events(&mut response, |_| false).await;
+        assert_eq!(events.last().map(kind), Some("workflow_abort"), "{:?}", events);
+
+        let unknown = post(&format!("{}/workflows/unknown/stop", url), json!({})).await;
+        assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+    });
+}
+
+#[test]
+fn gateway_requests_count_against_the_rate_limit() {
+    let (server, url) = rest_server_with(|config| config.rate_limit_per_sec = Some(1));
+    server.runtime.block_on(async {
+        let first = post(&format!("{}/workflows/unknown/stop", url), json!({})).await;
+        assert_eq!(first.status(), StatusCode::NOT_FOUND);
+
+        let limited = post(&format!("{}/workflows/unknown/stop", url), json!({})).await;
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        let body: Value = serde_json::from_slice(&limited.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(body["code"], "ResourceExhausted");
+    });
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use std::time::Duration;
+
+use actflow_server::proto::{
+    ReplayWorkflowRequest, RetryPolicy, RunWorkflowRequest, WorkflowEvent, workflow_event::Event as ProtoEvent,
+};
+use common::{TestServer, blocking_workflow};
+use tonic::{Code, Streaming};
+
+/// A workflow whose request node times out, optionally with retries set in the model
+fn timing_out_workflow(
+    server: &TestServer,
+    model_retries: Option<u64>,
+) -> String {
+    let mut model: serde_json::Value = serde_json::from_str(&blocking_workflow(&server.hanging_http_url())).unwrap();
+    model["nodes"][1]["timeout"] = 50.into();
+    if let Some(times) = model_retries {
+        model["nodes"][1]["retry"] = serde_json::json!({"times": times, "interval": 0});
+    }
+    model.to_string()
+}
+
+async fn collect(mut stream: Streaming<WorkflowEvent>) -> Vec<ProtoEvent> {
+    let mut events = Vec::new();
+    while let Some(WorkflowEvent {
+        event: Some(event),
+        ..
+    }) = tokio::time::timeout(Duration::from_secs(10), stream.message()).await.unwrap().unwrap()
+    {
+        events.push(event);
+    }
+    events
+}
+
+fn retries(events: &[ProtoEvent]) -> usize {
+    events.iter().filter(|event| matches!(event, ProtoEvent::NodeRetry(_))).count()
+}
+
+#[test]
+fn retry_policy_replaces_model_retries() {
+    let server = TestServer::start();
+    let model = timing_out_workflow(&server, Some(5));
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                retry_policy: Some(RetryPolicy {
+                    max_retries: 2,
+                    interval_ms: 10,
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let events = collect(stream).await;
+        assert_eq!(retries(&events), 2, "unexpected events: {:?}", events);
+        assert!(
+            matches!(events.last(), Some(ProtoEvent::WorkflowFailure(_))),
+            "unexpected events: {:?}",
+            events
+        );
+
+        // the replay runs the model as it was run, with the policy applied
+        let ProtoEvent::WorkflowStart(start) = &events[0] else {
+            panic!("expected a workflow start event, got {:?}", events);
+        };
+        let stream = client
+            .replay_workflow(ReplayWorkflowRequest {
+                original_pid: start.pid.clone(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(retries(&collect(stream).await), 2);
+    });
+}
+
+#[test]
+fn model_retries_are_kept_without_policy() {
+    let server = TestServer::start();
+    let model = timing_out_workflow(&server, Some(1));
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(retries(&collect(stream).await), 1);
+    });
+}
+
+#[test]
+fn excessive_retry_policy_is_invalid() {
+    let server = TestServer::start();
+    let model = timing_out_workflow(&server, None);
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let status = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                retry_policy: Some(RetryPolicy {
+                    max_retries: 1000,
+                    interval_ms: 0,
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    });
+}
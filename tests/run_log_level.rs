@@ -0,0 +1,75 @@
+mod common;
+
+use std::fs;
+
+use actflow_server::{
+    config::{LogColor, LogConfig},
+    logger::init_logger,
+    proto::{RunWorkflowRequest, workflow_event::Event as ProtoEvent},
+};
+use common::{SIMPLE_WORKFLOW, TestServer};
+use tonic::Code;
+
+#[test]
+fn run_log_level_only_logs_the_run_that_asked_for_it() {
+    let dir = std::env::temp_dir().join(format!("actflow-run-log-level-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let log_config = LogConfig {
+        level: "warn".to_owned(),
+        log_file: dir.join("server.log").display().to_string(),
+        create_symlink: false,
+        log_to_stderr: false,
+        color: LogColor::Never,
+        ..Default::default()
+    };
+    let logger = init_logger(&log_config).unwrap().start().unwrap();
+
+    let server = TestServer::start();
+    let (debugged, quiet) = server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut pids = Vec::new();
+        for log_level in ["debug", ""] {
+            let mut stream = client
+                .run_workflow(RunWorkflowRequest {
+                    workflow_model: SIMPLE_WORKFLOW.to_owned(),
+                    log_level: log_level.to_owned(),
+                    ..Default::default()
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            let Some(ProtoEvent::WorkflowStart(start)) = stream.message().await.unwrap().unwrap().event else {
+                panic!("expected a workflow start event");
+            };
+            while stream.message().await.unwrap().is_some() {}
+            pids.push(start.pid);
+        }
+
+        let status = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: SIMPLE_WORKFLOW.to_owned(),
+                log_level: "loud".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        (pids[0].clone(), pids[1].clone())
+    });
+    logger.flush();
+
+    let contents: String = fs::read_dir(&dir).unwrap().map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap()).collect();
+    let lines: Vec<_> = contents.lines().filter(|line| line.contains(" DEBUG ")).collect();
+    assert!(
+        lines.iter().any(|line| line.contains(&debugged) && line.contains("workflow_success")),
+        "{}",
+        contents
+    );
+    assert!(!lines.iter().any(|line| line.contains(&quiet)), "{}", contents);
+    // the configured level still holds for everything else
+    assert!(!contents.contains(" INFO "), "{}", contents);
+
+    logger.shutdown();
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,368 @@
+mod common;
+
+use actflow_server::{
+    config::EventOrdering,
+    proto::{RunWorkflowRequest, workflow_event::Event as ProtoEvent},
+};
+use common::{SIMPLE_WORKFLOW, TestServer, blocking_workflow, chain_workflow, run_to_end};
+use std::time::Duration;
+
+use tonic::Code;
+
+/// Short description of an event used to compare event sequences
+fn describe(event: &ProtoEvent) -> String {
+    match event {
+        ProtoEvent::WorkflowStart(_) => "workflow_start".to_owned(),
+        ProtoEvent::WorkflowSuccess(_) => "workflow_success".to_owned(),
+        ProtoEvent::WorkflowFailure(_) => "workflow_failure".to_owned(),
+        ProtoEvent::WorkflowAbort(_) => "workflow_abort".to_owned(),
+        ProtoEvent::WorkflowPause(_) => "workflow_pause".to_owned(),
+        ProtoEvent::WorkflowEngineError(_) => "workflow_engine_error".to_owned(),
+        ProtoEvent::WorkflowProgress(p) => format!("workflow_progress {}/{}", p.completed_nodes, p.total_nodes),
+        ProtoEvent::NodeStart(e) => format!("node_start {}", e.nid),
+        ProtoEvent::NodeRunning(e) => format!("node_running {}", e.nid),
+        ProtoEvent::NodeStopped(e) => format!("node_stopped {}", e.nid),
+        ProtoEvent::NodePaused(e) => format!("node_paused {}", e.nid),
+        ProtoEvent::NodeSkipped(e) => format!("node_skipped {}", e.nid),
+        ProtoEvent::NodeSuccess(e) => format!("node_success {}", e.nid),
+        ProtoEvent::NodeError(e) => format!("node_error {}", e.nid),
+        ProtoEvent::NodeRetry(e) => format!("node_retry {}", e.nid),
+        ProtoEvent::NodeLog(e) => format!("node_log {}", e.nid),
+        ProtoEvent::UnknownEvent(e) => format!("unknown_event {}", e.kind),
+        ProtoEvent::ServerShuttingDown(_) => "server_shutting_down".to_owned(),
+    }
+}
+
+/// Events of [`SIMPLE_WORKFLOW`] in the order they are streamed
+const SIMPLE_WORKFLOW_EVENTS: [&str; 8] = [
+    "workflow_start",
+    "node_running n1",
+    "node_success n1",
+    "workflow_progress 1/2",
+    "node_running n2",
+    "node_success n2",
+    "workflow_progress 2/2",
+    "workflow_success",
+];
+
+#[test]
+fn simple_workflow_event_sequence() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+
+        let described: Vec<_> = events.iter().map(describe).collect();
+        assert_eq!(described, SIMPLE_WORKFLOW_EVENTS);
+
+        // every event belongs to the same process
+        let ProtoEvent::WorkflowStart(start) = &events[0] else {
+            unreachable!();
+        };
+        let ProtoEvent::WorkflowSuccess(success) = &events[events.len() - 1] else {
+            unreachable!();
+        };
+        assert_eq!(start.pid, success.pid);
+    });
+}
+
+#[test]
+fn causal_ordering_keeps_event_sequence() {
+    let server = TestServer::start_with(|config| config.event_ordering = EventOrdering::Causal);
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+
+        let described: Vec<_> = events.iter().map(describe).collect();
+        assert_eq!(described, SIMPLE_WORKFLOW_EVENTS);
+    });
+}
+
+#[test]
+fn listener_with_tcp_backlog_runs_workflows() {
+    let server = TestServer::start_with(|config| config.tcp_backlog = Some(16));
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+
+        let described: Vec<_> = events.iter().map(describe).collect();
+        assert_eq!(described, SIMPLE_WORKFLOW_EVENTS);
+    });
+}
+
+#[test]
+fn node_filter_hides_other_nodes() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: SIMPLE_WORKFLOW.to_owned(),
+                node_filter: vec!["n2".to_owned()],
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        let mut described = Vec::new();
+        while let Some(event) = stream.message().await.unwrap() {
+            described.push(describe(&event.event.unwrap()));
+        }
+        assert_eq!(
+            described,
+            [
+                "workflow_start",
+                "workflow_progress 1/2",
+                "node_running n2",
+                "node_success n2",
+                "workflow_progress 2/2",
+                "workflow_success",
+            ]
+        );
+    });
+}
+
+#[test]
+fn replay_buffer_delivers_every_event_to_a_slow_reader() {
+    let server = TestServer::start_with(|config| config.replay_buffer_size = 1000);
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: chain_workflow(200),
+                replay_buffer: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        // let the run finish before reading anything
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let mut described = Vec::new();
+        while let Some(event) = stream.message().await.unwrap() {
+            described.push(describe(&event.event.unwrap()));
+        }
+        // start, running, success and progress for every node, then the terminal event
+        assert_eq!(described.len(), 1 + 200 * 3 + 1);
+        assert_eq!(described[0], "workflow_start");
+        assert_eq!(described[described.len() - 2], "workflow_progress 200/200");
+        assert_eq!(described[described.len() - 1], "workflow_success");
+    });
+}
+
+#[test]
+fn invalid_model_is_rejected() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let status = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: "not a model".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    });
+}
+
+#[test]
+fn failure_names_the_failing_node() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        // nothing listens on port 1, so the request node fails
+        let events = run_to_end(&mut client, &blocking_workflow("http://127.0.0.1:1/")).await;
+
+        let Some(ProtoEvent::WorkflowFailure(failure)) = events.last() else {
+            panic!("expected a workflow failure event, got {:?}", events);
+        };
+        assert_eq!(failure.failed_nid, "n2");
+    });
+}
+
+#[test]
+fn every_event_is_timestamped() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let before = chrono::Utc::now().timestamp_millis();
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: SIMPLE_WORKFLOW.to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        let mut count = 0;
+        while let Some(event) = stream.message().await.unwrap() {
+            let after = chrono::Utc::now().timestamp_millis();
+            assert!(
+                (before..=after).contains(&event.timestamp),
+                "{:?} was stamped {}",
+                event.event,
+                event.timestamp
+            );
+            count += 1;
+        }
+        assert_eq!(count, SIMPLE_WORKFLOW_EVENTS.len());
+    });
+}
+
+#[test]
+fn workflow_start_names_the_server_instance() {
+    let server = TestServer::start_with(|config| config.instance_id = Some("instance-3".to_owned()));
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        let Some(ProtoEvent::WorkflowStart(start)) = events.first() else {
+            panic!("expected a workflow start event, got {:?}", events.first());
+        };
+        assert_eq!(start.instance_id, "instance-3");
+    });
+}
+
+#[test]
+fn events_are_numbered_in_stream_order() {
+    let server = TestServer::start_with(|config| config.event_ordering = EventOrdering::Causal);
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: chain_workflow(20),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        let mut seqs = Vec::new();
+        while let Some(event) = stream.message().await.unwrap() {
+            seqs.push(event.seq);
+        }
+        let expected: Vec<u64> = (1..=seqs.len() as u64).collect();
+        assert_eq!(seqs, expected);
+    });
+}
+
+#[test]
+fn deeply_nested_model_is_rejected() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let depth = 100_000;
+        let model = format!(r#"{{"id": "nested", "env": {}{}}}"#, "[".repeat(depth), "]".repeat(depth));
+        let status = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert!(status.message().contains("nested"), "{}", status.message());
+
+        // the server is still serving
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+    });
+}
+
+#[test]
+fn node_start_captures_the_node_inputs_when_enabled() {
+    let server = TestServer::start_with(|config| config.capture_node_inputs = true);
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        let starts: Vec<_> = events
+            .iter()
+            .enumerate()
+            .filter_map(|(i, event)| match event {
+                ProtoEvent::NodeStart(start) => Some((i, start)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(starts.len(), 2);
+        for (i, start) in starts {
+            // right before the node runs
+            assert!(matches!(&events[i + 1], ProtoEvent::NodeRunning(running) if running.nid == start.nid));
+            assert_eq!(start.action, "{}");
+        }
+        // only the nodes finished before the node ran
+        let outputs: Vec<serde_json::Value> = events
+            .iter()
+            .filter_map(|event| match event {
+                ProtoEvent::NodeStart(start) => Some(serde_json::from_str(&start.outputs).unwrap()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(outputs, [serde_json::json!({}), serde_json::json!({"n1": {}})]);
+    });
+
+    // off by default
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        assert!(!events.iter().any(|event| matches!(event, ProtoEvent::NodeStart(_))));
+    });
+}
+
+#[test]
+fn workflow_success_carries_the_outputs_when_asked_for() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let success = |events: Vec<ProtoEvent>| match events.last() {
+            Some(ProtoEvent::WorkflowSuccess(success)) => success.clone(),
+            last => panic!("expected a workflow success event, got {:?}", last),
+        };
+
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: SIMPLE_WORKFLOW.to_owned(),
+                capture_outputs: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let mut events = Vec::new();
+        while let Some(event) = stream.message().await.unwrap() {
+            events.push(event.event.unwrap());
+        }
+        let outputs: serde_json::Value = serde_json::from_str(&success(events).outputs).unwrap();
+        assert_eq!(outputs, serde_json::json!({"n1": {}, "n2": {}}));
+
+        // not asked for
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        assert_eq!(success(events).outputs, "");
+    });
+
+    let server = TestServer::start_with(|config| config.max_captured_output_bytes = 4);
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: SIMPLE_WORKFLOW.to_owned(),
+                capture_outputs: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let mut last = None;
+        while let Some(event) = stream.message().await.unwrap() {
+            last = event.event;
+        }
+        let Some(ProtoEvent::WorkflowSuccess(success)) = last else {
+            panic!("expected a workflow success event, got {:?}", last);
+        };
+        assert!(success.outputs.starts_with("{\"n1…(truncated "), "{}", success.outputs);
+    });
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use std::time::Duration;
+
+use actflow_server::proto::{
+    CancelAllRequest, Empty, RunWorkflowRequest, WorkflowEvent, workflow_service_client::WorkflowServiceClient,
+};
+use common::{TestServer, blocking_workflow};
+use tonic::{Code, Status, Streaming, transport::Channel};
+
+fn request(
+    model: &str,
+    priority: i32,
+) -> RunWorkflowRequest {
+    RunWorkflowRequest {
+        workflow_model: model.to_owned(),
+        priority,
+        ..Default::default()
+    }
+}
+
+async fn wait_for_queued(
+    client: &mut WorkflowServiceClient<Channel>,
+    queued: u32,
+) {
+    for _ in 0..100 {
+        if client.get_server_stats(Empty {}).await.unwrap().into_inner().queued_workflows == queued {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("expected {} queued runs", queued);
+}
+
+fn spawn_run(
+    client: &WorkflowServiceClient<Channel>,
+    request: RunWorkflowRequest,
+) -> tokio::task::JoinHandle<Result<Streaming<WorkflowEvent>, Status>> {
+    let mut client = client.clone();
+    tokio::spawn(async move { client.run_workflow(request).await.map(|response| response.into_inner()) })
+}
+
+#[test]
+fn full_queue_is_resource_exhausted() {
+    let server = TestServer::start_with(|config| {
+        config.max_concurrent_workflows = Some(1);
+        config.max_queued_workflows = 1;
+    });
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let _running = client.run_workflow(request(&model, 0)).await.unwrap();
+        let _queued = spawn_run(&client, request(&model, 0));
+        wait_for_queued(&mut client, 1).await;
+
+        let err = client.run_workflow(request(&model, 0)).await.unwrap_err();
+        assert_eq!(err.code(), Code::ResourceExhausted);
+    });
+}
+
+#[test]
+fn higher_priority_runs_start_first() {
+    let server = TestServer::start_authenticated_with(|config| config.max_concurrent_workflows = Some(1));
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let _running = client.run_workflow(request(&model, 0)).await.unwrap();
+
+        let batch = spawn_run(&client, request(&model, 0));
+        wait_for_queued(&mut client, 1).await;
+        let critical = spawn_run(&client, request(&model, 10));
+        wait_for_queued(&mut client, 2).await;
+
+        // frees the only slot
+        client
+            .cancel_all(CancelAllRequest {
+                reason: "make room".to_owned(),
+            })
+            .await
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), critical).await.unwrap().unwrap().unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!batch.is_finished());
+        wait_for_queued(&mut client, 1).await;
+    });
+}
@@ -0,0 +1,12 @@
+mod common;
+
+use actflow_server::proto::Empty;
+use common::TestServer;
+
+#[test]
+fn server_serves_after_passing_self_test() {
+    let server = TestServer::start_with(|config| config.self_test = true);
+    server.runtime.block_on(async {
+        server.client().await.get_version(Empty {}).await.unwrap();
+    });
+}
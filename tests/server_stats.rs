@@ -0,0 +1,40 @@
+mod common;
+
+use std::time::Duration;
+
+use actflow_server::proto::{Empty, workflow_event::Event as ProtoEvent};
+use common::{SIMPLE_WORKFLOW, TestServer, blocking_workflow, run_to_end};
+
+#[test]
+fn server_stats_count_runs_by_outcome() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+        // nothing listens on port 1, so the request node fails
+        let events = run_to_end(&mut client, &blocking_workflow("http://127.0.0.1:1/")).await;
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowFailure(_))));
+
+        // runs are counted right after their terminal event was sent
+        let mut stats = client.get_server_stats(Empty {}).await.unwrap().into_inner();
+        for _ in 0..50 {
+            if stats.active_workflows == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            stats = client.get_server_stats(Empty {}).await.unwrap().into_inner();
+        }
+
+        assert_eq!(stats.active_workflows, 0);
+        assert_eq!(stats.started, 2);
+        assert_eq!(stats.succeeded, 1);
+        assert_eq!(stats.failed, 1);
+        assert_eq!(stats.aborted, 0);
+        assert_eq!(stats.queued_events, 0);
+        assert!(stats.uptime_ms > 0);
+        // the test runtime runs two worker threads
+        assert_eq!(stats.worker_threads, 2);
+    });
+}
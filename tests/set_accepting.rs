@@ -0,0 +1,43 @@
+mod common;
+
+use actflow_server::proto::{RunWorkflowRequest, SetAcceptingRequest, workflow_event::Event as ProtoEvent};
+use common::{SIMPLE_WORKFLOW, TestServer, run_to_end};
+use tonic::Code;
+
+#[test]
+fn quiesced_server_rejects_new_runs() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+
+        let res = client
+            .set_accepting(SetAcceptingRequest {
+                accepting: false,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(res.was_accepting);
+
+        let status = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: SIMPLE_WORKFLOW.to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable);
+
+        let res = client
+            .set_accepting(SetAcceptingRequest {
+                accepting: true,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!res.was_accepting);
+
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        assert!(matches!(events.last(), Some(ProtoEvent::WorkflowSuccess(_))));
+    });
+}
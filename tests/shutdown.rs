@@ -0,0 +1,148 @@
+mod common;
+
+use std::time::Duration;
+
+use actflow_server::{
+    common::shutdown::Shutdown,
+    proto::{RunWorkflowRequest, SubscribeWorkflowRequest, workflow_event::Event as ProtoEvent},
+};
+use common::{TestServer, blocking_workflow};
+use tokio::task::JoinSet;
+
+#[test]
+fn running_streams_end_with_a_shutdown_notice() {
+    let mut server = TestServer::start();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.clone().block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let Some(ProtoEvent::WorkflowStart(start)) = stream.message().await.unwrap().unwrap().event else {
+            panic!("expected a workflow start event");
+        };
+        let mut subscription = client
+            .subscribe_workflow(SubscribeWorkflowRequest {
+                pid: start.pid.clone(),
+                snapshot: false,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        server.shutdown();
+
+        let mut last = None;
+        while let Some(event) = stream.message().await.unwrap() {
+            last = event.event;
+        }
+        let Some(ProtoEvent::ServerShuttingDown(notice)) = last else {
+            panic!("expected the stream to end with a shutdown notice, got {:?}", last);
+        };
+        assert_eq!(notice.pid, start.pid);
+        assert_eq!(notice.reason, "manual");
+
+        let mut last = None;
+        while let Some(event) = subscription.message().await.unwrap() {
+            last = event.event;
+        }
+        assert!(matches!(last, Some(ProtoEvent::ServerShuttingDown(_))));
+
+        server.wait().await.unwrap();
+    });
+}
+
+#[test]
+fn shutdown_notice_comes_before_the_engine_stops_the_run() {
+    let mut server = TestServer::start();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.clone().block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        // the blocking node is running, so the run only ends if something stops it
+        loop {
+            let event = stream.message().await.unwrap().expect("the stream ended before the node started").event;
+            if matches!(event, Some(ProtoEvent::NodeRunning(_))) {
+                break;
+            }
+        }
+
+        server.shutdown();
+
+        let mut rest = Vec::new();
+        while let Some(event) = stream.message().await.unwrap() {
+            rest.extend(event.event);
+        }
+        assert!(
+            matches!(rest.last(), Some(ProtoEvent::ServerShuttingDown(_))),
+            "expected the stream to end with a shutdown notice, got {:?}",
+            rest
+        );
+        assert!(
+            !rest.iter().any(|event| matches!(
+                event,
+                ProtoEvent::WorkflowAbort(_)
+                    | ProtoEvent::WorkflowFailure(_)
+                    | ProtoEvent::WorkflowEngineError(_)
+                    | ProtoEvent::NodeStopped(_)
+                    | ProtoEvent::NodeError(_)
+            )),
+            "the run was stopped before its client was told about the shutdown: {:?}",
+            rest
+        );
+
+        server.wait().await.unwrap();
+    });
+}
+
+#[test]
+fn every_waiter_observes_a_single_shutdown() {
+    let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(8).enable_all().build().unwrap();
+    runtime.block_on(async {
+        for round in 0..50 {
+            let shutdown = Shutdown::new();
+            let mut waiters = JoinSet::new();
+            for _ in 0..500 {
+                waiters.spawn(shutdown.wait());
+            }
+            // a second batch is still being created on other threads while the shutdown happens
+            let racing = {
+                let shutdown = shutdown.clone();
+                tokio::spawn(async move {
+                    let mut waiters = JoinSet::new();
+                    for _ in 0..500 {
+                        waiters.spawn(shutdown.wait());
+                        tokio::task::yield_now().await;
+                    }
+                    waiters
+                })
+            };
+            tokio::task::yield_now().await;
+            shutdown.shutdown();
+            let mut racing = racing.await.unwrap();
+
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while let Some(res) = waiters.join_next().await {
+                    res.unwrap();
+                }
+                while let Some(res) = racing.join_next().await {
+                    res.unwrap();
+                }
+            })
+            .await
+            .unwrap_or_else(|_| panic!("a waiter missed the shutdown in round {}", round));
+        }
+    });
+}
@@ -0,0 +1,231 @@
+mod common;
+
+use actflow_server::proto::{CancelAllRequest, RunWorkflowRequest, StopWorkflowRequest, workflow_event::Event as ProtoEvent};
+use common::{SIMPLE_WORKFLOW, TestServer, blocking_workflow, run_to_end};
+use tonic::Code;
+
+#[test]
+fn stop_unknown_pid_is_not_found() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let status = client
+            .stop_workflow(StopWorkflowRequest {
+                pid: "unknown".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    });
+}
+
+#[test]
+fn stop_finished_pid_is_not_running() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let events = run_to_end(&mut client, SIMPLE_WORKFLOW).await;
+        let Some(ProtoEvent::WorkflowStart(start)) = events.first() else {
+            panic!("expected a workflow start event, got {:?}", events);
+        };
+
+        let res = client
+            .stop_workflow(StopWorkflowRequest {
+                pid: start.pid.clone(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!res.success);
+        assert!(res.err_msg.contains("not running"), "unexpected err_msg: {}", res.err_msg);
+    });
+}
+
+#[test]
+fn stop_by_wid_stops_the_running_process() {
+    let server = TestServer::start();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        stream.message().await.unwrap().unwrap();
+
+        let res = client
+            .stop_workflow(StopWorkflowRequest {
+                wid: "blocking".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(res.success, "unexpected err_msg: {}", res.err_msg);
+        assert_eq!(res.stopped, 1);
+
+        let status = client
+            .stop_workflow(StopWorkflowRequest {
+                wid: "blocking".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    });
+}
+
+#[test]
+fn stop_by_ambiguous_wid_is_refused() {
+    let server = TestServer::start_authenticated();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let request = RunWorkflowRequest {
+            workflow_model: model,
+            ..Default::default()
+        };
+        let mut first = client.run_workflow(request.clone()).await.unwrap().into_inner();
+        first.message().await.unwrap().unwrap();
+        let mut second = client.run_workflow(request).await.unwrap().into_inner();
+        second.message().await.unwrap().unwrap();
+
+        let status = client
+            .stop_workflow(StopWorkflowRequest {
+                wid: "blocking".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+
+        let stopped = client
+            .cancel_all(CancelAllRequest {
+                reason: "test done".to_owned(),
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .stopped;
+        assert_eq!(stopped, 2);
+    });
+}
+
+#[test]
+fn racing_stops_end_each_stream_with_one_terminal_event() {
+    let server = TestServer::start_authenticated();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut runs = Vec::new();
+        for _ in 0..8 {
+            let mut stream = client
+                .run_workflow(RunWorkflowRequest {
+                    workflow_model: model.clone(),
+                    ..Default::default()
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            let Some(ProtoEvent::WorkflowStart(start)) = stream.message().await.unwrap().unwrap().event else {
+                panic!("expected a workflow start event");
+            };
+            runs.push((start.pid, stream));
+        }
+
+        // every run is stopped twice at once, by its pid and by the cancel-all
+        let mut stops = Vec::new();
+        for (pid, _) in &runs {
+            let mut client = client.clone();
+            let pid = pid.clone();
+            stops.push(tokio::spawn(async move {
+                client
+                    .stop_workflow(StopWorkflowRequest {
+                        pid,
+                        ..Default::default()
+                    })
+                    .await
+            }));
+        }
+        client
+            .cancel_all(CancelAllRequest {
+                reason: "racing the stops".to_owned(),
+            })
+            .await
+            .unwrap();
+        for stop in stops {
+            let _ = stop.await.unwrap();
+        }
+
+        for (pid, mut stream) in runs {
+            let mut events = Vec::new();
+            while let Some(event) = stream.message().await.unwrap() {
+                events.extend(event.event);
+            }
+            let terminals = events
+                .iter()
+                .filter(|event| {
+                    matches!(
+                        event,
+                        ProtoEvent::WorkflowSuccess(_)
+                            | ProtoEvent::WorkflowFailure(_)
+                            | ProtoEvent::WorkflowAbort(_)
+                            | ProtoEvent::WorkflowEngineError(_)
+                    )
+                })
+                .count();
+            assert_eq!(terminals, 1, "run {} got {:?}", pid, events);
+            assert!(
+                matches!(events.last(), Some(ProtoEvent::WorkflowAbort(_))),
+                "run {} got {:?}",
+                pid,
+                events
+            );
+        }
+    });
+}
+
+#[test]
+fn cancel_all_requires_a_client_certificate() {
+    let server = TestServer::start();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .run_workflow(RunWorkflowRequest {
+                workflow_model: model,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let Some(ProtoEvent::WorkflowStart(start)) = stream.message().await.unwrap().unwrap().event else {
+            panic!("expected a workflow start event");
+        };
+
+        let status = client
+            .cancel_all(CancelAllRequest {
+                reason: "anyone".to_owned(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        // the run was left alone
+        let stopped = client
+            .stop_workflow(StopWorkflowRequest {
+                pid: start.pid,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(stopped.success, "{}", stopped.err_msg);
+    });
+}
@@ -0,0 +1,174 @@
+mod common;
+
+use actflow_server::proto::{
+    RunWorkflowRequest, StopWorkflowRequest, SubscribeWorkflowRequest, WorkflowEvent, workflow_event::Event as ProtoEvent,
+};
+use common::{TestServer, blocking_workflow};
+use std::time::Duration;
+use tokio::time::Instant;
+use tonic::{Code, Streaming};
+
+/// Short description of an event used to compare event sequences
+fn describe(event: &WorkflowEvent) -> String {
+    match event.event.as_ref().unwrap() {
+        ProtoEvent::WorkflowStart(_) => "workflow_start".to_owned(),
+        ProtoEvent::WorkflowAbort(_) => "workflow_abort".to_owned(),
+        ProtoEvent::WorkflowProgress(p) => format!("workflow_progress {}/{}", p.completed_nodes, p.total_nodes),
+        ProtoEvent::NodeRunning(e) => format!("node_running {}", e.nid),
+        ProtoEvent::NodeSuccess(e) => format!("node_success {}", e.nid),
+        event => format!("{:?}", event),
+    }
+}
+
+/// Starts the blocking workflow and reads its stream until the request node is running, returns the pid
+async fn start_blocking(
+    server: &TestServer,
+    model: String,
+) -> (String, Streaming<WorkflowEvent>) {
+    let mut client = server.client().await;
+    let mut stream = client
+        .run_workflow(RunWorkflowRequest {
+            workflow_model: model,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let Some(ProtoEvent::WorkflowStart(start)) = stream.message().await.unwrap().unwrap().event else {
+        panic!("expected a workflow start event");
+    };
+    while let Some(event) = stream.message().await.unwrap() {
+        if describe(&event) == "node_running n2" {
+            break;
+        }
+    }
+    (start.pid, stream)
+}
+
+async fn read_to_end(stream: &mut Streaming<WorkflowEvent>) -> Vec<String> {
+    let mut described = Vec::new();
+    while let Some(event) = stream.message().await.unwrap() {
+        described.push(describe(&event));
+    }
+    described
+}
+
+#[test]
+fn subscribe_to_unknown_pid_is_not_found() {
+    let server = TestServer::start();
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let status = client
+            .subscribe_workflow(SubscribeWorkflowRequest {
+                pid: "unknown".to_owned(),
+                snapshot: true,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    });
+}
+
+#[test]
+fn snapshot_sends_the_current_state_before_live_events() {
+    let server = TestServer::start();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let (pid, mut run_stream) = start_blocking(&server, model).await;
+
+        let mut client = server.client().await;
+        let mut stream = client
+            .subscribe_workflow(SubscribeWorkflowRequest {
+                pid: pid.clone(),
+                snapshot: true,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        client
+            .stop_workflow(StopWorkflowRequest {
+                pid,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            read_to_end(&mut stream).await,
+            ["workflow_start", "node_success n1", "node_running n2", "workflow_progress 1/3", "workflow_abort",]
+        );
+        assert_eq!(
+            read_to_end(&mut run_stream).await.last().map(String::as_str),
+            Some("workflow_abort")
+        );
+    });
+}
+
+#[test]
+fn subscribe_without_snapshot_only_streams_live_events() {
+    let server = TestServer::start();
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let (pid, _run_stream) = start_blocking(&server, model).await;
+
+        let mut client = server.client().await;
+        let mut stream = client
+            .subscribe_workflow(SubscribeWorkflowRequest {
+                pid: pid.clone(),
+                snapshot: false,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        client
+            .stop_workflow(StopWorkflowRequest {
+                pid: pid.clone(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(read_to_end(&mut stream).await, ["workflow_abort"]);
+
+        // finished processes cannot be followed
+        let status = client
+            .subscribe_workflow(SubscribeWorkflowRequest {
+                pid,
+                snapshot: true,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    });
+}
+
+#[test]
+fn subscribers_beyond_the_limit_are_rejected_until_one_leaves() {
+    let server = TestServer::start_with(|config| config.max_subscribers_per_workflow = 1);
+    let model = blocking_workflow(&server.hanging_http_url());
+    server.runtime.block_on(async {
+        let (pid, _stream) = start_blocking(&server, model).await;
+        let mut client = server.client().await;
+        let request = SubscribeWorkflowRequest {
+            pid: pid.clone(),
+            snapshot: false,
+        };
+
+        let first = client.subscribe_workflow(request.clone()).await.unwrap();
+        let status = client.subscribe_workflow(request.clone()).await.unwrap_err();
+        assert_eq!(status.code(), Code::ResourceExhausted);
+
+        // the slot frees up once the server notices the first subscriber went away
+        drop(first);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            match client.subscribe_workflow(request.clone()).await {
+                Ok(_) => break,
+                Err(status) if status.code() == Code::ResourceExhausted && Instant::now() < deadline => {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+                Err(status) => panic!("subscription still rejected: {}", status),
+            }
+        }
+    });
+}
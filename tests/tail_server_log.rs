@@ -0,0 +1,65 @@
+mod common;
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::Duration,
+};
+
+use actflow_server::proto::{LogLine, TailRequest};
+use common::TestServer;
+use tonic::{Code, Streaming};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("actflow-server-tail-{}-{}", std::process::id(), name))
+}
+
+async fn next_line(stream: &mut Streaming<LogLine>) -> String {
+    tokio::time::timeout(Duration::from_secs(5), stream.message()).await.unwrap().unwrap().unwrap().line
+}
+
+#[test]
+fn tail_streams_last_lines_then_appends() {
+    let log_file = temp_path("server.log");
+    fs::write(&log_file, "first\nsecond\nthird\n").unwrap();
+
+    let server = TestServer::start_authenticated_with(|config| config.server_log_file = Some(log_file.clone()));
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let mut stream = client
+            .tail_server_log(TailRequest {
+                lines: 2,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(next_line(&mut stream).await, "second");
+        assert_eq!(next_line(&mut stream).await, "third");
+
+        writeln!(OpenOptions::new().append(true).open(&log_file).unwrap(), "fourth").unwrap();
+        assert_eq!(next_line(&mut stream).await, "fourth");
+    });
+
+    let _ = fs::remove_file(&log_file);
+}
+
+#[test]
+fn tail_requires_a_client_certificate() {
+    let log_file = temp_path("unauthenticated.log");
+    fs::write(&log_file, "secret\n").unwrap();
+
+    let server = TestServer::start_with(|config| config.server_log_file = Some(log_file.clone()));
+    server.runtime.block_on(async {
+        let mut client = server.client().await;
+        let status = client
+            .tail_server_log(TailRequest {
+                lines: 1,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    });
+
+    let _ = fs::remove_file(&log_file);
+}
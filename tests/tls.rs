@@ -0,0 +1,65 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use actflow_server::{
+    config::TlsConfig,
+    proto::{Empty, workflow_service_client::WorkflowServiceClient},
+    server::ServerError,
+};
+use common::TestServer;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig};
+
+/// Self-signed certificate and key for "localhost" in PEM
+fn self_signed() -> (String, String) {
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+    (certified.cert.pem(), certified.signing_key.serialize_pem())
+}
+
+async fn connect(
+    server: &TestServer,
+    ca_pem: &str,
+) -> WorkflowServiceClient<Channel> {
+    let tls = ClientTlsConfig::new().ca_certificate(Certificate::from_pem(ca_pem)).domain_name("localhost");
+    let endpoint = Channel::from_shared(server.addr.replace("http://", "https://")).unwrap().tls_config(tls).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        match endpoint.connect().await {
+            Ok(channel) => return WorkflowServiceClient::new(channel),
+            Err(e) if Instant::now() > deadline => panic!("server did not come up: {}", e),
+            Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    }
+}
+
+#[test]
+fn tls_identity_can_be_inline_pem() {
+    let (cert_pem, key_pem) = self_signed();
+    let server = TestServer::start_with(|config| {
+        config.tls = Some(TlsConfig {
+            cert_pem: cert_pem.clone(),
+            key_pem: key_pem.clone(),
+            ..Default::default()
+        })
+    });
+    server.runtime.block_on(async {
+        let mut client = connect(&server, &cert_pem).await;
+        client.get_version(Empty {}).await.unwrap();
+    });
+}
+
+#[test]
+fn tls_without_certificate_fails_to_start() {
+    let (_, key_pem) = self_signed();
+    let mut server = TestServer::start_with(|config| {
+        config.tls = Some(TlsConfig {
+            key_pem,
+            ..Default::default()
+        })
+    });
+    let res = server.runtime.clone().block_on(server.wait());
+    match res {
+        Err(ServerError::Tls(msg)) => assert_eq!(msg, "cert-file or cert-pem is required"),
+        other => panic!("expected a tls error, got {:?}", other),
+    }
+}